@@ -0,0 +1,68 @@
+-- Base schema for H1B customer management.
+-- Written to be safe against databases that were set up by hand from
+-- src/DB-Quary.sql before migrations existed.
+
+CREATE EXTENSION IF NOT EXISTS pgcrypto;
+
+CREATE SCHEMA IF NOT EXISTS global_visa_mgmt;
+
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM pg_type t JOIN pg_namespace n ON n.oid = t.typnamespace
+        WHERE n.nspname = 'global_visa_mgmt' AND t.typname = 'sex_enum'
+    ) THEN
+        CREATE TYPE global_visa_mgmt.sex_enum AS ENUM ('Male', 'Female', 'Other');
+    END IF;
+
+    IF NOT EXISTS (
+        SELECT 1 FROM pg_type t JOIN pg_namespace n ON n.oid = t.typnamespace
+        WHERE n.nspname = 'global_visa_mgmt' AND t.typname = 'marital_status_enum'
+    ) THEN
+        CREATE TYPE global_visa_mgmt.marital_status_enum AS ENUM ('Single', 'Married', 'Divorced');
+    END IF;
+
+    IF NOT EXISTS (
+        SELECT 1 FROM pg_type t JOIN pg_namespace n ON n.oid = t.typnamespace
+        WHERE n.nspname = 'global_visa_mgmt' AND t.typname = 'h1b_status_enum'
+    ) THEN
+        CREATE TYPE global_visa_mgmt.h1b_status_enum AS ENUM ('Active', 'Inactive');
+    END IF;
+END
+$$;
+
+CREATE TABLE IF NOT EXISTS global_visa_mgmt.h1bcustomer (
+    customer_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    email VARCHAR(255) NOT NULL,
+    first_name VARCHAR(100) NOT NULL,
+    last_name VARCHAR(100) NOT NULL,
+    dob DATE NOT NULL,
+    sex global_visa_mgmt.sex_enum NOT NULL,
+    marital_status global_visa_mgmt.marital_status_enum NOT NULL,
+    phone VARCHAR(25) NOT NULL,
+    emergency_contact_name VARCHAR(100) NOT NULL,
+    emergency_contact_phone VARCHAR(25) NOT NULL,
+    employment_start_date DATE NOT NULL,
+    street_name VARCHAR(255) NOT NULL,
+    city VARCHAR(100) NOT NULL,
+    state VARCHAR(100) NOT NULL,
+    zip VARCHAR(20) NOT NULL,
+    client_name VARCHAR(255) NOT NULL,
+    client_street_name VARCHAR(255) NOT NULL,
+    client_city VARCHAR(100) NOT NULL,
+    client_state VARCHAR(100) NOT NULL,
+    client_zip VARCHAR(20) NOT NULL,
+    lca_title VARCHAR(255) NOT NULL,
+    lca_salary DECIMAL(15,2) NOT NULL,
+    lca_code VARCHAR(50) NOT NULL,
+    receipt_number VARCHAR(100) NOT NULL,
+    h1b_start_date DATE NOT NULL,
+    h1b_end_date DATE NOT NULL,
+    login_email VARCHAR(255) NOT NULL,
+    h1b_status global_visa_mgmt.h1b_status_enum DEFAULT 'Active'
+);
+
+-- Lookups used by the list and by-email endpoints
+CREATE INDEX IF NOT EXISTS h1bcustomer_email_idx ON global_visa_mgmt.h1bcustomer (email);
+CREATE INDEX IF NOT EXISTS h1bcustomer_login_email_idx ON global_visa_mgmt.h1bcustomer (login_email);
+CREATE INDEX IF NOT EXISTS h1bcustomer_h1b_status_idx ON global_visa_mgmt.h1bcustomer (h1b_status);
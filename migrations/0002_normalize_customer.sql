@@ -0,0 +1,91 @@
+-- Split the flat h1bcustomer row into one table per concern. Every part is
+-- keyed by the person's customer_id. The old table name becomes a view that
+-- joins the parts back together for the flat endpoints.
+
+CREATE TABLE global_visa_mgmt.person (
+    customer_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    email VARCHAR(255) NOT NULL,
+    login_email VARCHAR(255) NOT NULL,
+    first_name VARCHAR(100) NOT NULL,
+    last_name VARCHAR(100) NOT NULL,
+    dob DATE NOT NULL,
+    sex global_visa_mgmt.sex_enum NOT NULL,
+    marital_status global_visa_mgmt.marital_status_enum NOT NULL,
+    phone VARCHAR(25) NOT NULL,
+    emergency_contact_name VARCHAR(100) NOT NULL,
+    emergency_contact_phone VARCHAR(25) NOT NULL,
+    employment_start_date DATE NOT NULL,
+    h1b_status global_visa_mgmt.h1b_status_enum NOT NULL DEFAULT 'Active'
+);
+
+CREATE TABLE global_visa_mgmt.residential_address (
+    customer_id UUID PRIMARY KEY REFERENCES global_visa_mgmt.person (customer_id) ON DELETE CASCADE,
+    street_name VARCHAR(255) NOT NULL,
+    city VARCHAR(100) NOT NULL,
+    state VARCHAR(100) NOT NULL,
+    zip VARCHAR(20) NOT NULL
+);
+
+CREATE TABLE global_visa_mgmt.client_placement (
+    customer_id UUID PRIMARY KEY REFERENCES global_visa_mgmt.person (customer_id) ON DELETE CASCADE,
+    client_name VARCHAR(255) NOT NULL,
+    client_street_name VARCHAR(255) NOT NULL,
+    client_city VARCHAR(100) NOT NULL,
+    client_state VARCHAR(100) NOT NULL,
+    client_zip VARCHAR(20) NOT NULL
+);
+
+CREATE TABLE global_visa_mgmt.lca (
+    customer_id UUID PRIMARY KEY REFERENCES global_visa_mgmt.person (customer_id) ON DELETE CASCADE,
+    lca_title VARCHAR(255) NOT NULL,
+    lca_salary DECIMAL(15,2) NOT NULL,
+    lca_code VARCHAR(50) NOT NULL
+);
+
+CREATE TABLE global_visa_mgmt.petition (
+    customer_id UUID PRIMARY KEY REFERENCES global_visa_mgmt.person (customer_id) ON DELETE CASCADE,
+    receipt_number VARCHAR(100) NOT NULL,
+    h1b_start_date DATE NOT NULL,
+    h1b_end_date DATE NOT NULL
+);
+
+INSERT INTO global_visa_mgmt.person (
+    customer_id, email, login_email, first_name, last_name, dob, sex, marital_status, phone,
+    emergency_contact_name, emergency_contact_phone, employment_start_date, h1b_status
+)
+SELECT customer_id, email, login_email, first_name, last_name, dob, sex, marital_status, phone,
+    emergency_contact_name, emergency_contact_phone, employment_start_date, COALESCE(h1b_status, 'Active')
+FROM global_visa_mgmt.h1bcustomer;
+
+INSERT INTO global_visa_mgmt.residential_address (customer_id, street_name, city, state, zip)
+SELECT customer_id, street_name, city, state, zip FROM global_visa_mgmt.h1bcustomer;
+
+INSERT INTO global_visa_mgmt.client_placement (customer_id, client_name, client_street_name, client_city, client_state, client_zip)
+SELECT customer_id, client_name, client_street_name, client_city, client_state, client_zip FROM global_visa_mgmt.h1bcustomer;
+
+INSERT INTO global_visa_mgmt.lca (customer_id, lca_title, lca_salary, lca_code)
+SELECT customer_id, lca_title, lca_salary, lca_code FROM global_visa_mgmt.h1bcustomer;
+
+INSERT INTO global_visa_mgmt.petition (customer_id, receipt_number, h1b_start_date, h1b_end_date)
+SELECT customer_id, receipt_number, h1b_start_date, h1b_end_date FROM global_visa_mgmt.h1bcustomer;
+
+DROP TABLE global_visa_mgmt.h1bcustomer;
+
+CREATE VIEW global_visa_mgmt.h1bcustomer AS
+SELECT
+    p.customer_id, p.email, p.first_name, p.last_name, p.dob, p.sex, p.marital_status, p.phone,
+    p.emergency_contact_name, p.emergency_contact_phone, p.employment_start_date,
+    a.street_name, a.city, a.state, a.zip,
+    c.client_name, c.client_street_name, c.client_city, c.client_state, c.client_zip,
+    l.lca_title, l.lca_salary, l.lca_code,
+    t.receipt_number, t.h1b_start_date, t.h1b_end_date,
+    p.login_email, p.h1b_status
+FROM global_visa_mgmt.person p
+JOIN global_visa_mgmt.residential_address a ON a.customer_id = p.customer_id
+JOIN global_visa_mgmt.client_placement c ON c.customer_id = p.customer_id
+JOIN global_visa_mgmt.lca l ON l.customer_id = p.customer_id
+JOIN global_visa_mgmt.petition t ON t.customer_id = p.customer_id;
+
+CREATE INDEX person_email_idx ON global_visa_mgmt.person (email);
+CREATE INDEX person_login_email_idx ON global_visa_mgmt.person (login_email);
+CREATE INDEX person_h1b_status_idx ON global_visa_mgmt.person (h1b_status);
@@ -0,0 +1,20 @@
+-- Row version for optimistic concurrency control. Bumped on every change to
+-- any part of the customer and exposed as the ETag of the flat record.
+
+ALTER TABLE global_visa_mgmt.person ADD COLUMN version BIGINT NOT NULL DEFAULT 1;
+
+CREATE OR REPLACE VIEW global_visa_mgmt.h1bcustomer AS
+SELECT
+    p.customer_id, p.email, p.first_name, p.last_name, p.dob, p.sex, p.marital_status, p.phone,
+    p.emergency_contact_name, p.emergency_contact_phone, p.employment_start_date,
+    a.street_name, a.city, a.state, a.zip,
+    c.client_name, c.client_street_name, c.client_city, c.client_state, c.client_zip,
+    l.lca_title, l.lca_salary, l.lca_code,
+    t.receipt_number, t.h1b_start_date, t.h1b_end_date,
+    p.login_email, p.h1b_status,
+    p.version
+FROM global_visa_mgmt.person p
+JOIN global_visa_mgmt.residential_address a ON a.customer_id = p.customer_id
+JOIN global_visa_mgmt.client_placement c ON c.customer_id = p.customer_id
+JOIN global_visa_mgmt.lca l ON l.customer_id = p.customer_id
+JOIN global_visa_mgmt.petition t ON t.customer_id = p.customer_id;
@@ -0,0 +1,18 @@
+-- Full change history: one snapshot of the flat record per version, with
+-- who made the change and through which operation.
+
+CREATE TABLE global_visa_mgmt.customer_revision (
+    customer_id UUID NOT NULL REFERENCES global_visa_mgmt.person (customer_id) ON DELETE CASCADE,
+    version BIGINT NOT NULL,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    actor TEXT,
+    action TEXT NOT NULL,
+    snapshot JSONB NOT NULL,
+    PRIMARY KEY (customer_id, version)
+);
+
+-- Existing records start their history at the current state. Salaries are
+-- stored as strings, the way the API serializes decimals.
+INSERT INTO global_visa_mgmt.customer_revision (customer_id, version, actor, action, snapshot)
+SELECT c.customer_id, c.version, NULL, 'baseline', to_jsonb(c) || jsonb_build_object('lca_salary', c.lca_salary::text)
+FROM global_visa_mgmt.h1bcustomer c;
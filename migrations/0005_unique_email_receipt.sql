@@ -0,0 +1,6 @@
+-- One customer per email (case-insensitive) and per USCIS receipt number.
+-- Blank values, which legacy updates write for missing fields, are exempt.
+-- Fails if the table already holds duplicates; resolve those first.
+
+CREATE UNIQUE INDEX person_email_key ON global_visa_mgmt.person (lower(email)) WHERE email <> '';
+CREATE UNIQUE INDEX petition_receipt_number_key ON global_visa_mgmt.petition (receipt_number) WHERE receipt_number <> '';
@@ -0,0 +1,11 @@
+-- Trigram indexes for GET /customers/search. They serve both the fuzzy
+-- word-similarity operator (<%) and plain ILIKE substring matches.
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX person_first_name_trgm_idx ON global_visa_mgmt.person USING gin (first_name gin_trgm_ops);
+CREATE INDEX person_last_name_trgm_idx ON global_visa_mgmt.person USING gin (last_name gin_trgm_ops);
+CREATE INDEX person_email_trgm_idx ON global_visa_mgmt.person USING gin (email gin_trgm_ops);
+CREATE INDEX person_login_email_trgm_idx ON global_visa_mgmt.person USING gin (login_email gin_trgm_ops);
+CREATE INDEX client_placement_client_name_trgm_idx ON global_visa_mgmt.client_placement USING gin (client_name gin_trgm_ops);
+CREATE INDEX petition_receipt_number_trgm_idx ON global_visa_mgmt.petition USING gin (receipt_number gin_trgm_ops);
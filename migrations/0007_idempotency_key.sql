@@ -0,0 +1,14 @@
+-- Creates made with an Idempotency-Key header, so that a retried request
+-- returns the customer it already created instead of creating another.
+-- Keys are scoped to the caller and honoured for 24 hours; an older row is
+-- replaced when its key is reused. customer_id is filled in by the same
+-- transaction that claims the key.
+
+CREATE TABLE global_visa_mgmt.idempotency_key (
+    actor TEXT NOT NULL,
+    key TEXT NOT NULL,
+    request JSONB NOT NULL,
+    customer_id UUID REFERENCES global_visa_mgmt.person (customer_id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (actor, key)
+);
@@ -0,0 +1,51 @@
+-- Case lifecycle of the petition, separate from h1b_status, which stays the
+-- record's active/inactive flag. New customers start as Draft; existing ones
+-- all carry receipt numbers, so they start as Filed. The API only moves a
+-- case along the transitions in src/case_status.rs and logs every move.
+
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM pg_type t JOIN pg_namespace n ON n.oid = t.typnamespace
+        WHERE n.nspname = 'global_visa_mgmt' AND t.typname = 'case_status_enum'
+    ) THEN
+        CREATE TYPE global_visa_mgmt.case_status_enum AS ENUM (
+            'Draft', 'Filed', 'RFE Issued', 'RFE Responded', 'Approved', 'Denied', 'Withdrawn', 'Expired', 'Terminated'
+        );
+    END IF;
+END
+$$;
+
+ALTER TABLE global_visa_mgmt.person
+    ADD COLUMN case_status global_visa_mgmt.case_status_enum NOT NULL DEFAULT 'Filed';
+ALTER TABLE global_visa_mgmt.person ALTER COLUMN case_status SET DEFAULT 'Draft';
+
+CREATE OR REPLACE VIEW global_visa_mgmt.h1bcustomer AS
+SELECT
+    p.customer_id, p.email, p.first_name, p.last_name, p.dob, p.sex, p.marital_status, p.phone,
+    p.emergency_contact_name, p.emergency_contact_phone, p.employment_start_date,
+    a.street_name, a.city, a.state, a.zip,
+    c.client_name, c.client_street_name, c.client_city, c.client_state, c.client_zip,
+    l.lca_title, l.lca_salary, l.lca_code,
+    t.receipt_number, t.h1b_start_date, t.h1b_end_date,
+    p.login_email, p.h1b_status,
+    p.version,
+    p.case_status
+FROM global_visa_mgmt.person p
+JOIN global_visa_mgmt.residential_address a ON a.customer_id = p.customer_id
+JOIN global_visa_mgmt.client_placement c ON c.customer_id = p.customer_id
+JOIN global_visa_mgmt.lca l ON l.customer_id = p.customer_id
+JOIN global_visa_mgmt.petition t ON t.customer_id = p.customer_id;
+
+-- One row per status change, keyed like customer_revision by the version
+-- the change produced.
+CREATE TABLE global_visa_mgmt.case_transition (
+    customer_id UUID NOT NULL REFERENCES global_visa_mgmt.person (customer_id) ON DELETE CASCADE,
+    version BIGINT NOT NULL,
+    from_status global_visa_mgmt.case_status_enum NOT NULL,
+    to_status global_visa_mgmt.case_status_enum NOT NULL,
+    reason TEXT NOT NULL,
+    actor TEXT,
+    transitioned_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (customer_id, version)
+);
@@ -0,0 +1,172 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Where a customer's H-1B petition stands. Stored in the
+/// `global_visa_mgmt.case_status_enum` type created by `0008_case_status.sql`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum CaseStatus {
+    /// Being prepared; the status of every new customer.
+    Draft,
+    Filed,
+    #[serde(rename = "RFE Issued")]
+    RfeIssued,
+    #[serde(rename = "RFE Responded")]
+    RfeResponded,
+    Approved,
+    Denied,
+    Withdrawn,
+    /// The approved validity period has ended.
+    Expired,
+    /// Employment ended while the approval was valid.
+    Terminated,
+}
+
+/// Every move a case may make, by current status. A status without moves
+/// is final.
+const TRANSITIONS: [(CaseStatus, &[CaseStatus]); 9] = {
+    use CaseStatus::*;
+    [
+        (Draft, &[Filed, Withdrawn]),
+        (Filed, &[RfeIssued, Approved, Denied, Withdrawn]),
+        (RfeIssued, &[RfeResponded, Denied, Withdrawn]),
+        (RfeResponded, &[RfeIssued, Approved, Denied, Withdrawn]),
+        (Approved, &[Expired, Terminated, Withdrawn]),
+        (Denied, &[]),
+        (Withdrawn, &[]),
+        (Expired, &[]),
+        (Terminated, &[]),
+    ]
+};
+
+impl CaseStatus {
+    /// In lifecycle order, as the enum type declares them.
+    pub const ALL: [CaseStatus; 9] = [
+        CaseStatus::Draft,
+        CaseStatus::Filed,
+        CaseStatus::RfeIssued,
+        CaseStatus::RfeResponded,
+        CaseStatus::Approved,
+        CaseStatus::Denied,
+        CaseStatus::Withdrawn,
+        CaseStatus::Expired,
+        CaseStatus::Terminated,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CaseStatus::Draft => "Draft",
+            CaseStatus::Filed => "Filed",
+            CaseStatus::RfeIssued => "RFE Issued",
+            CaseStatus::RfeResponded => "RFE Responded",
+            CaseStatus::Approved => "Approved",
+            CaseStatus::Denied => "Denied",
+            CaseStatus::Withdrawn => "Withdrawn",
+            CaseStatus::Expired => "Expired",
+            CaseStatus::Terminated => "Terminated",
+        }
+    }
+
+    /// The statuses a case in this one may move to.
+    pub fn next(self) -> &'static [CaseStatus] {
+        TRANSITIONS.iter().find(|(from, _)| *from == self).map_or(&[], |(_, next)| next)
+    }
+
+    pub fn can_move_to(self, to: CaseStatus) -> bool {
+        self.next().contains(&to)
+    }
+}
+
+impl fmt::Display for CaseStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownCaseStatus(pub String);
+
+impl fmt::Display for UnknownCaseStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} is not a case status", self.0)
+    }
+}
+
+impl std::error::Error for UnknownCaseStatus {}
+
+impl FromStr for CaseStatus {
+    type Err = UnknownCaseStatus;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        CaseStatus::ALL
+            .into_iter()
+            .find(|status| status.as_str() == value)
+            .ok_or_else(|| UnknownCaseStatus(value.to_string()))
+    }
+}
+
+/// Reads the `::text` of the Postgres enum.
+impl TryFrom<String> for CaseStatus {
+    type Error = UnknownCaseStatus;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_every_listed_transition() {
+        use CaseStatus::*;
+        let edges = [
+            (Draft, Filed),
+            (Draft, Withdrawn),
+            (Filed, RfeIssued),
+            (Filed, Approved),
+            (Filed, Denied),
+            (Filed, Withdrawn),
+            (RfeIssued, RfeResponded),
+            (RfeIssued, Denied),
+            (RfeIssued, Withdrawn),
+            (RfeResponded, RfeIssued),
+            (RfeResponded, Approved),
+            (RfeResponded, Denied),
+            (RfeResponded, Withdrawn),
+            (Approved, Expired),
+            (Approved, Terminated),
+            (Approved, Withdrawn),
+        ];
+        for from in CaseStatus::ALL {
+            for to in CaseStatus::ALL {
+                assert_eq!(from.can_move_to(to), edges.contains(&(from, to)), "{} -> {}", from, to);
+            }
+        }
+    }
+
+    #[test]
+    fn final_statuses_allow_no_moves() {
+        use CaseStatus::*;
+        for status in [Denied, Withdrawn, Expired, Terminated] {
+            assert!(status.next().is_empty(), "{}", status);
+        }
+    }
+
+    #[test]
+    fn rejects_skipping_ahead() {
+        assert!(!CaseStatus::Draft.can_move_to(CaseStatus::Approved));
+        assert!(!CaseStatus::Draft.can_move_to(CaseStatus::Draft));
+    }
+
+    #[test]
+    fn parses_the_enum_labels() {
+        for status in CaseStatus::ALL {
+            assert_eq!(status.as_str().parse::<CaseStatus>(), Ok(status));
+        }
+        assert_eq!("RfeIssued".parse::<CaseStatus>(), Err(UnknownCaseStatus("RfeIssued".to_string())));
+    }
+}
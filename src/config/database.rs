@@ -1,55 +1,190 @@
-use sqlx::{PgPool, postgres::PgPoolOptions};
-use tokio::sync::OnceCell;
-
-static DB_POOL: OnceCell<PgPool> = OnceCell::const_new();
-
-pub async fn initialize_database() -> Result<(), Box<dyn std::error::Error>> {
-    let db_host = std::env::var("DB_HOST")?;
-    let db_port = std::env::var("DB_PORT")?;
-    let db_user = std::env::var("DB_USER")?;
-    let db_password = std::env::var("DB_PASSWORD")?;
-    let db_name = std::env::var("DB_NAME")?;
-    
-    let database_url = format!("postgresql://{}:{}@{}:{}/{}", db_user, db_password, db_host, db_port, db_name);
-    
+use sqlx::{PgPool, postgres::{PgConnectOptions, PgPoolOptions}, migrate::Migrator};
+
+use super::{env_flag, required_env, ConfigError};
+
+/// Schema migrations from `migrations/`, embedded at compile time.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// How connections reach Postgres. Behind a connection pooler (Supabase
+/// Supavisor, PgBouncer) consecutive queries can land on different backends,
+/// so named prepared statements cannot be reused or may collide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolerMode {
+    /// Direct connection: statements are prepared once and cached.
+    Direct,
+    /// Pooled connection: no statement cache, every query runs as an unnamed
+    /// statement.
+    Pooler,
+}
+
+impl PoolerMode {
+    /// `DB_POOLER_MODE=pooler|direct` forces a mode; otherwise it is inferred
+    /// from a `*.pooler.*` host or the usual pooler ports (6543, 6432).
+    fn from_env(host: &str, port: u16) -> Result<Self, ConfigError> {
+        match std::env::var("DB_POOLER_MODE").map(|v| v.to_ascii_lowercase()).as_deref() {
+            Ok("pooler") | Ok("transaction") => Ok(PoolerMode::Pooler),
+            Ok("direct") | Ok("none") => Ok(PoolerMode::Direct),
+            Ok("auto") | Ok("") | Err(_) => Ok(Self::detect(host, port)),
+            Ok(other) => Err(ConfigError::Invalid("DB_POOLER_MODE", other.to_string())),
+        }
+    }
+
+    fn detect(host: &str, port: u16) -> Self {
+        if host.contains(".pooler.") || port == 6543 || port == 6432 {
+            PoolerMode::Pooler
+        } else {
+            PoolerMode::Direct
+        }
+    }
+
+    /// Whether queries may be kept as named prepared statements.
+    pub fn persistent_statements(self) -> bool {
+        self == PoolerMode::Direct
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DatabaseConfig {
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub password: String,
+    pub name: String,
+    pub max_connections: u32,
+    pub pooler_mode: PoolerMode,
+    /// `DB_MIGRATE_ON_START=true` applies pending migrations before serving.
+    pub migrate_on_start: bool,
+    /// Read replica for list and lookup queries, enabled by `DB_REPLICA_HOST`.
+    pub replica: Option<Box<DatabaseConfig>>,
+}
+
+impl DatabaseConfig {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let max_connections = match std::env::var("DB_MAX_CONNECTIONS") {
+            Ok(value) => value.parse().map_err(|_| ConfigError::Invalid("DB_MAX_CONNECTIONS", value))?,
+            Err(_) => 10,
+        };
+
+        let host = required_env("DB_HOST")?;
+        let port = required_env("DB_PORT")?;
+        let port = port.parse().map_err(|_| ConfigError::Invalid("DB_PORT", port))?;
+        let pooler_mode = PoolerMode::from_env(&host, port)?;
+
+        let mut config = Self {
+            host,
+            port,
+            user: required_env("DB_USER")?,
+            password: required_env("DB_PASSWORD")?,
+            name: required_env("DB_NAME")?,
+            max_connections,
+            pooler_mode,
+            migrate_on_start: env_flag("DB_MIGRATE_ON_START"),
+            replica: None,
+        };
+        config.replica = config.replica_from_env()?.map(Box::new);
+        Ok(config)
+    }
+
+    /// `DB_REPLICA_HOST` plus optional `DB_REPLICA_PORT`, `DB_REPLICA_USER`,
+    /// `DB_REPLICA_PASSWORD`, `DB_REPLICA_NAME` and
+    /// `DB_REPLICA_MAX_CONNECTIONS`; unset values are taken from the primary.
+    fn replica_from_env(&self) -> Result<Option<Self>, ConfigError> {
+        let Ok(host) = std::env::var("DB_REPLICA_HOST") else {
+            return Ok(None);
+        };
+        let port = match std::env::var("DB_REPLICA_PORT") {
+            Ok(value) => value.parse().map_err(|_| ConfigError::Invalid("DB_REPLICA_PORT", value))?,
+            Err(_) => self.port,
+        };
+        let max_connections = match std::env::var("DB_REPLICA_MAX_CONNECTIONS") {
+            Ok(value) => value.parse().map_err(|_| ConfigError::Invalid("DB_REPLICA_MAX_CONNECTIONS", value))?,
+            Err(_) => self.max_connections,
+        };
+        let pooler_mode = PoolerMode::from_env(&host, port)?;
+
+        Ok(Some(Self {
+            host,
+            port,
+            user: std::env::var("DB_REPLICA_USER").unwrap_or_else(|_| self.user.clone()),
+            password: std::env::var("DB_REPLICA_PASSWORD").unwrap_or_else(|_| self.password.clone()),
+            name: std::env::var("DB_REPLICA_NAME").unwrap_or_else(|_| self.name.clone()),
+            max_connections,
+            pooler_mode,
+            migrate_on_start: false,
+            replica: None,
+        }))
+    }
+
+    pub fn connect_options(&self) -> PgConnectOptions {
+        let options = PgConnectOptions::new()
+            .host(&self.host)
+            .port(self.port)
+            .username(&self.user)
+            .password(&self.password)
+            .database(&self.name);
+        match self.pooler_mode {
+            PoolerMode::Direct => options,
+            PoolerMode::Pooler => options.statement_cache_capacity(0),
+        }
+    }
+}
+
+/// Connects to Postgres, retrying a few times so the API can start alongside
+/// a database that is still booting.
+pub async fn initialize_database(config: &DatabaseConfig) -> Result<PgPool, Box<dyn std::error::Error>> {
+    let connect_options = config.connect_options();
+    tracing::info!("Connecting to {}:{} ({:?} mode)", config.host, config.port, config.pooler_mode);
+
     let mut attempts = 0;
     let max_attempts = 5;
-    
+
     loop {
         match PgPoolOptions::new()
-            .max_connections(10)
-            .connect(&database_url)
+            .max_connections(config.max_connections)
+            .connect_with(connect_options.clone())
             .await {
-            Ok(pool) => {
-                DB_POOL.set(pool).map_err(|_| "Failed to set database pool")?;
-                return Ok(());
-            }
+            Ok(pool) => return Ok(pool),
             Err(e) => {
                 attempts += 1;
                 if attempts >= max_attempts {
                     return Err(e.into());
                 }
-                println!("Database connection attempt {} failed, retrying in 2 seconds...", attempts);
+                tracing::warn!("Database connection attempt {} failed, retrying in 2 seconds...", attempts);
                 tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
             }
         }
     }
 }
 
-pub async fn get_db_pool() -> &'static PgPool {
-    DB_POOL.get_or_init(|| async {
-        let db_host = std::env::var("DB_HOST").expect("DB_HOST not set");
-        let db_port = std::env::var("DB_PORT").expect("DB_PORT not set");
-        let db_user = std::env::var("DB_USER").expect("DB_USER not set");
-        let db_password = std::env::var("DB_PASSWORD").expect("DB_PASSWORD not set");
-        let db_name = std::env::var("DB_NAME").expect("DB_NAME not set");
-        
-        let database_url = format!("postgresql://{}:{}@{}:{}/{}", db_user, db_password, db_host, db_port, db_name);
-        
-        PgPoolOptions::new()
-            .max_connections(10)
-            .connect(&database_url)
-            .await
-            .expect("Failed to connect to database")
-    }).await
-}
\ No newline at end of file
+/// Pool for a read replica. Connections are opened on first use so a replica
+/// that is down never blocks startup, and acquiring one gives up quickly so
+/// reads can fall back to the primary.
+pub fn connect_replica(config: &DatabaseConfig) -> PgPool {
+    tracing::info!("Read replica at {}:{} ({:?} mode)", config.host, config.port, config.pooler_mode);
+    PgPoolOptions::new()
+        .max_connections(config.max_connections)
+        .acquire_timeout(std::time::Duration::from_secs(3))
+        .connect_lazy_with(config.connect_options())
+}
+
+/// Applies pending migrations and returns the resulting schema version.
+pub async fn run_migrations(pool: &PgPool) -> Result<Option<i64>, Box<dyn std::error::Error>> {
+    MIGRATOR.run(pool).await?;
+    Ok(schema_version(pool).await?)
+}
+
+/// Latest successfully applied migration, or `None` if migrations never ran.
+pub async fn schema_version(pool: &PgPool) -> Result<Option<i64>, sqlx::Error> {
+    // Rarely called, so never worth a named statement (see `PoolerMode`)
+    let has_table: bool = sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+        .persistent(false)
+        .fetch_one(pool)
+        .await?;
+    if !has_table {
+        return Ok(None);
+    }
+    sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+        .persistent(false)
+        .fetch_one(pool)
+        .await
+}
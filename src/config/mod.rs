@@ -1 +1,65 @@
-pub mod database;
\ No newline at end of file
+pub mod database;
+
+use database::DatabaseConfig;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Missing(&'static str),
+    Invalid(&'static str, String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Missing(name) => write!(f, "{} not set", name),
+            ConfigError::Invalid(name, value) => write!(f, "{} has invalid value {:?}", name, value),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+pub(crate) fn required_env(name: &'static str) -> Result<String, ConfigError> {
+    std::env::var(name).map_err(|_| ConfigError::Missing(name))
+}
+
+pub(crate) fn env_flag(name: &str) -> bool {
+    matches!(
+        std::env::var(name).map(|v| v.to_ascii_lowercase()).as_deref(),
+        Ok("true") | Ok("1") | Ok("yes")
+    )
+}
+
+#[derive(Debug, Clone)]
+pub enum StorageBackend {
+    Postgres(DatabaseConfig),
+    /// `STORAGE_BACKEND=memory` runs the API without a database.
+    Memory,
+}
+
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    pub port: String,
+    pub storage: StorageBackend,
+    /// `WAGE_DATA_DIR`: where the OFLC prevailing wage files are loaded
+    /// from. Without it the wage compliance routes answer 503.
+    pub wage_data_dir: Option<std::path::PathBuf>,
+}
+
+impl AppConfig {
+    /// Reads the whole configuration up front so a missing variable stops the
+    /// server at boot instead of on the first request.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let storage = match std::env::var("STORAGE_BACKEND").as_deref() {
+            Ok("memory") => StorageBackend::Memory,
+            Ok("postgres") | Err(_) => StorageBackend::Postgres(DatabaseConfig::from_env()?),
+            Ok(other) => return Err(ConfigError::Invalid("STORAGE_BACKEND", other.to_string())),
+        };
+
+        Ok(Self {
+            port: std::env::var("PORT").unwrap_or_else(|_| "3000".to_string()),
+            storage,
+            wage_data_dir: std::env::var_os("WAGE_DATA_DIR").map(Into::into),
+        })
+    }
+}
@@ -0,0 +1,436 @@
+use axum::{
+    async_trait,
+    extract::{
+        rejection::{JsonRejection, QueryRejection},
+        FromRequest, FromRequestParts, Query, Request,
+    },
+    http::{header, request::Parts, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::error::ErrorKind;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::case_status::CaseStatus;
+use crate::middleware::request_logging::current_request_id;
+use crate::repository::RepositoryError;
+use crate::validation::FieldError;
+
+/// Everything a handler can fail with. Rendered as an RFC 7807
+/// `application/problem+json` body carrying a stable `code`, the offending
+/// `field` where there is one, and the request's correlation id.
+#[derive(Debug)]
+pub enum ApiError {
+    /// Malformed JSON, query string or header.
+    BadRequest { field: Option<String>, detail: String },
+    /// Well-formed request with a field value that cannot be stored.
+    InvalidField { field: Option<String>, detail: String },
+    /// Values that break the field rules in `validation`, all of them.
+    Validation(Vec<FieldError>),
+    /// A value outside a Postgres enum, e.g. an unknown `h1b_status`.
+    InvalidEnumValue { field: String, value: String },
+    Unauthorized,
+    NotFound { detail: String },
+    /// A unique field already belongs to another customer.
+    Duplicate { field: &'static str, existing_customer_id: Uuid },
+    /// A status change to the status the record already has.
+    StatusUnchanged { h1b_status: String },
+    /// A case status change the transition table does not allow.
+    IllegalTransition { from: CaseStatus, to: CaseStatus },
+    /// A write referencing a row that does not exist.
+    MissingReference { field: Option<String> },
+    /// Any other request that clashes with the current state.
+    Conflict { detail: String },
+    /// An `Idempotency-Key` already used for a different request.
+    IdempotencyKeyReused,
+    /// `If-Match` names a version that is not current. `current_version` is
+    /// `None` when the header could never match.
+    VersionMismatch { current_version: Option<i64> },
+    UnsupportedMediaType { detail: String },
+    /// The server was started without `WAGE_DATA_DIR`.
+    WageDataNotLoaded,
+    /// No database connection could be had in time. Holds the cause, which
+    /// is logged but not shown.
+    Unavailable(String),
+    /// Logged with the request id; clients only see the code.
+    Internal(String),
+}
+
+impl ApiError {
+    pub fn bad_request(field: Option<&str>, detail: impl Into<String>) -> Self {
+        ApiError::BadRequest { field: field.map(str::to_string), detail: detail.into() }
+    }
+
+    pub fn not_found(detail: impl Into<String>) -> Self {
+        ApiError::NotFound { detail: detail.into() }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest { .. } => StatusCode::BAD_REQUEST,
+            ApiError::InvalidField { .. }
+            | ApiError::Validation(_)
+            | ApiError::InvalidEnumValue { .. }
+            | ApiError::IdempotencyKeyReused => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            },
+            ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::NotFound { .. } => StatusCode::NOT_FOUND,
+            ApiError::Duplicate { .. }
+            | ApiError::StatusUnchanged { .. }
+            | ApiError::IllegalTransition { .. }
+            | ApiError::MissingReference { .. }
+            | ApiError::Conflict { .. } => StatusCode::CONFLICT,
+            ApiError::VersionMismatch { .. } => StatusCode::PRECONDITION_FAILED,
+            ApiError::UnsupportedMediaType { .. } => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::WageDataNotLoaded | ApiError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Stable machine-readable name of the problem.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest { .. } => "bad_request",
+            ApiError::InvalidField { .. } => "invalid_field",
+            ApiError::Validation(_) => "validation_failed",
+            ApiError::InvalidEnumValue { .. } => "invalid_enum_value",
+            ApiError::Unauthorized => "unauthorized",
+            ApiError::NotFound { .. } => "not_found",
+            ApiError::Duplicate { .. } => "duplicate",
+            ApiError::StatusUnchanged { .. } => "status_unchanged",
+            ApiError::IllegalTransition { .. } => "illegal_transition",
+            ApiError::MissingReference { .. } => "missing_reference",
+            ApiError::Conflict { .. } => "conflict",
+            ApiError::IdempotencyKeyReused => "idempotency_key_reused",
+            ApiError::VersionMismatch { .. } => "version_mismatch",
+            ApiError::UnsupportedMediaType { .. } => "unsupported_media_type",
+            ApiError::WageDataNotLoaded => "wage_data_not_loaded",
+            ApiError::Unavailable(_) => "database_unavailable",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest { .. } => "Malformed request",
+            ApiError::InvalidField { .. } => "Invalid field value",
+            ApiError::Validation(_) => "Validation failed",
+            ApiError::InvalidEnumValue { .. } => "Value not allowed",
+            ApiError::Unauthorized => "Not authorized",
+            ApiError::NotFound { .. } => "Not found",
+            ApiError::Duplicate { .. } => "Duplicate value",
+            ApiError::StatusUnchanged { .. } => "Status unchanged",
+            ApiError::IllegalTransition { .. } => "Transition not allowed",
+            ApiError::MissingReference { .. } => "Referenced record missing",
+            ApiError::Conflict { .. } => "Conflict",
+            ApiError::IdempotencyKeyReused => "Idempotency key reused",
+            ApiError::VersionMismatch { .. } => "Version mismatch",
+            ApiError::UnsupportedMediaType { .. } => "Unsupported media type",
+            ApiError::WageDataNotLoaded => "Wage data not loaded",
+            ApiError::Unavailable(_) => "Database unavailable",
+            ApiError::Internal(_) => "Internal error",
+        }
+    }
+
+    fn detail(&self) -> String {
+        match self {
+            ApiError::BadRequest { detail, .. }
+            | ApiError::InvalidField { detail, .. }
+            | ApiError::NotFound { detail }
+            | ApiError::Conflict { detail }
+            | ApiError::UnsupportedMediaType { detail } => detail.clone(),
+            ApiError::Validation(errors) => errors
+                .iter()
+                .map(|e| format!("{} {}", e.field, e.message))
+                .collect::<Vec<_>>()
+                .join("; "),
+            ApiError::InvalidEnumValue { field, value } => format!("{} is not a valid {}", value, field),
+            ApiError::Unauthorized => "you are not authorized".to_string(),
+            ApiError::Duplicate { field, .. } => format!("A customer with this {} already exists", field),
+            ApiError::StatusUnchanged { h1b_status } => format!("Customer is already {}", h1b_status),
+            ApiError::IllegalTransition { from, to } => match from.next() {
+                [] => format!("Case is {}, a final status; it cannot move to {}", from, to),
+                next => format!(
+                    "Case cannot move from {} to {}; from {} it can only move to {}",
+                    from,
+                    to,
+                    from,
+                    next.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(", ")
+                ),
+            },
+            ApiError::MissingReference { field } => match field {
+                Some(field) => format!("{} refers to a record that does not exist", field),
+                None => "The request refers to a record that does not exist".to_string(),
+            },
+            ApiError::IdempotencyKeyReused => {
+                "Idempotency-Key was already used with a different request body".to_string()
+            },
+            ApiError::VersionMismatch { .. } => "Customer was modified by another request".to_string(),
+            ApiError::WageDataNotLoaded => {
+                "No prevailing wage data is loaded; the server must be started with WAGE_DATA_DIR".to_string()
+            },
+            ApiError::Unavailable(_) => "The database is not accepting connections, try again shortly".to_string(),
+            ApiError::Internal(_) => "Something went wrong on our side".to_string(),
+        }
+    }
+
+    fn field(&self) -> Option<&str> {
+        match self {
+            ApiError::BadRequest { field, .. }
+            | ApiError::InvalidField { field, .. }
+            | ApiError::MissingReference { field } => field.as_deref(),
+            ApiError::InvalidEnumValue { field, .. } => Some(field),
+            ApiError::Duplicate { field, .. } => Some(field),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::Unavailable(source) | ApiError::Internal(source) => write!(f, "{}: {}", self.code(), source),
+            _ => write!(f, "{}: {}", self.code(), self.detail()),
+        }
+    }
+}
+
+/// RFC 7807 body of every error response.
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct Problem {
+    /// `/problems/<code>`.
+    #[serde(rename = "type")]
+    #[schema(example = "/problems/duplicate")]
+    pub problem_type: String,
+    #[schema(example = "Duplicate value")]
+    pub title: String,
+    #[schema(example = 409)]
+    pub status: u16,
+    #[schema(example = "A customer with this email already exists")]
+    pub detail: String,
+    /// Stable identifier of the problem, e.g. `duplicate`, `not_found`,
+    /// `version_mismatch`.
+    #[schema(example = "duplicate")]
+    pub code: String,
+    /// Same as the `X-Request-Id` response header.
+    pub request_id: Option<String>,
+    /// Field or parameter at fault.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "email")]
+    pub field: Option<String>,
+    /// With `duplicate`: the customer already holding the value.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub existing_customer_id: Option<Uuid>,
+    /// With `version_mismatch`: the version to retry against.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_version: Option<i64>,
+    /// With `status_unchanged`: the status the customer already has.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub h1b_status: Option<String>,
+    /// With `illegal_transition`: the customer's current case status.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub case_status: Option<CaseStatus>,
+    /// With `illegal_transition`: the statuses the case can move to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_transitions: Option<Vec<CaseStatus>>,
+    /// With `invalid_enum_value`: the rejected value.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    /// With `validation_failed`: every field that broke a rule.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<Vec<FieldError>>,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let request_id = current_request_id();
+        if status.is_server_error() {
+            // Logged inside the request span, which carries the request id
+            tracing::error!("{}", self);
+        }
+
+        let mut body = Problem {
+            problem_type: format!("/problems/{}", self.code()),
+            title: self.title().to_string(),
+            status: status.as_u16(),
+            detail: self.detail(),
+            code: self.code().to_string(),
+            request_id,
+            field: self.field().map(str::to_string),
+            ..Problem::default()
+        };
+        match &self {
+            ApiError::Duplicate { existing_customer_id, .. } => body.existing_customer_id = Some(*existing_customer_id),
+            ApiError::StatusUnchanged { h1b_status } => body.h1b_status = Some(h1b_status.clone()),
+            ApiError::IllegalTransition { from, .. } => {
+                body.case_status = Some(*from);
+                body.allowed_transitions = Some(from.next().to_vec());
+            },
+            ApiError::InvalidEnumValue { value, .. } => body.value = Some(value.clone()),
+            ApiError::VersionMismatch { current_version } => body.current_version = *current_version,
+            ApiError::Validation(errors) => body.errors = Some(errors.clone()),
+            _ => {},
+        }
+
+        let mut response = (status, Json(body)).into_response();
+        let headers = response.headers_mut();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/problem+json"));
+        match self {
+            ApiError::VersionMismatch { current_version: Some(version) } => {
+                if let Ok(etag) = HeaderValue::from_str(&format!("\"{}\"", version)) {
+                    headers.insert(header::ETAG, etag);
+                }
+            },
+            ApiError::Unavailable(_) => {
+                headers.insert(header::RETRY_AFTER, HeaderValue::from_static("5"));
+            },
+            ApiError::Unauthorized => {
+                headers.insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            },
+            _ => {},
+        }
+        response
+    }
+}
+
+/// Customer field stored in a Postgres enum type, by type name.
+fn enum_field(type_name: &str) -> String {
+    let name = type_name.rsplit('.').next().unwrap_or(type_name);
+    name.strip_suffix("_enum").unwrap_or(name).to_string()
+}
+
+/// Reads `invalid input value for enum global_visa_mgmt.h1b_status_enum: "X"`.
+fn parse_enum_error(message: &str) -> Option<ApiError> {
+    let rest = message.strip_prefix("invalid input value for enum ")?;
+    let (type_name, value) = rest.split_once(": ")?;
+    Some(ApiError::InvalidEnumValue {
+        field: enum_field(type_name),
+        value: value.trim_matches('"').to_string(),
+    })
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(e: sqlx::Error) -> Self {
+        match &e {
+            sqlx::Error::RowNotFound => return ApiError::not_found("Record not found"),
+            sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed | sqlx::Error::Io(_) | sqlx::Error::Tls(_) => {
+                return ApiError::Unavailable(e.to_string());
+            },
+            _ => {},
+        }
+        let Some(db) = e.as_database_error() else {
+            return ApiError::Internal(e.to_string());
+        };
+        // Column or constraint names double as field names in this schema.
+        let constraint = db.constraint().map(str::to_string);
+        match db.kind() {
+            ErrorKind::UniqueViolation => ApiError::Conflict {
+                detail: format!("Value already exists ({})", constraint.as_deref().unwrap_or("unique constraint")),
+            },
+            ErrorKind::ForeignKeyViolation => ApiError::MissingReference { field: constraint },
+            ErrorKind::NotNullViolation => ApiError::InvalidField {
+                field: db.try_downcast_ref::<sqlx::postgres::PgDatabaseError>().and_then(|pg| pg.column()).map(str::to_string),
+                detail: "A required value is missing".to_string(),
+            },
+            ErrorKind::CheckViolation => ApiError::InvalidField {
+                field: constraint,
+                detail: db.message().to_string(),
+            },
+            _ => match db.code().as_deref() {
+                // invalid_text_representation, raised for unknown enum labels
+                Some("22P02") => parse_enum_error(db.message()).unwrap_or_else(|| ApiError::InvalidField {
+                    field: None,
+                    detail: db.message().to_string(),
+                }),
+                _ => ApiError::Internal(e.to_string()),
+            },
+        }
+    }
+}
+
+impl From<RepositoryError> for ApiError {
+    fn from(e: RepositoryError) -> Self {
+        match e {
+            RepositoryError::Database(e) => e.into(),
+            RepositoryError::VersionMismatch { current_version } => {
+                ApiError::VersionMismatch { current_version: Some(current_version) }
+            },
+            RepositoryError::StatusUnchanged { h1b_status } => ApiError::StatusUnchanged { h1b_status },
+            RepositoryError::IllegalTransition { from, to } => ApiError::IllegalTransition { from, to },
+            RepositoryError::Duplicate { field, customer_id } => {
+                ApiError::Duplicate { field, existing_customer_id: customer_id }
+            },
+            RepositoryError::IdempotencyKeyInUse => ApiError::Conflict {
+                detail: "A request with this Idempotency-Key is already being processed".to_string(),
+            },
+        }
+    }
+}
+
+/// Field named in a body rejection such as `Failed to deserialize the JSON
+/// body into the target type: dob: input contains invalid characters ...`
+/// or `... target type: missing field `dob` ...`.
+fn rejected_field(detail: &str) -> Option<String> {
+    let (_, rest) = detail.split_once("target type: ")?;
+    if let Some(missing) = rest.strip_prefix("missing field `") {
+        return missing.split_once('`').map(|(field, _)| field.to_string());
+    }
+    let (path, _) = rest.split_once(": ")?;
+    (!path.contains(' ')).then(|| path.to_string())
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        let detail = rejection.body_text();
+        match rejection {
+            JsonRejection::JsonDataError(_) => ApiError::InvalidField { field: rejected_field(&detail), detail },
+            JsonRejection::MissingJsonContentType(_) => ApiError::UnsupportedMediaType { detail },
+            _ => ApiError::BadRequest { field: None, detail },
+        }
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::BadRequest { field: None, detail: rejection.body_text() }
+    }
+}
+
+/// `Json` whose rejections are problem responses.
+pub struct ApiJson<T>(pub T);
+
+#[async_trait]
+impl<S, T> FromRequest<S> for ApiJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(request, state).await?;
+        Ok(ApiJson(value))
+    }
+}
+
+/// `Query` whose rejections are problem responses.
+pub struct ApiQuery<T>(pub T);
+
+#[async_trait]
+impl<S, T> FromRequestParts<S> for ApiQuery<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::<T>::from_request_parts(parts, state).await?;
+        Ok(ApiQuery(value))
+    }
+}
+
@@ -1,17 +1,91 @@
 use axum::{
-    extract::Path,
-    http::StatusCode,
-    response::Json,
+    body::Bytes,
+    extract::{Extension, Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Json, Response},
 };
-use sqlx::{Row, Executor};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use crate::case_status::CaseStatus;
+use crate::error::{ApiError, ApiJson, ApiQuery};
 use crate::models::*;
-use crate::config::database::get_db_pool;
-use std::time::{SystemTime, UNIX_EPOCH};
+use crate::metrics::SharedMetrics;
+use crate::middleware::auth::AuthUser;
+use crate::search;
+use crate::soc;
+use crate::validation::{FieldError, Validate, Validator, H1B_STATUSES};
+use crate::wages::{ComplianceStatus, PrevailingWages, SharedWages, WageCompliance};
+use crate::repository::{
+    ChangeContext, CustomerFilter, CustomerListQuery, DateRange, IdempotencyKey, Position, RepositoryError,
+    SharedCustomerRepository, SortField, SortKey, SORT_FIELDS,
+};
+
+fn parse_customer_id(customer_id: &str) -> Option<Uuid> {
+    Uuid::parse_str(customer_id).ok()
+}
+
+fn change_by<'a>(user: &'a AuthUser, action: &'a str) -> ChangeContext<'a> {
+    ChangeContext { actor: Some(user.actor()), action }
+}
+
+/// Strong ETag for a record version, e.g. `"3"`.
+fn etag_header(version: i64) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::ETAG, HeaderValue::from_str(&format!("\"{}\"", version)).unwrap());
+    headers
+}
+
+/// Version required by the request's `If-Match` header. A missing header or
+/// `*` puts no condition on the update. Anything other than a single strong
+/// ETag can never match, so it is rejected with 412.
+fn if_match_version(headers: &HeaderMap) -> Result<Option<i64>, ApiError> {
+    let never_matches = ApiError::VersionMismatch { current_version: None };
+    let Some(value) = headers.get(header::IF_MATCH) else {
+        return Ok(None);
+    };
+    let Ok(value) = value.to_str() else {
+        return Err(never_matches);
+    };
+    let value = value.trim();
+    if value == "*" {
+        return Ok(None);
+    }
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .and_then(|v| v.parse().ok())
+        .map(Some)
+        .ok_or(never_matches)
+}
+
+/// Adds `warnings` to a JSON object response when there are any, as
+/// `CustomerResponse` does.
+fn add_warnings(body: &mut serde_json::Value, warnings: Vec<FieldError>) {
+    if let (Some(fields), false) = (body.as_object_mut(), warnings.is_empty()) {
+        fields.insert("warnings".to_string(), serde_json::json!(warnings));
+    }
+}
 
-fn get_timestamp() -> u128 {
-    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos()
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "system",
+    responses((status = 200, description = "Counters in the Prometheus text format", body = String, content_type = "text/plain"))
+)]
+pub async fn get_metrics(State(metrics): State<SharedMetrics>) -> Response {
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], metrics.render()).into_response()
 }
 
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "system",
+    responses((status = 200, description = "The API is up", body = HealthStatus))
+)]
 pub async fn health_check() -> Result<Json<serde_json::Value>, StatusCode> {
     Ok(Json(serde_json::json!({
         "status": "OK",
@@ -19,1027 +93,1321 @@ pub async fn health_check() -> Result<Json<serde_json::Value>, StatusCode> {
     })))
 }
 
-pub async fn test_connection() -> Result<Json<serde_json::Value>, StatusCode> {
-    let pool = get_db_pool().await;
-    match sqlx::query("SELECT 1 as test")
-        .fetch_one(pool)
-        .await {
-        Ok(_) => Ok(Json(serde_json::json!({
-            "status": "Database connected successfully"
-        }))),
-        Err(e) => {
-            eprintln!("Database connection error: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
-    }
+#[utoipa::path(
+    get,
+    path = "/hello",
+    tag = "system",
+    responses((status = 200, description = "The database is reachable", body = ConnectionStatus))
+)]
+pub async fn test_connection(
+    State(customers): State<SharedCustomerRepository>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let schema_version = customers.schema_version().await?;
+    Ok(Json(serde_json::json!({
+        "status": "Database connected successfully",
+        "schema_version": schema_version
+    })))
 }
 
-pub async fn get_all_customers() -> Result<Json<Vec<CreateCustomer>>, StatusCode> {
-    println!("🔥 get_all_customers function called");
-    let pool = get_db_pool().await;
-    
-    let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos();
-    let raw_sql = format!("SELECT customer_id, email, first_name, last_name, dob, sex::text, marital_status::text, phone, 
-        emergency_contact_name, emergency_contact_phone, employment_start_date,
-        street_name, city, state, zip,
-        client_name, client_street_name, client_city, client_state, client_zip,
-        lca_title, lca_salary, lca_code, receipt_number, h1b_start_date, h1b_end_date, h1b_status::text
-        FROM visa_db.h1bcustomer -- {}", timestamp);
-    
-    let rows = pool.fetch_all(raw_sql.as_str())
-    .await
-    .map_err(|e| {
-        eprintln!("❌ Database error in get_all_customers: {}", e);
-        eprintln!("❌ Error details: {:?}", e);
-        eprintln!("❌ SQL Query: {}", raw_sql);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
-    let customers: Vec<CreateCustomer> = rows.into_iter().map(|row| CreateCustomer {
-        customer_id: row.get("customer_id"),
-        email: row.get("email"),
-        first_name: row.get("first_name"),
-        last_name: row.get("last_name"),
-        dob: row.get("dob"),
-        sex: row.get("sex"),
-        marital_status: row.get("marital_status"),
-        phone: row.get("phone"),
-        emergency_contact_name: row.get("emergency_contact_name"),
-        emergency_contact_phone: row.get("emergency_contact_phone"),
-        employment_start_date: row.get("employment_start_date"),
-        street_name: row.get("street_name"),
-        city: row.get("city"),
-        state: row.get("state"),
-        zip: row.get("zip"),
-        client_name: row.get("client_name"),
-        client_street_name: row.get("client_street_name"),
-        client_city: row.get("client_city"),
-        client_state: row.get("client_state"),
-        client_zip: row.get("client_zip"),
-        lca_title: row.get("lca_title"),
-        lca_salary: row.get("lca_salary"),
-        lca_code: row.get("lca_code"),
-        receipt_number: row.get("receipt_number"),
-        h1b_start_date: row.get("h1b_start_date"),
-        h1b_end_date: row.get("h1b_end_date"),
-        h1b_status: row.get("h1b_status"),
-    }).collect();
-
-    Ok(Json(customers))
-}
+/// Header that makes a create safe to retry.
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+/// Set on a create answered from an earlier request with the same key.
+const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
 
-pub async fn create_visa_details(
-    Json(payload): Json<CreateCompleteCustomerRequest>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    println!("🔥 create_visa_details function called");
-    let pool = get_db_pool().await;
-    let h1b_status = payload.h1b_status.as_deref().unwrap_or("Active");
-    
-    let _query_sql = "INSERT INTO visa_db.h1bcustomer (
-            email, first_name, last_name, dob, sex, marital_status, phone,
-            emergency_contact_name, emergency_contact_phone, employment_start_date,
-            street_name, city, state, zip,
-            client_name, client_street_name, client_city, client_state, client_zip,
-            lca_title, lca_salary, lca_code, receipt_number, h1b_start_date, h1b_end_date, h1b_status
-        ) VALUES (
-            $1, $2, $3, $4, $5::visa_db.sex_enum, $6::visa_db.marital_status_enum, $7,
-            $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26::visa_db.h1b_status_enum
-        )";
-    
-    let raw_sql = format!("INSERT INTO global_visa_mgmt.h1bcustomer (
-            email, first_name, last_name, dob, sex, marital_status, phone,
-            emergency_contact_name, emergency_contact_phone, employment_start_date,
-            street_name, city, state, zip,
-            client_name, client_street_name, client_city, client_state, client_zip,
-            lca_title, lca_salary, lca_code, receipt_number, h1b_start_date, h1b_end_date, login_email, h1b_status
-        ) VALUES (
-            '{}', '{}', '{}', '{}', '{}'::global_visa_mgmt.sex_enum, '{}'::global_visa_mgmt.marital_status_enum, '{}',
-            '{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}', {}, '{}', '{}', '{}', '{}', '{}', '{}'::global_visa_mgmt.h1b_status_enum
-        )",
-        payload.email.replace("'", "''"), payload.first_name.replace("'", "''"), payload.last_name.replace("'", "''"), 
-        payload.dob, payload.sex, payload.marital_status, payload.phone.replace("'", "''"),
-        payload.emergency_contact_name.replace("'", "''"), payload.emergency_contact_phone.replace("'", "''"), payload.employment_start_date,
-        payload.street_name.replace("'", "''"), payload.city.replace("'", "''"), payload.state.replace("'", "''"), payload.zip.replace("'", "''"),
-        payload.client_name.replace("'", "''"), payload.client_street_name.replace("'", "''"), payload.client_city.replace("'", "''"), 
-        payload.client_state.replace("'", "''"), payload.client_zip.replace("'", "''"),
-        payload.lca_title.replace("'", "''"), payload.lca_salary, payload.lca_code.replace("'", "''"), 
-        payload.receipt_number.replace("'", "''"), payload.h1b_start_date, payload.h1b_end_date, payload.login_email.replace("'", "''"), h1b_status
-    );
-    
-    match pool.execute(raw_sql.as_str()).await {
-        Ok(result) => {
-            Ok(Json(serde_json::json!({
-                "message": "Visa details created successfully",
-                "email": payload.email,
-                "rows_affected": result.rows_affected()
-            })))
-        },
-        Err(e) => {
-            eprintln!("❌ Database error in create_visa_details: {}", e);
-            eprintln!("❌ Error details: {:?}", e);
-            eprintln!("❌ SQL Query: {}", raw_sql);
-            eprintln!("❌ Email: {}", payload.email);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+fn idempotency_key(headers: &HeaderMap) -> Result<Option<&str>, ApiError> {
+    let Some(value) = headers.get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
+    match value.to_str().map(str::trim) {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LENGTH => Ok(Some(key)),
+        _ => Err(ApiError::bad_request(
+            Some("Idempotency-Key"),
+            format!("Idempotency-Key must be 1 to {} visible characters", MAX_IDEMPOTENCY_KEY_LENGTH),
+        )),
     }
 }
 
-pub async fn soft_delete_customer(
-    Path(email): Path<String>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    println!("🔥 soft_delete_customer function called for email: {}", email);
-    let pool = get_db_pool().await;
-
-    // Use the pool directly, SQLx will manage connections automatically
-    match sqlx::query("UPDATE visa_db.h1bcustomer SET h1b_status = 'Inactive' WHERE email = $1")
-        .bind(&email)
-        .execute(pool)
-        .await 
-    {
-        Ok(result) => {
-            if result.rows_affected() == 0 {
-                Ok(Json(serde_json::json!({
-                    "status": 404,
-                    "message": "Record not found in the database",
-                    "email": email
-                })))
-            } else {
-                Ok(Json(serde_json::json!({
-                    "message": "Customer soft deleted successfully",
-                    "email": email,
-                    "rows_affected": result.rows_affected()
-                })))
-            }
+/// Creates the customer, returning it with the validation warnings. When the
+/// caller already sent this request with the same `Idempotency-Key`, returns
+/// the customer it created instead, flagged `true`; the same key with a
+/// different body is rejected.
+async fn create_customer_once(
+    customers: &SharedCustomerRepository,
+    user: &AuthUser,
+    headers: &HeaderMap,
+    payload: &CreateCompleteCustomerRequest,
+) -> Result<(Customer, bool, Vec<FieldError>), ApiError> {
+    let warnings = payload.validate().map_err(ApiError::Validation)?;
+    let change = change_by(user, "create");
+    let Some(key) = idempotency_key(headers)? else {
+        return Ok((customers.create(payload, change, None).await?, false, warnings));
+    };
+    let request = serde_json::to_value(payload).map_err(|e| ApiError::Internal(e.to_string()))?;
+
+    let earlier = match customers.find_idempotent_create(user.actor(), key).await? {
+        Some(earlier) => earlier,
+        None => match customers.create(payload, change, Some(IdempotencyKey { key, request: &request })).await {
+            Ok(customer) => return Ok((customer, false, warnings)),
+            // A concurrent request with the same key committed first
+            Err(RepositoryError::IdempotencyKeyInUse) => customers
+                .find_idempotent_create(user.actor(), key)
+                .await?
+                .ok_or(RepositoryError::IdempotencyKeyInUse)?,
+            Err(e) => return Err(e.into()),
         },
-        Err(e) => {
-            eprintln!("❌ Database error in soft_delete_customer: {}", e);
-            eprintln!("❌ Soft delete error details: {:?}", e);
-            eprintln!("❌ Email: {}", email);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+    };
+    if earlier.request != request {
+        return Err(ApiError::IdempotencyKeyReused);
     }
+    let customer = customers
+        .find_by_id(earlier.customer_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("Customer {} not found", earlier.customer_id)))?;
+    Ok((customer, true, warnings))
 }
 
-pub async fn get_customer_personal(
-    Path(id): Path<String>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let pool = get_db_pool().await;
-    
-    let query_sql = "SELECT customer_id, email, first_name, last_name, dob, sex::text, marital_status::text, phone 
-        FROM visa_db.h1bcustomer WHERE customer_id = $1";
-    
-    match sqlx::query(query_sql)
-        .bind(&id)
-        .fetch_optional(pool)
-        .await {
-        Ok(Some(row)) => {
-            Ok(Json(serde_json::json!({
-                "customer_id": row.get::<uuid::Uuid, _>("customer_id"),
-                "email": row.get::<String, _>("email"),
-                "first_name": row.get::<String, _>("first_name"),
-                "last_name": row.get::<String, _>("last_name"),
-                "dob": row.get::<chrono::NaiveDate, _>("dob"),
-                "sex": row.get::<String, _>("sex"),
-                "marital_status": row.get::<String, _>("marital_status"),
-                "phone": row.get::<String, _>("phone")
-            })))
-        },
-        Ok(None) => {
-            eprintln!("❌ Customer not found in get_customer_personal: {}", id);
-            Err(StatusCode::NOT_FOUND)
-        },
-        Err(e) => {
-            eprintln!("❌ Database error in get_customer_personal: {}", e);
-            eprintln!("❌ Error details: {:?}", e);
-            eprintln!("❌ Customer ID: {}", id);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+/// `Location` and `ETag` of a created customer, and `Idempotent-Replayed`
+/// when it was created by an earlier request.
+fn created_headers(customer: &Customer, replayed: bool) -> HeaderMap {
+    let mut headers = etag_header(customer.version);
+    let location = format!("/v1/customers/{}", customer.customer_id);
+    headers.insert(header::LOCATION, HeaderValue::from_str(&location).unwrap());
+    if replayed {
+        headers.insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
     }
+    headers
 }
 
-pub async fn get_customer_address(
-    Path(id): Path<String>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let pool = get_db_pool().await;
-    
-    let query_sql = "SELECT customer_id, street_name, city, state, zip 
-        FROM visa_db.h1bcustomer WHERE customer_id = $1";
-    
-    match sqlx::query(query_sql)
-        .bind(&id)
-        .fetch_optional(pool)
-        .await {
-        Ok(Some(row)) => {
-            Ok(Json(serde_json::json!({
-                "customer_id": row.get::<uuid::Uuid, _>("customer_id"),
-                "street_name": row.get::<String, _>("street_name"),
-                "city": row.get::<String, _>("city"),
-                "state": row.get::<String, _>("state"),
-                "zip": row.get::<String, _>("zip")
-            })))
-        },
-        Ok(None) => {
-            eprintln!("❌ Customer not found in get_customer_address: {}", id);
-            Err(StatusCode::NOT_FOUND)
-        },
-        Err(e) => {
-            eprintln!("❌ Database error in get_customer_address: {}", e);
-            eprintln!("❌ Error details: {:?}", e);
-            eprintln!("❌ Customer ID: {}", id);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+#[utoipa::path(
+    post,
+    path = "/v1/customers",
+    tag = "customers",
+    params(
+        ("Idempotency-Key" = Option<String>, Header,
+         description = "Makes the request safe to retry for 24 hours: a repeat returns the customer already created")
+    ),
+    request_body = CreateCompleteCustomerRequest,
+    responses(
+        (status = 201, description = "The created customer", body = CustomerResponse, headers(
+            ("Location" = String, description = "URL of the new customer"),
+            ("ETag" = String, description = "Record version"),
+            ("Idempotent-Replayed" = String, description = "`true` when an earlier request with the same key created it")
+        )),
+        (status = 400, description = "Invalid `Idempotency-Key`", body = Problem),
+        (status = 409, description = "`email` or `receipt_number` already belongs to another customer", body = Problem),
+        (status = 422, description = "A field is missing, has the wrong type or breaks a validation rule (`errors` lists each), or `Idempotency-Key` was used with another body", body = Problem)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn create_customer(
+    State(customers): State<SharedCustomerRepository>,
+    Extension(user): Extension<AuthUser>,
+    headers: HeaderMap,
+    ApiJson(payload): ApiJson<CreateCompleteCustomerRequest>,
+) -> Result<Response, ApiError> {
+    tracing::debug!("create_customer called");
+    let (customer, replayed, warnings) = create_customer_once(&customers, &user, &headers, &payload).await?;
+    let body = CustomerResponse::from(customer.clone()).with_warnings(warnings);
+    Ok((StatusCode::CREATED, created_headers(&customer, replayed), Json(body)).into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/h1b_customer/create",
+    tag = "legacy",
+    params(("Idempotency-Key" = Option<String>, Header, description = "As for `POST /v1/customers`")),
+    request_body = CreateCompleteCustomerRequest,
+    responses(
+        (status = 201, description = "Customer created; `Location` gives its URL", body = CreatedMessage),
+        (status = 400, description = "Invalid `Idempotency-Key`", body = Problem),
+        (status = 409, description = "`email` or `receipt_number` already belongs to another customer", body = Problem),
+        (status = 422, description = "A field is missing, has the wrong type or breaks a validation rule (`errors` lists each), or `Idempotency-Key` was used with another body", body = Problem)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn create_visa_details(
+    State(customers): State<SharedCustomerRepository>,
+    Extension(user): Extension<AuthUser>,
+    headers: HeaderMap,
+    ApiJson(payload): ApiJson<CreateCompleteCustomerRequest>,
+) -> Result<Response, ApiError> {
+    tracing::debug!("create_visa_details called");
+
+    let (customer, replayed, warnings) = create_customer_once(&customers, &user, &headers, &payload).await?;
+    Ok((StatusCode::CREATED, created_headers(&customer, replayed), Json(serde_json::json!({
+        "message": "Visa details created successfully",
+        "email": payload.email,
+        "customer_id": customer.customer_id,
+        "rows_affected": if replayed { 0 } else { 1 },
+        "created_record": CustomerResponse::from(customer).with_warnings(warnings)
+    }))).into_response())
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AsOfQuery {
+    /// RFC 3339 timestamp, or a `YYYY-MM-DD` date meaning the end of that day
+    /// (UTC).
+    pub as_of: Option<String>,
+}
+
+fn parse_as_of(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+        return Some(at.with_timezone(&Utc));
     }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    Some(date.and_hms_micro_opt(23, 59, 59, 999_999)?.and_utc())
 }
 
-pub async fn get_customer_h1b(
-    Path(id): Path<String>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let pool = get_db_pool().await;
-    
-    let query_sql = "SELECT customer_id, client_name, client_street_name, client_city, client_state, client_zip,
-        lca_title, lca_salary, lca_code, receipt_number, h1b_start_date, h1b_end_date, h1b_status::text 
-        FROM visa_db.h1bcustomer WHERE customer_id = $1";
-    
-    match sqlx::query(query_sql)
-        .bind(&id)
-        .fetch_optional(pool)
-        .await {
-        Ok(Some(row)) => {
-            Ok(Json(serde_json::json!({
-                "customer_id": row.get::<uuid::Uuid, _>("customer_id"),
-                "client_name": row.get::<String, _>("client_name"),
-                "client_street_name": row.get::<String, _>("client_street_name"),
-                "client_city": row.get::<String, _>("client_city"),
-                "client_state": row.get::<String, _>("client_state"),
-                "client_zip": row.get::<String, _>("client_zip"),
-                "lca_title": row.get::<String, _>("lca_title"),
-                "lca_salary": row.get::<rust_decimal::Decimal, _>("lca_salary"),
-                "lca_code": row.get::<String, _>("lca_code"),
-                "receipt_number": row.get::<String, _>("receipt_number"),
-                "h1b_start_date": row.get::<chrono::NaiveDate, _>("h1b_start_date"),
-                "h1b_end_date": row.get::<chrono::NaiveDate, _>("h1b_end_date"),
-                "h1b_status": row.get::<String, _>("h1b_status")
-            })))
+/// With `?as_of=` the record is read from its history as it was at that
+/// time; the status check then applies to the historical status.
+#[utoipa::path(
+    get,
+    path = "/get_customer_by_id/{id}",
+    tag = "legacy",
+    params(("id" = Uuid, Path, description = "Customer id"), AsOfQuery),
+    responses(
+        (status = 200, description = "The active customer, or `{\"message\": \"Data not found\"}`", body = CustomerResponse),
+        (status = 400, description = "Invalid `as_of`", body = Problem)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn get_customer_by_id(
+    State(customers): State<SharedCustomerRepository>,
+    Path(customer_id): Path<String>,
+    ApiQuery(query): ApiQuery<AsOfQuery>,
+) -> Result<(HeaderMap, Json<serde_json::Value>), ApiError> {
+    let not_found = (HeaderMap::new(), Json(serde_json::json!({
+        "message": "Data not found"
+    })));
+    let Some(id) = parse_customer_id(&customer_id) else {
+        return Ok(not_found);
+    };
+
+    let result = match query.as_of.as_deref() {
+        Some(as_of) => {
+            let at = parse_as_of(as_of).ok_or_else(|| {
+                ApiError::bad_request(Some("as_of"), "as_of must be an RFC 3339 timestamp or a YYYY-MM-DD date")
+            })?;
+            customers.find_as_of(id, at).await?
         },
-        Ok(None) => {
-            eprintln!("❌ Customer not found in get_customer_h1b: {}", id);
-            Err(StatusCode::NOT_FOUND)
+        None => customers.find_by_id(id).await?,
+    };
+
+    match result {
+        Some(customer) if customer.h1b_status == "Active" => {
+            // A past version must not be usable as an If-Match precondition
+            let headers = match query.as_of {
+                Some(_) => HeaderMap::new(),
+                None => etag_header(customer.version),
+            };
+            Ok((headers, Json(serde_json::json!(CustomerResponse::from(customer)))))
         },
-        Err(e) => {
-            eprintln!("❌ Database error in get_customer_h1b: {}", e);
-            eprintln!("❌ Error details: {:?}", e);
-            eprintln!("❌ Customer ID: {}", id);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+        _ => Ok(not_found),
     }
 }
 
-pub async fn update_customer_address(
-    Path(id): Path<String>,
-    Json(payload): Json<serde_json::Value>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let pool = get_db_pool().await;
-    
-    let query_sql = "UPDATE visa_db.h1bcustomer SET 
-        street_name = $2, city = $3, state = $4, zip = $5 
-        WHERE customer_id = $1";
-    
-    match sqlx::query(query_sql)
-        .bind(&id)
-        .bind(payload["street_name"].as_str().unwrap_or(""))
-        .bind(payload["city"].as_str().unwrap_or(""))
-        .bind(payload["state"].as_str().unwrap_or(""))
-        .bind(payload["zip"].as_str().unwrap_or(""))
-        .execute(pool)
-        .await {
-        Ok(result) => {
-            if result.rows_affected() > 0 {
-                Ok(Json(serde_json::json!({
-                    "message": "Address updated successfully",
-                    "customer_id": id
-                })))
-            } else {
-                eprintln!("❌ Customer not found in update_customer_address: {}", id);
-                Err(StatusCode::NOT_FOUND)
-            }
+/// `GET /v1/customers/{id}`: the record whatever its status, or 404. Takes
+/// `as_of` like `get_customer_by_id`.
+#[utoipa::path(
+    get,
+    path = "/v1/customers/{id}",
+    tag = "customers",
+    params(("id" = Uuid, Path, description = "Customer id"), AsOfQuery),
+    responses(
+        (status = 200, description = "The customer; `ETag` carries its version unless `as_of` is given", body = CustomerResponse),
+        (status = 400, description = "Invalid `as_of`", body = Problem),
+        (status = 404, description = "Unknown customer", body = Problem)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn get_customer(
+    State(customers): State<SharedCustomerRepository>,
+    Path(customer_id): Path<String>,
+    ApiQuery(query): ApiQuery<AsOfQuery>,
+) -> Result<(HeaderMap, Json<CustomerResponse>), ApiError> {
+    let not_found = || ApiError::not_found(format!("Customer {} not found", customer_id));
+    let id = parse_customer_id(&customer_id).ok_or_else(not_found)?;
+
+    let customer = match query.as_of.as_deref() {
+        Some(as_of) => {
+            let at = parse_as_of(as_of).ok_or_else(|| {
+                ApiError::bad_request(Some("as_of"), "as_of must be an RFC 3339 timestamp or a YYYY-MM-DD date")
+            })?;
+            customers.find_as_of(id, at).await?
         },
-        Err(e) => {
-            eprintln!("❌ Database error in update_customer_address: {}", e);
-            eprintln!("❌ Error details: {:?}", e);
-            eprintln!("❌ Customer ID: {}", id);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
-    }
+        None => customers.find_by_id(id).await?,
+    };
+    let customer = customer.ok_or_else(not_found)?;
+    let headers = match query.as_of {
+        Some(_) => HeaderMap::new(),
+        None => etag_header(customer.version),
+    };
+    Ok((headers, Json(customer.into())))
 }
 
-pub async fn update_customer_h1b(
-    Path(id): Path<String>,
-    Json(payload): Json<serde_json::Value>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let pool = get_db_pool().await;
-    
-    let query_sql = "UPDATE visa_db.h1bcustomer SET 
-        client_name = $2, client_street_name = $3, client_city = $4, client_state = $5, client_zip = $6,
-        lca_title = $7, lca_salary = $8, lca_code = $9, receipt_number = $10, 
-        h1b_start_date = $11, h1b_end_date = $12, h1b_status = $13::visa_db.h1b_status_enum 
-        WHERE customer_id = $1";
-    
-    match sqlx::query(query_sql)
-        .bind(&id)
-        .bind(payload["client_name"].as_str().unwrap_or(""))
-        .bind(payload["client_street_name"].as_str().unwrap_or(""))
-        .bind(payload["client_city"].as_str().unwrap_or(""))
-        .bind(payload["client_state"].as_str().unwrap_or(""))
-        .bind(payload["client_zip"].as_str().unwrap_or(""))
-        .bind(payload["lca_title"].as_str().unwrap_or(""))
-        .bind(payload["lca_salary"].as_f64().unwrap_or(0.0))
-        .bind(payload["lca_code"].as_str().unwrap_or(""))
-        .bind(payload["receipt_number"].as_str().unwrap_or(""))
-        .bind(payload["h1b_start_date"].as_str().unwrap_or(""))
-        .bind(payload["h1b_end_date"].as_str().unwrap_or(""))
-        .bind(payload["h1b_status"].as_str().unwrap_or("Active"))
-        .execute(pool)
-        .await {
-        Ok(result) => {
-            if result.rows_affected() > 0 {
-                Ok(Json(serde_json::json!({
-                    "message": "H1B details updated successfully",
-                    "customer_id": id
-                })))
-            } else {
-                eprintln!("❌ Customer not found in update_customer_h1b: {}", id);
-                Err(StatusCode::NOT_FOUND)
-            }
-        },
-        Err(e) => {
-            eprintln!("❌ Database error in update_customer_h1b: {}", e);
-            eprintln!("❌ Error details: {:?}", e);
-            eprintln!("❌ Customer ID: {}", id);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+/// `POST /v1/customers/{id}:deactivate` and `POST /v1/customers/{id}:activate`.
+/// The router cannot split a segment, so the id and the action arrive together.
+#[utoipa::path(
+    post,
+    path = "/v1/customers/{id}:deactivate",
+    tag = "customers",
+    params(("id" = Uuid, Path, description = "Customer id")),
+    responses(
+        (status = 200, description = "The updated customer", body = CustomerResponse),
+        (status = 404, description = "Unknown customer", body = Problem),
+        (status = 409, description = "The customer already has that status", body = Problem)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn customer_action(
+    State(customers): State<SharedCustomerRepository>,
+    Extension(user): Extension<AuthUser>,
+    Path(target): Path<String>,
+) -> Result<(HeaderMap, Json<CustomerResponse>), ApiError> {
+    tracing::debug!("customer_action called for {}", target);
+    let (customer_id, (h1b_status, action)) = match target.split_once(':') {
+        Some((id, "deactivate")) => (id, ("Inactive", "soft_delete")),
+        Some((id, "activate")) => (id, ("Active", "activate")),
+        _ => return Err(ApiError::not_found(format!("No action at /v1/customers/{}", target))),
+    };
+    let customer = set_customer_status(&customers, &user, customer_id, h1b_status, action).await?;
+    Ok((etag_header(customer.version), Json(customer.into())))
+}
+
+#[utoipa::path(
+    get,
+    path = "/get_customer_by_email/{email}",
+    tag = "legacy",
+    params(("email" = String, Path, description = "`email` or `login_email`")),
+    responses((status = 200, description = "Matching customers, or `[{\"message\": \"Data not found\"}]`", body = [CustomerResponse])),
+    security(("bearer_auth" = []))
+)]
+pub async fn get_customer_by_email(
+    State(customers): State<SharedCustomerRepository>,
+    Path(email): Path<String>,
+) -> Result<Json<Vec<serde_json::Value>>, ApiError> {
+    let rows = customers.find_by_email(&email).await?;
+    if rows.is_empty() {
+        Ok(Json(vec![serde_json::json!({
+            "message": "Data not found"
+        })]))
+    } else {
+        Ok(Json(rows.into_iter().map(|customer| serde_json::json!(CustomerResponse::from(customer))).collect()))
     }
 }
 
-pub async fn update_visa_details_by_id(
+/// Moves a customer to `h1b_status` in one conditional write: 404 for an
+/// unknown id, 409 if it already has that status, otherwise the updated
+/// record.
+async fn set_customer_status(
+    customers: &SharedCustomerRepository,
+    user: &AuthUser,
+    customer_id: &str,
+    h1b_status: &str,
+    action: &str,
+) -> Result<Customer, ApiError> {
+    let not_found = || ApiError::not_found(format!("Customer {} not found", customer_id));
+    let id = parse_customer_id(customer_id).ok_or_else(not_found)?;
+
+    customers
+        .set_status(id, h1b_status, change_by(user, action))
+        .await?
+        .ok_or_else(not_found)
+}
+
+/// `set_customer_status` answered in the legacy envelope.
+async fn change_customer_status(
+    customers: &SharedCustomerRepository,
+    user: &AuthUser,
+    customer_id: &str,
+    h1b_status: &str,
+    action: &str,
+    success_message: &str,
+) -> Result<Response, ApiError> {
+    let customer = set_customer_status(customers, user, customer_id, h1b_status, action).await?;
+    Ok((etag_header(customer.version), Json(serde_json::json!({
+        "message": success_message,
+        "customer_id": customer_id,
+        "rows_affected": 1,
+        "updated_record": CustomerResponse::from(customer)
+    }))).into_response())
+}
+
+#[utoipa::path(
+    patch,
+    path = "/soft_delete_customer_via_id/{id}",
+    tag = "legacy",
+    params(("id" = Uuid, Path, description = "Customer id")),
+    responses(
+        (status = 200, description = "Customer deactivated", body = StatusChange),
+        (status = 404, description = "Unknown customer", body = Problem),
+        (status = 409, description = "Already inactive", body = Problem)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn soft_delete_customer_by_id(
+    State(customers): State<SharedCustomerRepository>,
+    Extension(user): Extension<AuthUser>,
     Path(customer_id): Path<String>,
-    Json(payload): Json<UpdateVisaDetailsRequest>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    println!("🔥 update_visa_details_by_id function called for customer_id: {}", customer_id);
-    let pool = get_db_pool().await;
-    let mut tx = pool.begin().await.map_err(|e| {
-        eprintln!("❌ Failed to begin transaction in update_visa_details_by_id: {}", e);
-        eprintln!("❌ Transaction error details: {:?}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-    
-    let select_sql = "SELECT * FROM visa_db.h1bcustomer WHERE customer_id = $1";
-    
-    let current_row = sqlx::query(&select_sql)
-        .bind(&customer_id)
-        .fetch_optional(&mut *tx)
-        .await
-        .map_err(|e| {
-            eprintln!("❌ Database error in update_visa_details_by_id select: {}", e);
-            eprintln!("❌ Select error details: {:?}", e);
-            eprintln!("❌ SQL Query: {}", select_sql);
-            eprintln!("❌ Customer ID: {}", customer_id);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-    
-    let current = match current_row {
-        Some(row) => row,
-        None => {
-            return Ok(Json(serde_json::json!({
-                "status": 404,
-                "message": "Record not found in the database",
-                "customer_id": customer_id
-            })));
-        }
+) -> Result<Response, ApiError> {
+    tracing::debug!("soft_delete_customer_by_id called for customer_id: {}", customer_id);
+    change_customer_status(&customers, &user, &customer_id, "Inactive", "soft_delete", "Customer soft deleted successfully").await
+}
+
+/// Reads the loosely typed body accepted by `PUT /update_customer_by_id`.
+/// Missing text fields are written as empty strings and a missing salary as 0,
+/// matching what the web client has always relied on.
+fn customer_update_from_json(payload: &serde_json::Value) -> Result<CustomerUpdate, (&'static str, String)> {
+    let text = |field: &str| payload[field].as_str().unwrap_or("").to_string();
+    let date = |field: &'static str| {
+        payload[field]
+            .as_str()
+            .unwrap_or("")
+            .parse::<NaiveDate>()
+            .map_err(|e| (field, format!("{}: {}", field, e)))
+    };
+    // A number or a numeric string, as `PATCH` accepts
+    let lca_salary = match &payload["lca_salary"] {
+        serde_json::Value::Null => Decimal::ZERO,
+        salary => serde_json::from_value::<Decimal>(salary.clone())
+            .map_err(|e| ("lca_salary", format!("lca_salary: {}", e)))?,
     };
-    
-    let first_name = payload.first_name.unwrap_or_else(|| current.get("first_name"));
-    let last_name = payload.last_name.unwrap_or_else(|| current.get("last_name"));
-    let dob = payload.dob.unwrap_or_else(|| current.get("dob"));
-    let sex = payload.sex.unwrap_or_else(|| current.get("sex"));
-    let marital_status = payload.marital_status.unwrap_or_else(|| current.get("marital_status"));
-    let phone = payload.phone.unwrap_or_else(|| current.get("phone"));
-    let emergency_contact_name = payload.emergency_contact_name.unwrap_or_else(|| current.get("emergency_contact_name"));
-    let emergency_contact_phone = payload.emergency_contact_phone.unwrap_or_else(|| current.get("emergency_contact_phone"));
-    let employment_start_date = payload.employment_start_date.unwrap_or_else(|| current.get("employment_start_date"));
-    let street_name = payload.street_name.unwrap_or_else(|| current.get("street_name"));
-    let city = payload.city.unwrap_or_else(|| current.get("city"));
-    let state = payload.state.unwrap_or_else(|| current.get("state"));
-    let zip = payload.zip.unwrap_or_else(|| current.get("zip"));
-    let client_name = payload.client_name.unwrap_or_else(|| current.get("client_name"));
-    let client_street_name = payload.client_street_name.unwrap_or_else(|| current.get("client_street_name"));
-    let client_city = payload.client_city.unwrap_or_else(|| current.get("client_city"));
-    let client_state = payload.client_state.unwrap_or_else(|| current.get("client_state"));
-    let client_zip = payload.client_zip.unwrap_or_else(|| current.get("client_zip"));
-    let lca_title = payload.lca_title.unwrap_or_else(|| current.get("lca_title"));
-    let lca_salary = payload.lca_salary.unwrap_or_else(|| current.get("lca_salary"));
-    let lca_code = payload.lca_code.unwrap_or_else(|| current.get("lca_code"));
-    let receipt_number = payload.receipt_number.unwrap_or_else(|| current.get("receipt_number"));
-    let h1b_start_date = payload.h1b_start_date.unwrap_or_else(|| current.get("h1b_start_date"));
-    let h1b_end_date = payload.h1b_end_date.unwrap_or_else(|| current.get("h1b_end_date"));
-    let h1b_status = payload.h1b_status.unwrap_or_else(|| current.get("h1b_status"));
-    
-    let update_sql = "UPDATE visa_db.h1bcustomer SET
-            first_name = $2, last_name = $3, dob = $4, sex = $5::visa_db.sex_enum,
-            marital_status = $6::visa_db.marital_status_enum, phone = $7,
-            emergency_contact_name = $8, emergency_contact_phone = $9, employment_start_date = $10,
-            street_name = $11, city = $12, state = $13, zip = $14,
-            client_name = $15, client_street_name = $16, client_city = $17, client_state = $18, client_zip = $19,
-            lca_title = $20, lca_salary = $21, lca_code = $22, receipt_number = $23,
-            h1b_start_date = $24, h1b_end_date = $25, h1b_status = $26::visa_db.h1b_status_enum
-         WHERE customer_id = $1";
-    
-    let result = sqlx::query(&update_sql)
-        .bind(&customer_id)
-        .bind(&first_name)
-        .bind(&last_name)
-        .bind(&dob)
-        .bind(&sex)
-        .bind(&marital_status)
-        .bind(&phone)
-        .bind(&emergency_contact_name)
-        .bind(&emergency_contact_phone)
-        .bind(&employment_start_date)
-        .bind(&street_name)
-        .bind(&city)
-        .bind(&state)
-        .bind(&zip)
-        .bind(&client_name)
-        .bind(&client_street_name)
-        .bind(&client_city)
-        .bind(&client_state)
-        .bind(&client_zip)
-        .bind(&lca_title)
-        .bind(&lca_salary)
-        .bind(&lca_code)
-        .bind(&receipt_number)
-        .bind(&h1b_start_date)
-        .bind(&h1b_end_date)
-        .bind(&h1b_status)
-        .execute(&mut *tx)
-        .await
-        .map_err(|e| {
-            eprintln!("❌ Database error in update_visa_details_by_id: {}", e);
-            eprintln!("❌ Update error details: {:?}", e);
-            eprintln!("❌ SQL Query: {}", update_sql);
-            eprintln!("❌ Customer ID: {}", customer_id);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-    
-    tx.commit().await.map_err(|e| {
-        eprintln!("❌ Failed to commit transaction in update_visa_details_by_id: {}", e);
-        eprintln!("❌ Commit error details: {:?}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
 
-    Ok(Json(serde_json::json!({
-        "message": "Visa details updated successfully",
-        "customer_id": customer_id,
-        "rows_affected": result.rows_affected()
-    })))
+    Ok(CustomerUpdate {
+        email: text("email"),
+        first_name: text("first_name"),
+        last_name: text("last_name"),
+        dob: date("dob")?,
+        sex: text("sex"),
+        marital_status: text("marital_status"),
+        phone: text("phone"),
+        emergency_contact_name: text("emergency_contact_name"),
+        emergency_contact_phone: text("emergency_contact_phone"),
+        employment_start_date: date("employment_start_date")?,
+        street_name: text("street_name"),
+        city: text("city"),
+        state: text("state"),
+        zip: text("zip"),
+        client_name: text("client_name"),
+        client_street_name: text("client_street_name"),
+        client_city: text("client_city"),
+        client_state: text("client_state"),
+        client_zip: text("client_zip"),
+        lca_title: text("lca_title"),
+        lca_salary,
+        lca_code: text("lca_code"),
+        receipt_number: text("receipt_number"),
+        h1b_start_date: date("h1b_start_date")?,
+        h1b_end_date: date("h1b_end_date")?,
+        login_email: text("login_email"),
+    })
 }
 
-pub async fn get_customer_by_id(
+/// Sending the ETag from `get_customer_by_id` as `If-Match` makes the update
+/// conditional: it fails with 412 if the record changed in the meantime.
+#[utoipa::path(
+    put,
+    path = "/update_customer_by_id/{id}",
+    tag = "legacy",
+    params(("id" = Uuid, Path, description = "Customer id")),
+    request_body(content = CreateCompleteCustomerRequest, description = "Every field; text fields left out are blanked and a missing `lca_salary` becomes 0, which fails validation for required fields"),
+    responses(
+        (status = 200, description = "Customer updated, or `{\"message\": \"Customer not found\"}`", body = UpdatedMessage),
+        (status = 400, description = "Unparsable date or salary", body = Problem),
+        (status = 409, description = "`email` or `receipt_number` already belongs to another customer", body = Problem),
+        (status = 412, description = "`If-Match` does not name the current version", body = Problem),
+        (status = 422, description = "The resulting record breaks a validation rule (`errors` lists each)", body = Problem)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn update_customer_by_id(
+    State(customers): State<SharedCustomerRepository>,
+    Extension(user): Extension<AuthUser>,
     Path(customer_id): Path<String>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let pool = get_db_pool().await;
-    
-    let raw_sql = format!("SELECT customer_id, email, first_name, last_name, dob, sex::text, marital_status::text, phone, 
-        emergency_contact_name, emergency_contact_phone, employment_start_date,
-        street_name, city, state, zip,
-        client_name, client_street_name, client_city, client_state, client_zip,
-        lca_title, lca_salary, lca_code, receipt_number, h1b_start_date, h1b_end_date, login_email, h1b_status::text
-        FROM global_visa_mgmt.h1bcustomer WHERE customer_id::text = '{}' AND h1b_status = 'Active'", customer_id.replace("'", "''"));
-    
-    match pool.fetch_optional(raw_sql.as_str())
-        .await {
-        Ok(Some(row)) => {
-            let mut response = serde_json::Map::new();
-            response.insert("customer_id".to_string(), serde_json::json!(row.get::<uuid::Uuid, _>("customer_id")));
-            response.insert("email".to_string(), serde_json::json!(row.get::<String, _>("email")));
-            response.insert("first_name".to_string(), serde_json::json!(row.get::<String, _>("first_name")));
-            response.insert("last_name".to_string(), serde_json::json!(row.get::<String, _>("last_name")));
-            response.insert("dob".to_string(), serde_json::json!(row.get::<chrono::NaiveDate, _>("dob")));
-            response.insert("sex".to_string(), serde_json::json!(row.get::<String, _>("sex")));
-            response.insert("marital_status".to_string(), serde_json::json!(row.get::<String, _>("marital_status")));
-            response.insert("phone".to_string(), serde_json::json!(row.get::<String, _>("phone")));
-            response.insert("emergency_contact_name".to_string(), serde_json::json!(row.get::<String, _>("emergency_contact_name")));
-            response.insert("emergency_contact_phone".to_string(), serde_json::json!(row.get::<String, _>("emergency_contact_phone")));
-            response.insert("employment_start_date".to_string(), serde_json::json!(row.get::<chrono::NaiveDate, _>("employment_start_date")));
-            response.insert("street_name".to_string(), serde_json::json!(row.get::<String, _>("street_name")));
-            response.insert("city".to_string(), serde_json::json!(row.get::<String, _>("city")));
-            response.insert("state".to_string(), serde_json::json!(row.get::<String, _>("state")));
-            response.insert("zip".to_string(), serde_json::json!(row.get::<String, _>("zip")));
-            response.insert("client_name".to_string(), serde_json::json!(row.get::<String, _>("client_name")));
-            response.insert("client_street_name".to_string(), serde_json::json!(row.get::<String, _>("client_street_name")));
-            response.insert("client_city".to_string(), serde_json::json!(row.get::<String, _>("client_city")));
-            response.insert("client_state".to_string(), serde_json::json!(row.get::<String, _>("client_state")));
-            response.insert("client_zip".to_string(), serde_json::json!(row.get::<String, _>("client_zip")));
-            response.insert("lca_title".to_string(), serde_json::json!(row.get::<String, _>("lca_title")));
-            response.insert("lca_salary".to_string(), serde_json::json!(row.get::<rust_decimal::Decimal, _>("lca_salary")));
-            response.insert("lca_code".to_string(), serde_json::json!(row.get::<String, _>("lca_code")));
-            response.insert("receipt_number".to_string(), serde_json::json!(row.get::<String, _>("receipt_number")));
-            response.insert("h1b_start_date".to_string(), serde_json::json!(row.get::<chrono::NaiveDate, _>("h1b_start_date")));
-            response.insert("h1b_end_date".to_string(), serde_json::json!(row.get::<chrono::NaiveDate, _>("h1b_end_date")));
-            response.insert("login_email".to_string(), serde_json::json!(row.get::<String, _>("login_email")));
-            response.insert("h1b_status".to_string(), serde_json::json!(row.get::<String, _>("h1b_status")));
-            Ok(Json(serde_json::Value::Object(response)))
-        },
-        Ok(None) => {
-            Ok(Json(serde_json::json!({
-                "message": "Data not found"
-            })))
+    headers: HeaderMap,
+    ApiJson(payload): ApiJson<serde_json::Value>,
+) -> Result<Response, ApiError> {
+    tracing::debug!("update_customer_by_id called for customer_id: {}", customer_id);
+    let not_found = Json(serde_json::json!({
+        "message": "Customer not found",
+        "customer_id": customer_id
+    }));
+    let Some(id) = parse_customer_id(&customer_id) else {
+        return Ok(not_found.into_response());
+    };
+    let expected_version = if_match_version(&headers)?;
+
+    let update = customer_update_from_json(&payload).map_err(|(field, detail)| ApiError::bad_request(Some(field), detail))?;
+    // The whole record is replaced, so the whole record must pass, blanked fields included
+    let warnings = update.validate().map_err(ApiError::Validation)?;
+
+    match customers.update(id, &update, expected_version, change_by(&user, "update")).await? {
+        Some(customer) => {
+            let mut body = serde_json::json!({
+                "message": "Customer updated successfully",
+                "customer_id": customer_id,
+                "rows_affected": 1,
+                "version": customer.version
+            });
+            add_warnings(&mut body, warnings);
+            Ok((etag_header(customer.version), Json(body)).into_response())
         },
-        Err(e) => {
-            eprintln!("❌ Database error in get_customer_by_id: {}", e);
-            eprintln!("❌ Error details: {:?}", e);
-            eprintln!("❌ Customer ID: {}", customer_id);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+        None => Ok(not_found.into_response()),
     }
 }
 
-pub async fn get_customer_by_email(
-    Path(email): Path<String>,
-) -> Result<Json<Vec<serde_json::Value>>, StatusCode> {
-    let pool = get_db_pool().await;
-    
-    let raw_sql = format!("SELECT customer_id, email, first_name, last_name, dob, sex::text, marital_status::text, phone, 
-        emergency_contact_name, emergency_contact_phone, employment_start_date,
-        street_name, city, state, zip,
-        client_name, client_street_name, client_city, client_state, client_zip,
-        lca_title, lca_salary, lca_code, receipt_number, h1b_start_date, h1b_end_date, login_email, h1b_status::text
-        FROM global_visa_mgmt.h1bcustomer WHERE (email = '{}' OR login_email = '{}')", email.replace("'", "''"), email.replace("'", "''"));
-    
-    match pool.fetch_all(raw_sql.as_str())
-        .await {
-        Ok(rows) => {
-            if rows.is_empty() {
-                Ok(Json(vec![serde_json::json!({
-                    "message": "Data not found"
-                })]))
-            } else {
-                let customers: Vec<serde_json::Value> = rows.into_iter().map(|row| {
-                    serde_json::json!({
-                        "customer_id": row.get::<uuid::Uuid, _>("customer_id"),
-                        "email": row.get::<String, _>("email"),
-                        "first_name": row.get::<String, _>("first_name"),
-                        "last_name": row.get::<String, _>("last_name"),
-                        "dob": row.get::<chrono::NaiveDate, _>("dob"),
-                        "sex": row.get::<String, _>("sex"),
-                        "marital_status": row.get::<String, _>("marital_status"),
-                        "phone": row.get::<String, _>("phone"),
-                        "emergency_contact_name": row.get::<String, _>("emergency_contact_name"),
-                        "emergency_contact_phone": row.get::<String, _>("emergency_contact_phone"),
-                        "employment_start_date": row.get::<chrono::NaiveDate, _>("employment_start_date"),
-                        "street_name": row.get::<String, _>("street_name"),
-                        "city": row.get::<String, _>("city"),
-                        "state": row.get::<String, _>("state"),
-                        "zip": row.get::<String, _>("zip"),
-                        "client_name": row.get::<String, _>("client_name"),
-                        "client_street_name": row.get::<String, _>("client_street_name"),
-                        "client_city": row.get::<String, _>("client_city"),
-                        "client_state": row.get::<String, _>("client_state"),
-                        "client_zip": row.get::<String, _>("client_zip"),
-                        "lca_title": row.get::<String, _>("lca_title"),
-                        "lca_salary": row.get::<rust_decimal::Decimal, _>("lca_salary"),
-                        "lca_code": row.get::<String, _>("lca_code"),
-                        "receipt_number": row.get::<String, _>("receipt_number"),
-                        "h1b_start_date": row.get::<chrono::NaiveDate, _>("h1b_start_date"),
-                        "h1b_end_date": row.get::<chrono::NaiveDate, _>("h1b_end_date"),
-                        "login_email": row.get::<String, _>("login_email"),
-                        "h1b_status": row.get::<String, _>("h1b_status")
-                    })
-                }).collect();
-                Ok(Json(customers))
-            }
-        },
-        Err(e) => {
-            eprintln!("❌ Database error in get_customer_by_email: {}", e);
-            eprintln!("❌ Error details: {:?}", e);
-            eprintln!("❌ Email: {}", email);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+/// How often `patch_customer` re-reads and re-applies a patch when another
+/// write lands between its read and its write.
+const PATCH_ATTEMPTS: usize = 3;
+
+/// Applies an RFC 7396 merge patch (`application/merge-patch+json`, or plain
+/// `application/json`) and returns the whole updated record. Fields left out
+/// of the patch keep their stored values. With `If-Match` the patch only
+/// applies to that version (412 otherwise); without it a concurrent write
+/// just means the patch is applied again on top of it.
+#[utoipa::path(
+    patch,
+    path = "/v1/customers/{id}",
+    tag = "customers",
+    params(("id" = Uuid, Path, description = "Customer id")),
+    request_body(content = UpdateVisaDetailsRequest, content_type = "application/merge-patch+json"),
+    responses(
+        (status = 200, description = "The updated customer", body = CustomerResponse),
+        (status = 400, description = "Body is not JSON", body = Problem),
+        (status = 404, description = "Unknown customer", body = Problem),
+        (status = 409, description = "`email` or `receipt_number` already belongs to another customer", body = Problem),
+        (status = 412, description = "`If-Match` does not name the current version", body = Problem),
+        (status = 415, description = "Not `application/merge-patch+json` or `application/json`", body = Problem),
+        (status = 422, description = "Unknown field, wrong type, `null`, or a value breaking a validation rule (`errors` lists each)", body = Problem)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn patch_customer(
+    State(customers): State<SharedCustomerRepository>,
+    Extension(user): Extension<AuthUser>,
+    Path(customer_id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    tracing::debug!("patch_customer called for customer_id: {}", customer_id);
+    let not_found = || ApiError::not_found(format!("Customer {} not found", customer_id));
+    let id = parse_customer_id(&customer_id).ok_or_else(not_found)?;
+    let expected_version = if_match_version(&headers)?;
+
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(str::trim);
+    if !matches!(content_type, Some("application/merge-patch+json" | "application/json")) {
+        return Err(ApiError::UnsupportedMediaType {
+            detail: "Expected Content-Type: application/merge-patch+json".to_string(),
+        });
+    }
+    let patch: serde_json::Value = serde_json::from_slice(&body)
+        .map_err(|e| ApiError::bad_request(None, format!("body is not valid JSON: {}", e)))?;
+    let patched_fields: Vec<String> = patch.as_object().into_iter().flat_map(|fields| fields.keys().cloned()).collect();
+    let patch = UpdateVisaDetailsRequest::from_merge_patch(patch)
+        .map_err(|(field, detail)| ApiError::InvalidField { field, detail })?;
+
+    for _ in 0..PATCH_ATTEMPTS {
+        let current = customers.find_by_id(id).await?.ok_or_else(not_found)?;
+        let mut update = CustomerUpdate::from(&current);
+        patch.clone().apply(&mut update);
+        // Only the patched fields, so existing data that breaks a rule does
+        // not block unrelated changes
+        let mut validator = Validator::only(&patched_fields);
+        update.check(&mut validator);
+        let warnings = validator.finish().map_err(ApiError::Validation)?;
+
+        let version = expected_version.unwrap_or(current.version);
+        match customers.update(id, &update, Some(version), change_by(&user, "patch")).await {
+            Ok(Some(customer)) => {
+                let body = CustomerResponse::from(customer.clone()).with_warnings(warnings);
+                return Ok((etag_header(customer.version), Json(body)).into_response())
+            },
+            Ok(None) => return Err(not_found()),
+            Err(RepositoryError::VersionMismatch { .. }) if expected_version.is_none() => continue,
+            Err(e) => return Err(e.into()),
         }
     }
+    Err(ApiError::Conflict {
+        detail: format!("Customer {} kept changing while the patch was applied, try again", customer_id),
+    })
 }
 
-pub async fn soft_delete_customer_by_id(
+/// Replaces every field of the record and returns it. The `/v1` successor of
+/// `PUT /update_customer_by_id/{id}`, except that nothing is blanked: every
+/// field is required and the whole record must pass validation.
+#[utoipa::path(
+    put,
+    path = "/v1/customers/{id}",
+    tag = "customers",
+    params(("id" = Uuid, Path, description = "Customer id")),
+    request_body = CustomerUpdate,
+    responses(
+        (status = 200, description = "The updated customer", body = CustomerResponse),
+        (status = 404, description = "Unknown customer", body = Problem),
+        (status = 409, description = "`email` or `receipt_number` already belongs to another customer", body = Problem),
+        (status = 412, description = "`If-Match` does not name the current version", body = Problem),
+        (status = 422, description = "A missing or unknown field, a wrong type, or a value breaking a validation rule (`errors` lists each)", body = Problem)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn replace_customer(
+    State(customers): State<SharedCustomerRepository>,
+    Extension(user): Extension<AuthUser>,
     Path(customer_id): Path<String>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    println!("🔥 soft_delete_customer_by_id function called for customer_id: {}", customer_id);
-    let pool = get_db_pool().await;
-
-    let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos();
-    let check_sql = format!("SELECT h1b_status::text FROM global_visa_mgmt.h1bcustomer WHERE customer_id = '{}'::uuid -- {}", customer_id.replace("'", "''"), timestamp);
-    
-    match pool.fetch_optional(check_sql.as_str()).await {
-        Ok(Some(row)) => {
-            let current_status: String = row.get("h1b_status");
-            if current_status == "Inactive" {
-                return Ok(Json(serde_json::json!({
-                    "message": "Data not found"
-                })));
+    headers: HeaderMap,
+    ApiJson(update): ApiJson<CustomerUpdate>,
+) -> Result<Response, ApiError> {
+    tracing::debug!("replace_customer called for customer_id: {}", customer_id);
+    let not_found = || ApiError::not_found(format!("Customer {} not found", customer_id));
+    let id = parse_customer_id(&customer_id).ok_or_else(not_found)?;
+    let expected_version = if_match_version(&headers)?;
+    let warnings = update.validate().map_err(ApiError::Validation)?;
+
+    let customer = customers
+        .update(id, &update, expected_version, change_by(&user, "update"))
+        .await?
+        .ok_or_else(not_found)?;
+    let body = CustomerResponse::from(customer.clone()).with_warnings(warnings);
+    Ok((etag_header(customer.version), Json(body)).into_response())
+}
+
+/// Largest page `limit` accepted on the list endpoints.
+const MAX_PAGE_SIZE: u32 = 500;
+/// Page size when only a `cursor` is given.
+const DEFAULT_PAGE_SIZE: u32 = 50;
+
+/// Paging and filter parameters for the customer lists. Without `limit` or
+/// `cursor` the whole list comes back as a bare array, as it always has.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListParams {
+    /// Page size, 1 to 500; 50 when only `cursor` is given.
+    pub limit: Option<u32>,
+    /// `next_cursor` from the previous page.
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub include_total: bool,
+    /// Comma-separated fields, `-` for descending, e.g. `h1b_end_date,-last_name`.
+    pub sort: Option<String>,
+    /// Comma-separated fields to return, e.g. `first_name,last_name,h1b_status`.
+    pub fields: Option<String>,
+    pub h1b_status: Option<String>,
+    /// E.g. `RFE Issued`.
+    pub case_status: Option<String>,
+    pub email: Option<String>,
+    pub login_email: Option<String>,
+    pub state: Option<String>,
+    pub client_name: Option<String>,
+    pub lca_code: Option<String>,
+    pub lca_title: Option<String>,
+    pub employment_start_date_from: Option<NaiveDate>,
+    pub employment_start_date_to: Option<NaiveDate>,
+    pub h1b_end_date_from: Option<NaiveDate>,
+    pub h1b_end_date_to: Option<NaiveDate>,
+}
+
+/// Longest text filter value; the filtered columns are at most 255 chars.
+const MAX_FILTER_LENGTH: usize = 255;
+
+impl ListParams {
+    /// Checks the filter parameters. Blank text values are ignored; unknown
+    /// statuses, over-long values and reversed date ranges are rejected with
+    /// the offending parameter.
+    fn filter(&self) -> Result<CustomerFilter, (String, String)> {
+        let text = |name: &str, value: &Option<String>| -> Result<Option<String>, (String, String)> {
+            match value.as_deref().map(str::trim) {
+                Some(v) if v.len() > MAX_FILTER_LENGTH => {
+                    Err((name.to_string(), format!("{} is longer than {} characters", name, MAX_FILTER_LENGTH)))
+                },
+                Some("") | None => Ok(None),
+                Some(v) => Ok(Some(v.to_string())),
+            }
+        };
+        let range = |name: &str, from: Option<NaiveDate>, to: Option<NaiveDate>| -> Result<DateRange, (String, String)> {
+            match (from, to) {
+                (Some(from), Some(to)) if from > to => Err((format!("{}_from", name), format!("{}_from is after {}_to", name, name))),
+                _ => Ok(DateRange { from, to }),
+            }
+        };
+
+        let h1b_status = text("h1b_status", &self.h1b_status)?;
+        if let Some(status) = h1b_status.as_deref() {
+            if !H1B_STATUSES.contains(&status) {
+                return Err(("h1b_status".to_string(), format!("h1b_status must be one of {}", H1B_STATUSES.join(", "))));
             }
-        },
-        Ok(None) => {
-            return Ok(Json(serde_json::json!({
-                "message": "Data not found"
-            })));
-        },
-        Err(e) => {
-            eprintln!("❌ Database error checking status: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
         }
+
+        let case_status = text("case_status", &self.case_status)?
+            .map(|status| status.parse::<CaseStatus>())
+            .transpose()
+            .map_err(|_| {
+                let names: Vec<&str> = CaseStatus::ALL.iter().map(|s| s.as_str()).collect();
+                ("case_status".to_string(), format!("case_status must be one of {}", names.join(", ")))
+            })?;
+
+        Ok(CustomerFilter {
+            h1b_status,
+            case_status,
+            email: text("email", &self.email)?,
+            login_email: text("login_email", &self.login_email)?,
+            state: text("state", &self.state)?,
+            client_name: text("client_name", &self.client_name)?,
+            lca_code: text("lca_code", &self.lca_code)?,
+            lca_title: text("lca_title", &self.lca_title)?,
+            employment_start_date: range("employment_start_date", self.employment_start_date_from, self.employment_start_date_to)?,
+            h1b_end_date: range("h1b_end_date", self.h1b_end_date_from, self.h1b_end_date_to)?,
+        })
     }
+}
 
-    let raw_sql = format!("UPDATE global_visa_mgmt.h1bcustomer SET h1b_status = 'Inactive' WHERE customer_id = '{}'::uuid -- {}", customer_id.replace("'", "''"), timestamp);
-
-    match pool.execute(raw_sql.as_str()).await 
-    {
-        Ok(result) => {
-            if result.rows_affected() == 0 {
-                Ok(Json(serde_json::json!({
-                    "status": 404,
-                    "message": "Record not found in the database",
-                    "customer_id": customer_id
-                })))
-            } else {
-                Ok(Json(serde_json::json!({
-                    "message": "Customer soft deleted successfully",
-                    "customer_id": customer_id,
-                    "rows_affected": result.rows_affected()
-                })))
-            }
-        },
-        Err(e) => {
-            eprintln!("❌ Database error in soft_delete_customer_by_id: {}", e);
-            eprintln!("❌ Soft delete error details: {:?}", e);
-            eprintln!("❌ Customer ID: {}", customer_id);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+/// Parses `sort=h1b_end_date,-last_name`: comma-separated whitelisted
+/// fields, `-` for descending.
+fn parse_sort(spec: &str) -> Result<Vec<SortKey>, String> {
+    let mut keys: Vec<SortKey> = Vec::new();
+    for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (name, descending) = match part.strip_prefix('-') {
+            Some(name) => (name, true),
+            None => (part, false),
+        };
+        let field = SortField::by_name(name).ok_or_else(|| {
+            let allowed: Vec<&str> = SORT_FIELDS.iter().map(|f| f.name).collect();
+            format!("cannot sort by {}; allowed: {}", name, allowed.join(", "))
+        })?;
+        if keys.iter().any(|k| k.field == field) {
+            return Err(format!("{} appears more than once in sort", name));
         }
+        keys.push(SortKey { field, descending });
     }
+    Ok(keys)
 }
 
-pub async fn update_customer_by_id(
-    Path(customer_id): Path<String>,
-    Json(payload): Json<serde_json::Value>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    println!("🔥 update_customer_by_id function called for customer_id: {}", customer_id);
-    let pool = get_db_pool().await;
-
-    let raw_sql = format!("UPDATE global_visa_mgmt.h1bcustomer SET 
-        email = '{}', first_name = '{}', last_name = '{}', dob = '{}', 
-        sex = '{}'::global_visa_mgmt.sex_enum, marital_status = '{}'::global_visa_mgmt.marital_status_enum, 
-        phone = '{}', emergency_contact_name = '{}', emergency_contact_phone = '{}', 
-        employment_start_date = '{}', street_name = '{}', city = '{}', state = '{}', 
-        zip = '{}', client_name = '{}', client_street_name = '{}', client_city = '{}', 
-        client_state = '{}', client_zip = '{}', lca_title = '{}', lca_salary = {}, 
-        lca_code = '{}', receipt_number = '{}', h1b_start_date = '{}', h1b_end_date = '{}', login_email = '{}'
-        WHERE customer_id = '{}'::uuid",
-        payload["email"].as_str().unwrap_or("").replace("'", "''"),
-        payload["first_name"].as_str().unwrap_or("").replace("'", "''"),
-        payload["last_name"].as_str().unwrap_or("").replace("'", "''"),
-        payload["dob"].as_str().unwrap_or(""),
-        payload["sex"].as_str().unwrap_or(""),
-        payload["marital_status"].as_str().unwrap_or(""),
-        payload["phone"].as_str().unwrap_or("").replace("'", "''"),
-        payload["emergency_contact_name"].as_str().unwrap_or("").replace("'", "''"),
-        payload["emergency_contact_phone"].as_str().unwrap_or("").replace("'", "''"),
-        payload["employment_start_date"].as_str().unwrap_or(""),
-        payload["street_name"].as_str().unwrap_or("").replace("'", "''"),
-        payload["city"].as_str().unwrap_or("").replace("'", "''"),
-        payload["state"].as_str().unwrap_or("").replace("'", "''"),
-        payload["zip"].as_str().unwrap_or("").replace("'", "''"),
-        payload["client_name"].as_str().unwrap_or("").replace("'", "''"),
-        payload["client_street_name"].as_str().unwrap_or("").replace("'", "''"),
-        payload["client_city"].as_str().unwrap_or("").replace("'", "''"),
-        payload["client_state"].as_str().unwrap_or("").replace("'", "''"),
-        payload["client_zip"].as_str().unwrap_or("").replace("'", "''"),
-        payload["lca_title"].as_str().unwrap_or("").replace("'", "''"),
-        payload["lca_salary"].as_str().unwrap_or("0"),
-        payload["lca_code"].as_str().unwrap_or("").replace("'", "''"),
-        payload["receipt_number"].as_str().unwrap_or("").replace("'", "''"),
-        payload["h1b_start_date"].as_str().unwrap_or(""),
-        payload["h1b_end_date"].as_str().unwrap_or(""),
-        payload["login_email"].as_str().unwrap_or("").replace("'", "''"),
-        customer_id.replace("'", "''")
-    );
-
-    match pool.execute(raw_sql.as_str()).await {
-        Ok(result) => {
-            if result.rows_affected() > 0 {
-                Ok(Json(serde_json::json!({
-                    "message": "Customer updated successfully",
-                    "customer_id": customer_id,
-                    "rows_affected": result.rows_affected()
-                })))
-            } else {
-                Ok(Json(serde_json::json!({
-                    "message": "Customer not found",
-                    "customer_id": customer_id
-                })))
-            }
-        },
-        Err(e) => {
-            eprintln!("❌ Database error in update_customer_by_id: {}", e);
-            eprintln!("❌ Error details: {:?}", e);
-            eprintln!("❌ Customer ID: {}", customer_id);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+fn sort_spec(sort: &[SortKey]) -> String {
+    sort.iter()
+        .map(|key| format!("{}{}", if key.descending { "-" } else { "" }, key.field.name))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Parses `fields=first_name,h1b_end_date`. `customer_id` is always
+/// returned so rows stay addressable.
+fn parse_fields(spec: &str) -> Result<Vec<String>, String> {
+    let mut fields = vec!["customer_id".to_string()];
+    for name in spec.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        if !CUSTOMER_FIELDS.contains(&name) {
+            return Err(format!("unknown field {}", name));
+        }
+        if !fields.iter().any(|f| f == name) {
+            fields.push(name.to_string());
         }
     }
+    Ok(fields)
 }
 
-pub async fn get_all_customers_with_status() -> Result<Json<Vec<serde_json::Value>>, StatusCode> {
-    println!("🔥 get_all_customers_with_status function called");
-    let pool = get_db_pool().await;
-    
-    let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos();
-    let raw_sql = format!("SELECT customer_id, email, first_name, last_name, dob, sex::text, marital_status::text, phone, 
-        emergency_contact_name, emergency_contact_phone, employment_start_date,
-        street_name, city, state, zip,
-        client_name, client_street_name, client_city, client_state, client_zip,
-        lca_title, lca_salary, lca_code, receipt_number, h1b_start_date, h1b_end_date, login_email, h1b_status::text
-        FROM global_visa_mgmt.h1bcustomer WHERE h1b_status = 'Active' -- {}", timestamp);
-    
-    let rows = pool.fetch_all(raw_sql.as_str())
-    .await
-    .map_err(|e| {
-        eprintln!("❌ Database error in get_all_customers_with_status: {}", e);
-        eprintln!("❌ Error details: {:?}", e);
-        eprintln!("❌ SQL Query: {}", raw_sql);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
-    let customers: Vec<serde_json::Value> = rows.into_iter().map(|row| {
-        serde_json::json!({
-            "customer_id": row.get::<uuid::Uuid, _>("customer_id"),
-            "email": row.get::<String, _>("email"),
-            "first_name": row.get::<String, _>("first_name"),
-            "last_name": row.get::<String, _>("last_name"),
-            "dob": row.get::<chrono::NaiveDate, _>("dob"),
-            "sex": row.get::<String, _>("sex"),
-            "marital_status": row.get::<String, _>("marital_status"),
-            "phone": row.get::<String, _>("phone"),
-            "emergency_contact_name": row.get::<String, _>("emergency_contact_name"),
-            "emergency_contact_phone": row.get::<String, _>("emergency_contact_phone"),
-            "employment_start_date": row.get::<chrono::NaiveDate, _>("employment_start_date"),
-            "street_name": row.get::<String, _>("street_name"),
-            "city": row.get::<String, _>("city"),
-            "state": row.get::<String, _>("state"),
-            "zip": row.get::<String, _>("zip"),
-            "client_name": row.get::<String, _>("client_name"),
-            "client_street_name": row.get::<String, _>("client_street_name"),
-            "client_city": row.get::<String, _>("client_city"),
-            "client_state": row.get::<String, _>("client_state"),
-            "client_zip": row.get::<String, _>("client_zip"),
-            "lca_title": row.get::<String, _>("lca_title"),
-            "lca_salary": row.get::<rust_decimal::Decimal, _>("lca_salary"),
-            "lca_code": row.get::<String, _>("lca_code"),
-            "receipt_number": row.get::<String, _>("receipt_number"),
-            "h1b_start_date": row.get::<chrono::NaiveDate, _>("h1b_start_date"),
-            "h1b_end_date": row.get::<chrono::NaiveDate, _>("h1b_end_date"),
-            "login_email": row.get::<String, _>("login_email"),
-            "h1b_status": row.get::<String, _>("h1b_status")
-        })
-    }).collect();
+/// The customer as JSON, reduced to `fields` when given.
+fn project(customer: Customer, fields: Option<&[String]>) -> serde_json::Value {
+    let record = serde_json::json!(CustomerResponse::from(customer));
+    match (fields, record) {
+        (Some(fields), serde_json::Value::Object(mut map)) => {
+            map.retain(|key, _| fields.contains(key));
+            serde_json::Value::Object(map)
+        },
+        (_, record) => record,
+    }
+}
+
+/// Contents of the opaque `next_cursor`: the sort it was issued for and the
+/// last row's sort values and id.
+#[derive(Debug, Serialize, Deserialize)]
+struct PageCursor {
+    sort: String,
+    values: Vec<String>,
+    after: Uuid,
+}
+
+fn encode_cursor(position: &Position, sort: &[SortKey]) -> String {
+    let cursor = PageCursor {
+        sort: sort_spec(sort),
+        values: position.values.iter().map(|v| v.to_string()).collect(),
+        after: position.customer_id,
+    };
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(&cursor).unwrap_or_default())
+}
+
+/// The position a cursor points at, if it was issued for this `sort`.
+fn decode_cursor(cursor: &str, sort: &[SortKey]) -> Option<Position> {
+    let bytes = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+    let cursor: PageCursor = serde_json::from_slice(&bytes).ok()?;
+    if cursor.sort != sort_spec(sort) || cursor.values.len() != sort.len() {
+        return None;
+    }
+    let values = sort.iter()
+        .zip(&cursor.values)
+        .map(|(key, raw)| key.field.parse(raw))
+        .collect::<Option<Vec<_>>>()?;
+    Some(Position { values, customer_id: cursor.after })
+}
+
+/// Shared body of the list endpoints. `h1b_status` pins the status the
+/// endpoint is limited to; with `always_paged` the response is a page even
+/// without `limit` or `cursor`. Returns 400 for an out-of-range
+/// `limit`, an unknown sort or field, a cursor issued for another sort (or
+/// not by this server), or an invalid filter.
+async fn list_customers(
+    customers: &SharedCustomerRepository,
+    params: ListParams,
+    h1b_status: Option<&str>,
+    always_paged: bool,
+) -> Result<Response, ApiError> {
+    let mut filter = params.filter().map_err(|(field, detail)| ApiError::bad_request(Some(&field), detail))?;
+    if let Some(pinned) = h1b_status {
+        if filter.h1b_status.as_deref().is_some_and(|status| status != pinned) {
+            return Err(ApiError::bad_request(Some("h1b_status"), format!("only {} customers are listed here", pinned)));
+        }
+        filter.h1b_status = Some(pinned.to_string());
+    }
+    let sort = parse_sort(params.sort.as_deref().unwrap_or("")).map_err(|e| ApiError::bad_request(Some("sort"), e))?;
+    let fields = params
+        .fields
+        .as_deref()
+        .map(parse_fields)
+        .transpose()
+        .map_err(|e| ApiError::bad_request(Some("fields"), e))?;
+
+    let paged = always_paged || params.limit.is_some() || params.cursor.is_some();
+    let limit = match params.limit {
+        Some(limit) if limit == 0 || limit > MAX_PAGE_SIZE => {
+            return Err(ApiError::bad_request(Some("limit"), format!("limit must be between 1 and {}", MAX_PAGE_SIZE)));
+        },
+        Some(limit) => Some(limit),
+        None if paged => Some(DEFAULT_PAGE_SIZE),
+        None => None,
+    };
+    let after = match params.cursor.as_deref() {
+        Some(cursor) => Some(decode_cursor(cursor, &sort).ok_or_else(|| {
+            ApiError::bad_request(Some("cursor"), "cursor is invalid or was issued for a different sort")
+        })?),
+        None => None,
+    };
+
+    let query = CustomerListQuery {
+        filter,
+        sort,
+        limit,
+        after,
+        with_total: params.include_total,
+    };
+    let page = customers.list(&query).await?;
+
+    let items: Vec<serde_json::Value> = page.customers.into_iter().map(|c| project(c, fields.as_deref())).collect();
+    if !paged && !params.include_total {
+        return Ok(Json(serde_json::json!(items)).into_response());
+    }
+    let mut body = serde_json::json!({
+        "items": items,
+        "next_cursor": page.next_after.map(|position| encode_cursor(&position, &query.sort))
+    });
+    if let Some(total) = page.total {
+        body["total"] = serde_json::json!(total);
+    }
+    Ok(Json(body).into_response())
+}
 
-    Ok(Json(customers))
+#[utoipa::path(
+    get,
+    path = "/customers",
+    tag = "legacy",
+    params(ListParams),
+    responses(
+        (status = 200, description = "Active customers: an array, or a page with `limit` or `cursor`", body = CustomerPage),
+        (status = 400, description = "Invalid parameter", body = Problem)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn get_all_customers_with_status(
+    State(customers): State<SharedCustomerRepository>,
+    ApiQuery(params): ApiQuery<ListParams>,
+) -> Result<Response, ApiError> {
+    tracing::debug!("get_all_customers_with_status called");
+    list_customers(&customers, params, Some("Active"), false).await
 }
 
+#[utoipa::path(
+    get,
+    path = "/h1b_customer/by_login_email/{login_email}",
+    tag = "legacy",
+    params(("login_email" = String, Path, description = "Login email")),
+    responses((status = 200, description = "Active customers with this login, or `[{\"message\": \"Data not found\"}]`", body = [CustomerResponse])),
+    security(("bearer_auth" = []))
+)]
 pub async fn get_customer_by_login_email(
+    State(customers): State<SharedCustomerRepository>,
     Path(login_email): Path<String>,
-) -> Result<Json<Vec<serde_json::Value>>, StatusCode> {
-    let pool = get_db_pool().await;
-    
-    let raw_sql = format!("SELECT customer_id, email, first_name, last_name, dob, sex::text, marital_status::text, phone, 
-        emergency_contact_name, emergency_contact_phone, employment_start_date,
-        street_name, city, state, zip,
-        client_name, client_street_name, client_city, client_state, client_zip,
-        lca_title, lca_salary, lca_code, receipt_number, h1b_start_date, h1b_end_date, login_email, h1b_status::text
-        FROM global_visa_mgmt.h1bcustomer WHERE login_email = '{}' AND h1b_status = 'Active'", login_email.replace("'", "''"));
-    
-    match pool.fetch_all(raw_sql.as_str())
-        .await {
-        Ok(rows) => {
-            if rows.is_empty() {
-                Ok(Json(vec![serde_json::json!({
-                    "message": "Data not found"
-                })]))
-            } else {
-                let customers: Vec<serde_json::Value> = rows.into_iter().map(|row| {
-                    serde_json::json!({
-                        "customer_id": row.get::<uuid::Uuid, _>("customer_id"),
-                        "email": row.get::<String, _>("email"),
-                        "first_name": row.get::<String, _>("first_name"),
-                        "last_name": row.get::<String, _>("last_name"),
-                        "dob": row.get::<chrono::NaiveDate, _>("dob"),
-                        "sex": row.get::<String, _>("sex"),
-                        "marital_status": row.get::<String, _>("marital_status"),
-                        "phone": row.get::<String, _>("phone"),
-                        "emergency_contact_name": row.get::<String, _>("emergency_contact_name"),
-                        "emergency_contact_phone": row.get::<String, _>("emergency_contact_phone"),
-                        "employment_start_date": row.get::<chrono::NaiveDate, _>("employment_start_date"),
-                        "street_name": row.get::<String, _>("street_name"),
-                        "city": row.get::<String, _>("city"),
-                        "state": row.get::<String, _>("state"),
-                        "zip": row.get::<String, _>("zip"),
-                        "client_name": row.get::<String, _>("client_name"),
-                        "client_street_name": row.get::<String, _>("client_street_name"),
-                        "client_city": row.get::<String, _>("client_city"),
-                        "client_state": row.get::<String, _>("client_state"),
-                        "client_zip": row.get::<String, _>("client_zip"),
-                        "lca_title": row.get::<String, _>("lca_title"),
-                        "lca_salary": row.get::<rust_decimal::Decimal, _>("lca_salary"),
-                        "lca_code": row.get::<String, _>("lca_code"),
-                        "receipt_number": row.get::<String, _>("receipt_number"),
-                        "h1b_start_date": row.get::<chrono::NaiveDate, _>("h1b_start_date"),
-                        "h1b_end_date": row.get::<chrono::NaiveDate, _>("h1b_end_date"),
-                        "login_email": row.get::<String, _>("login_email"),
-                        "h1b_status": row.get::<String, _>("h1b_status")
-                    })
-                }).collect();
-                Ok(Json(customers))
-            }
-        },
-        Err(e) => {
-            eprintln!("❌ Database error in get_customer_by_login_email: {}", e);
-            eprintln!("❌ Error details: {:?}", e);
-            eprintln!("❌ Login Email: {}", login_email);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+) -> Result<Json<Vec<serde_json::Value>>, ApiError> {
+    let rows = customers.find_by_login_email(&login_email, Some("Active")).await?;
+    if rows.is_empty() {
+        Ok(Json(vec![serde_json::json!({
+            "message": "Data not found"
+        })]))
+    } else {
+        Ok(Json(rows.into_iter().map(|customer| serde_json::json!(CustomerResponse::from(customer))).collect()))
     }
 }
-pub async fn get_all_customers_no_filter() -> Result<Json<Vec<serde_json::Value>>, StatusCode> {
-    println!("🔥 get_all_customers_no_filter function called");
-    let pool = get_db_pool().await;
-    
-    let timestamp = get_timestamp();
-    let raw_sql = format!("SELECT customer_id, email, first_name, last_name, dob, sex::text, marital_status::text, phone, 
-        emergency_contact_name, emergency_contact_phone, employment_start_date,
-        street_name, city, state, zip,
-        client_name, client_street_name, client_city, client_state, client_zip,
-        lca_title, lca_salary, lca_code, receipt_number, h1b_start_date, h1b_end_date, login_email, h1b_status::text
-        FROM global_visa_mgmt.h1bcustomer -- {}", timestamp);
-    
-    let rows = pool.fetch_all(raw_sql.as_str())
-    .await
-    .map_err(|e| {
-        eprintln!("❌ Database error in get_all_customers_no_filter: {}", e);
-        eprintln!("❌ Error details: {:?}", e);
-        eprintln!("❌ SQL Query: {}", raw_sql);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
-    let customers: Vec<serde_json::Value> = rows.into_iter().map(|row| {
-        serde_json::json!({
-            "customer_id": row.get::<uuid::Uuid, _>("customer_id"),
-            "email": row.get::<String, _>("email"),
-            "first_name": row.get::<String, _>("first_name"),
-            "last_name": row.get::<String, _>("last_name"),
-            "dob": row.get::<chrono::NaiveDate, _>("dob"),
-            "sex": row.get::<String, _>("sex"),
-            "marital_status": row.get::<String, _>("marital_status"),
-            "phone": row.get::<String, _>("phone"),
-            "emergency_contact_name": row.get::<String, _>("emergency_contact_name"),
-            "emergency_contact_phone": row.get::<String, _>("emergency_contact_phone"),
-            "employment_start_date": row.get::<chrono::NaiveDate, _>("employment_start_date"),
-            "street_name": row.get::<String, _>("street_name"),
-            "city": row.get::<String, _>("city"),
-            "state": row.get::<String, _>("state"),
-            "zip": row.get::<String, _>("zip"),
-            "client_name": row.get::<String, _>("client_name"),
-            "client_street_name": row.get::<String, _>("client_street_name"),
-            "client_city": row.get::<String, _>("client_city"),
-            "client_state": row.get::<String, _>("client_state"),
-            "client_zip": row.get::<String, _>("client_zip"),
-            "lca_title": row.get::<String, _>("lca_title"),
-            "lca_salary": row.get::<rust_decimal::Decimal, _>("lca_salary"),
-            "lca_code": row.get::<String, _>("lca_code"),
-            "receipt_number": row.get::<String, _>("receipt_number"),
-            "h1b_start_date": row.get::<chrono::NaiveDate, _>("h1b_start_date"),
-            "h1b_end_date": row.get::<chrono::NaiveDate, _>("h1b_end_date"),
-            "login_email": row.get::<String, _>("login_email"),
-            "h1b_status": row.get::<String, _>("h1b_status")
-        })
-    }).collect();
 
-    Ok(Json(customers))
+#[utoipa::path(
+    get,
+    path = "/h1b_customer/all",
+    tag = "legacy",
+    params(ListParams),
+    responses(
+        (status = 200, description = "Customers: an array, or a page with `limit` or `cursor`", body = CustomerPage),
+        (status = 400, description = "Invalid parameter", body = Problem)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn get_all_customers_no_filter(
+    State(customers): State<SharedCustomerRepository>,
+    ApiQuery(params): ApiQuery<ListParams>,
+) -> Result<Response, ApiError> {
+    tracing::debug!("get_all_customers_no_filter called");
+    list_customers(&customers, params, None, false).await
+}
+
+/// `GET /v1/customers`: every customer, always as a page.
+#[utoipa::path(
+    get,
+    path = "/v1/customers",
+    tag = "customers",
+    params(ListParams),
+    responses(
+        (status = 200, description = "One page of customers", body = CustomerPage),
+        (status = 400, description = "Invalid parameter", body = Problem)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn list_all_customers(
+    State(customers): State<SharedCustomerRepository>,
+    ApiQuery(params): ApiQuery<ListParams>,
+) -> Result<Response, ApiError> {
+    tracing::debug!("list_all_customers called");
+    list_customers(&customers, params, None, true).await
+}
+
+/// Longest page `limit` accepted on the search endpoint.
+const MAX_SEARCH_RESULTS: u32 = 100;
+const DEFAULT_SEARCH_RESULTS: u32 = 20;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchParams {
+    /// 2 to 100 characters.
+    pub q: Option<String>,
+    /// 1 to 100, default 20.
+    pub limit: Option<u32>,
+}
+
+/// Fuzzy lookup over names, emails, client names and receipt numbers. Each
+/// result names the field that matched and the char ranges of its value to
+/// highlight.
+#[utoipa::path(
+    get,
+    path = "/v1/customers/search",
+    tag = "customers",
+    params(SearchParams),
+    responses(
+        (status = 200, description = "Matches, best first", body = SearchResults),
+        (status = 400, description = "Invalid `q` or `limit`", body = Problem)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn search_customers(
+    State(customers): State<SharedCustomerRepository>,
+    ApiQuery(params): ApiQuery<SearchParams>,
+) -> Result<Response, ApiError> {
+    tracing::debug!("search_customers called");
+    let query = params.q.as_deref().unwrap_or("").trim();
+    if query.chars().count() < 2 || query.chars().count() > 100 {
+        return Err(ApiError::bad_request(Some("q"), "q must be between 2 and 100 characters"));
+    }
+    let limit = params.limit.unwrap_or(DEFAULT_SEARCH_RESULTS);
+    if limit == 0 || limit > MAX_SEARCH_RESULTS {
+        return Err(ApiError::bad_request(Some("limit"), "limit must be between 1 and 100"));
+    }
+
+    let hits = customers.search(query, limit).await?;
+
+    let results: Vec<serde_json::Value> = hits
+        .into_iter()
+        .map(|hit| serde_json::json!({
+            "score": hit.score,
+            "matched_field": hit.matched_field,
+            "highlight": search::highlight(query, &hit.matched_value),
+            "matched_value": hit.matched_value,
+            "customer": CustomerResponse::from(hit.customer)
+        }))
+        .collect();
+    Ok(Json(serde_json::json!({
+        "query": query,
+        "results": results
+    })).into_response())
+}
+
+const MAX_OCCUPATION_RESULTS: u32 = 50;
+const DEFAULT_OCCUPATION_RESULTS: u32 = 10;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OccupationParams {
+    /// Start of a SOC code (`15-12`), or words of a job title (`software eng`);
+    /// 1 to 100 characters.
+    pub q: Option<String>,
+    /// 1 to 50, default 10.
+    pub limit: Option<u32>,
 }
 
+/// Occupations of the bundled 2018 SOC taxonomy that `lca_code` is checked
+/// against, for autocompleting it by code or by job title.
+#[utoipa::path(
+    get,
+    path = "/v1/occupations",
+    tag = "occupations",
+    params(OccupationParams),
+    responses(
+        (status = 200, description = "Matches: in code order for a code, best first for a title", body = OccupationResults),
+        (status = 400, description = "Invalid `q` or `limit`", body = Problem)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn lookup_occupations(ApiQuery(params): ApiQuery<OccupationParams>) -> Result<Response, ApiError> {
+    tracing::debug!("lookup_occupations called");
+    let query = params.q.as_deref().unwrap_or("").trim();
+    if query.is_empty() || query.chars().count() > 100 {
+        return Err(ApiError::bad_request(Some("q"), "q must be between 1 and 100 characters"));
+    }
+    let limit = params.limit.unwrap_or(DEFAULT_OCCUPATION_RESULTS);
+    if limit == 0 || limit > MAX_OCCUPATION_RESULTS {
+        return Err(ApiError::bad_request(Some("limit"), "limit must be between 1 and 50"));
+    }
+
+    Ok(Json(serde_json::json!({
+        "query": query,
+        "occupations": soc::taxonomy().lookup(query, limit as usize)
+    })).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/occupations/{code}",
+    tag = "occupations",
+    params(("code" = String, Path, description = "SOC code like `15-1252`, or O*NET code like `15-1252.00`")),
+    responses(
+        (status = 200, description = "The occupation", body = Occupation),
+        (status = 404, description = "Not a 2018 SOC detailed occupation; `detail` says whether it is malformed, retired or unknown", body = Problem)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn get_occupation(Path(code): Path<String>) -> Result<Json<soc::Occupation>, ApiError> {
+    tracing::debug!("get_occupation called for code: {}", code);
+    match soc::taxonomy().find(&code) {
+        Ok(occupation) => Ok(Json(occupation.clone())),
+        Err(e) => Err(ApiError::not_found(format!("Occupation {}: {}", code, e))),
+    }
+}
+
+#[utoipa::path(
+    patch,
+    path = "/h1b_customer/activate/{customer_id}",
+    tag = "legacy",
+    params(("customer_id" = Uuid, Path, description = "Customer id")),
+    responses(
+        (status = 200, description = "Customer activated", body = StatusChange),
+        (status = 404, description = "Unknown customer", body = Problem),
+        (status = 409, description = "Already active", body = Problem)
+    ),
+    security(("bearer_auth" = []))
+)]
 pub async fn activate_customer_by_id(
+    State(customers): State<SharedCustomerRepository>,
+    Extension(user): Extension<AuthUser>,
     Path(customer_id): Path<String>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    println!("🔥 activate_customer_by_id function called for customer_id: {}", customer_id);
-    let pool = get_db_pool().await;
-
-    let timestamp = get_timestamp();
-    let check_sql = format!("SELECT h1b_status::text FROM global_visa_mgmt.h1bcustomer WHERE customer_id = '{}'::uuid -- {}", customer_id.replace("'", "''"), timestamp);
-    
-    match pool.fetch_optional(check_sql.as_str()).await {
-        Ok(Some(row)) => {
-            let current_status: String = row.get("h1b_status");
-            if current_status == "Active" {
-                return Ok(Json(serde_json::json!({
-                    "message": "This customer is already active."
-                })));
-            }
-        },
-        Ok(None) => {
-            return Ok(Json(serde_json::json!({
-                "status": 404,
-                "message": "Record not found in the database",
-                "customer_id": customer_id
-            })));
-        },
-        Err(e) => {
-            eprintln!("❌ Database error checking status: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
+) -> Result<Response, ApiError> {
+    tracing::debug!("activate_customer_by_id called for customer_id: {}", customer_id);
+    change_customer_status(&customers, &user, &customer_id, "Active", "activate", "Customer activated successfully").await
+}
+
+/// A part of the customer record served at `/customers/:id/<NAME>`.
+pub trait SectionResource:
+    for<'a> From<&'a Customer>
+    + Into<CustomerSection>
+    + Validate
+    + Serialize
+    + DeserializeOwned
+    + for<'s> ToSchema<'s>
+    + Send
+    + 'static
+{
+    const NAME: &'static str;
+}
+
+impl SectionResource for PersonDetails {
+    const NAME: &'static str = "person";
+}
+
+impl SectionResource for ResidentialAddress {
+    const NAME: &'static str = "address";
+}
+
+impl SectionResource for ClientPlacement {
+    const NAME: &'static str = "placement";
+}
+
+impl SectionResource for LcaDetails {
+    const NAME: &'static str = "lca";
+}
+
+impl SectionResource for PetitionDetails {
+    const NAME: &'static str = "petition";
+}
+
+pub async fn get_customer_section<S: SectionResource>(
+    State(customers): State<SharedCustomerRepository>,
+    Path(customer_id): Path<String>,
+) -> Result<(HeaderMap, Json<S>), ApiError> {
+    let not_found = || ApiError::not_found(format!("Customer {} not found", customer_id));
+    let id = parse_customer_id(&customer_id).ok_or_else(not_found)?;
+
+    let customer = customers.find_by_id(id).await?.ok_or_else(not_found)?;
+    Ok((etag_header(customer.version), Json(S::from(&customer))))
+}
+
+pub async fn update_customer_section<S: SectionResource>(
+    State(customers): State<SharedCustomerRepository>,
+    Extension(user): Extension<AuthUser>,
+    Path(customer_id): Path<String>,
+    headers: HeaderMap,
+    ApiJson(payload): ApiJson<S>,
+) -> Result<Response, ApiError> {
+    tracing::debug!("update_customer_section ({}) called for customer_id: {}", S::NAME, customer_id);
+    let not_found = || ApiError::not_found(format!("Customer {} not found", customer_id));
+    let id = parse_customer_id(&customer_id).ok_or_else(not_found)?;
+    let expected_version = if_match_version(&headers)?;
+    let warnings = payload.validate().map_err(ApiError::Validation)?;
+    let action = format!("update_{}", S::NAME);
+
+    let customer = customers
+        .update_section(id, payload.into(), expected_version, change_by(&user, &action))
+        .await?
+        .ok_or_else(not_found)?;
+    let mut body = serde_json::to_value(S::from(&customer)).map_err(|e| ApiError::Internal(e.to_string()))?;
+    add_warnings(&mut body, warnings);
+    Ok((etag_header(customer.version), Json(body)).into_response())
+}
+
+/// Every stored version of a customer, oldest first, each with the fields
+/// that changed relative to the version before it.
+#[utoipa::path(
+    get,
+    path = "/v1/customers/{id}/history",
+    tag = "customers",
+    params(("id" = Uuid, Path, description = "Customer id")),
+    responses(
+        (status = 200, description = "Every version, oldest first", body = CustomerHistory),
+        (status = 404, description = "Unknown customer", body = Problem)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn get_customer_history(
+    State(customers): State<SharedCustomerRepository>,
+    Path(customer_id): Path<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let not_found = || ApiError::not_found(format!("Customer {} not found", customer_id));
+    let id = parse_customer_id(&customer_id).ok_or_else(not_found)?;
+
+    let revisions = customers.history(id).await?;
+    if revisions.is_empty() {
+        return Err(not_found());
     }
 
-    let raw_sql = format!("UPDATE global_visa_mgmt.h1bcustomer SET h1b_status = 'Active' WHERE customer_id = '{}'::uuid -- {}", customer_id.replace("'", "''"), timestamp);
-
-    match pool.execute(raw_sql.as_str()).await {
-        Ok(result) => {
-            if result.rows_affected() == 0 {
-                Ok(Json(serde_json::json!({
-                    "status": 404,
-                    "message": "Record not found in the database",
-                    "customer_id": customer_id
-                })))
-            } else {
-                let select_sql = format!("SELECT customer_id, email, first_name, last_name, dob, sex::text, marital_status::text, phone, 
-                    emergency_contact_name, emergency_contact_phone, employment_start_date,
-                    street_name, city, state, zip,
-                    client_name, client_street_name, client_city, client_state, client_zip,
-                    lca_title, lca_salary, lca_code, receipt_number, h1b_start_date, h1b_end_date, login_email, h1b_status::text
-                    FROM global_visa_mgmt.h1bcustomer WHERE customer_id = '{}'::uuid -- {}", customer_id.replace("'", "''"), timestamp);
-                
-                match pool.fetch_optional(select_sql.as_str()).await {
-                    Ok(Some(row)) => {
-                        Ok(Json(serde_json::json!({
-                            "message": "Customer activated successfully",
-                            "customer_id": customer_id,
-                            "rows_affected": result.rows_affected(),
-                            "updated_record": {
-                                "customer_id": row.get::<uuid::Uuid, _>("customer_id"),
-                                "email": row.get::<String, _>("email"),
-                                "first_name": row.get::<String, _>("first_name"),
-                                "last_name": row.get::<String, _>("last_name"),
-                                "dob": row.get::<chrono::NaiveDate, _>("dob"),
-                                "sex": row.get::<String, _>("sex"),
-                                "marital_status": row.get::<String, _>("marital_status"),
-                                "phone": row.get::<String, _>("phone"),
-                                "emergency_contact_name": row.get::<String, _>("emergency_contact_name"),
-                                "emergency_contact_phone": row.get::<String, _>("emergency_contact_phone"),
-                                "employment_start_date": row.get::<chrono::NaiveDate, _>("employment_start_date"),
-                                "street_name": row.get::<String, _>("street_name"),
-                                "city": row.get::<String, _>("city"),
-                                "state": row.get::<String, _>("state"),
-                                "zip": row.get::<String, _>("zip"),
-                                "client_name": row.get::<String, _>("client_name"),
-                                "client_street_name": row.get::<String, _>("client_street_name"),
-                                "client_city": row.get::<String, _>("client_city"),
-                                "client_state": row.get::<String, _>("client_state"),
-                                "client_zip": row.get::<String, _>("client_zip"),
-                                "lca_title": row.get::<String, _>("lca_title"),
-                                "lca_salary": row.get::<rust_decimal::Decimal, _>("lca_salary"),
-                                "lca_code": row.get::<String, _>("lca_code"),
-                                "receipt_number": row.get::<String, _>("receipt_number"),
-                                "h1b_start_date": row.get::<chrono::NaiveDate, _>("h1b_start_date"),
-                                "h1b_end_date": row.get::<chrono::NaiveDate, _>("h1b_end_date"),
-                                "login_email": row.get::<String, _>("login_email"),
-                                "h1b_status": row.get::<String, _>("h1b_status")
-                            }
-                        })))
-                    },
-                    Ok(None) => {
-                        Ok(Json(serde_json::json!({
-                            "message": "Customer activated successfully",
-                            "customer_id": customer_id,
-                            "rows_affected": result.rows_affected()
-                        })))
-                    },
-                    Err(e) => {
-                        eprintln!("❌ Database error fetching updated record: {}", e);
-                        Ok(Json(serde_json::json!({
-                            "message": "Customer activated successfully",
-                            "customer_id": customer_id,
-                            "rows_affected": result.rows_affected()
-                        })))
-                    }
-                }
-            }
-        },
-        Err(e) => {
-            eprintln!("❌ Database error in activate_customer_by_id: {}", e);
-            eprintln!("❌ Error details: {:?}", e);
-            eprintln!("❌ Customer ID: {}", customer_id);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+    let versions: Vec<CustomerHistoryEntry> = revisions
+        .iter()
+        .enumerate()
+        .map(|(i, revision)| CustomerHistoryEntry {
+            version: revision.version,
+            changed_at: revision.changed_at,
+            actor: revision.actor.clone(),
+            action: revision.action.clone(),
+            changes: revision.changes_since(i.checked_sub(1).map(|prev| &revisions[prev])),
+        })
+        .collect();
+
+    Ok(Json(serde_json::json!({
+        "customer_id": id,
+        "versions": versions
+    })))
+}
+
+/// Moves the customer's case to another status, if the transition table
+/// allows it from the current one, and logs the move with its reason and
+/// the caller as actor.
+#[utoipa::path(
+    post,
+    path = "/v1/customers/{id}/transitions",
+    tag = "customers",
+    params(("id" = Uuid, Path, description = "Customer id")),
+    request_body = CaseTransitionRequest,
+    responses(
+        (status = 201, description = "The logged transition; the new version is in ETag", body = CaseTransition),
+        (status = 404, description = "Unknown customer", body = Problem),
+        (status = 409, description = "The case cannot move to that status from its current one", body = Problem),
+        (status = 412, description = "`If-Match` does not name the current version", body = Problem),
+        (status = 422, description = "`to` is not a case status or `reason` is blank", body = Problem)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn create_case_transition(
+    State(customers): State<SharedCustomerRepository>,
+    Extension(user): Extension<AuthUser>,
+    Path(customer_id): Path<String>,
+    headers: HeaderMap,
+    ApiJson(payload): ApiJson<CaseTransitionRequest>,
+) -> Result<Response, ApiError> {
+    tracing::debug!("create_case_transition called for customer_id: {}", customer_id);
+    let not_found = || ApiError::not_found(format!("Customer {} not found", customer_id));
+    let id = parse_customer_id(&customer_id).ok_or_else(not_found)?;
+    let expected_version = if_match_version(&headers)?;
+    payload.validate().map_err(ApiError::Validation)?;
+    let to = payload
+        .to
+        .parse::<CaseStatus>()
+        .map_err(|e| ApiError::InvalidEnumValue { field: "to".to_string(), value: e.0 })?;
+
+    let (customer, transition) = customers
+        .transition(id, to, payload.reason.trim(), expected_version, change_by(&user, "transition"))
+        .await?
+        .ok_or_else(not_found)?;
+    Ok((StatusCode::CREATED, etag_header(customer.version), Json(transition)).into_response())
+}
+
+/// Every case status change of a customer, oldest first.
+#[utoipa::path(
+    get,
+    path = "/v1/customers/{id}/transitions",
+    tag = "customers",
+    params(("id" = Uuid, Path, description = "Customer id")),
+    responses(
+        (status = 200, description = "The current case status and every transition to it", body = CaseTransitions),
+        (status = 404, description = "Unknown customer", body = Problem)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn get_case_transitions(
+    State(customers): State<SharedCustomerRepository>,
+    Path(customer_id): Path<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let not_found = || ApiError::not_found(format!("Customer {} not found", customer_id));
+    let id = parse_customer_id(&customer_id).ok_or_else(not_found)?;
+
+    let customer = customers.find_by_id(id).await?.ok_or_else(not_found)?;
+    let transitions = customers.transitions(id).await?;
+    Ok(Json(serde_json::json!({
+        "customer_id": id,
+        "case_status": customer.case_status,
+        "allowed_transitions": customer.case_status.next(),
+        "transitions": transitions
+    })))
+}
+
+fn wage_data(wages: &SharedWages) -> Result<&PrevailingWages, ApiError> {
+    wages.as_deref().ok_or(ApiError::WageDataNotLoaded)
+}
+
+/// How the customer's `lca_salary` compares with the OFLC prevailing wage
+/// for `lca_code` in the wage area of `client_zip`.
+#[utoipa::path(
+    get,
+    path = "/v1/customers/{id}/wage-compliance",
+    tag = "customers",
+    params(("id" = Uuid, Path, description = "Customer id")),
+    responses(
+        (status = 200, description = "The wage level met, or why it could not be checked", body = WageCompliance),
+        (status = 404, description = "Unknown customer", body = Problem),
+        (status = 503, description = "No prevailing wage data is loaded, or storage is unavailable", body = Problem)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn get_wage_compliance(
+    State(customers): State<SharedCustomerRepository>,
+    State(wages): State<SharedWages>,
+    Path(customer_id): Path<String>,
+) -> Result<Json<WageCompliance>, ApiError> {
+    tracing::debug!("get_wage_compliance called for customer_id: {}", customer_id);
+    let wages = wage_data(&wages)?;
+    let not_found = || ApiError::not_found(format!("Customer {} not found", customer_id));
+    let id = parse_customer_id(&customer_id).ok_or_else(not_found)?;
+
+    let customer = customers.find_by_id(id).await?.ok_or_else(not_found)?;
+    Ok(Json(wages.check(&customer)))
+}
+
+fn wage_report_entry(customer: &Customer, compliance: WageCompliance) -> serde_json::Value {
+    serde_json::json!({
+        "customer_id": customer.customer_id,
+        "first_name": customer.first_name,
+        "last_name": customer.last_name,
+        "email": customer.email,
+        "client_name": customer.client_name,
+        "lca_title": customer.lca_title,
+        "compliance": compliance
+    })
+}
+
+/// Active customers paid below the Level I prevailing wage at their
+/// worksite, largest shortfall first, and those that could not be checked.
+#[utoipa::path(
+    get,
+    path = "/v1/reports/below-level-1",
+    tag = "reports",
+    responses(
+        (status = 200, description = "Customers below Level I, and those without a wage to compare against", body = BelowLevel1Report),
+        (status = 503, description = "No prevailing wage data is loaded, or storage is unavailable", body = Problem)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn below_level_1_report(
+    State(customers): State<SharedCustomerRepository>,
+    State(wages): State<SharedWages>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    tracing::debug!("below_level_1_report called");
+    let wages = wage_data(&wages)?;
+    let query = CustomerListQuery {
+        filter: CustomerFilter { h1b_status: Some("Active".to_string()), ..CustomerFilter::default() },
+        ..CustomerListQuery::default()
+    };
+    let active = customers.list(&query).await?.customers;
+
+    let mut below = Vec::new();
+    let mut unchecked = Vec::new();
+    for customer in &active {
+        let compliance = wages.check(customer);
+        match compliance.status {
+            ComplianceStatus::BelowLevel1 => below.push((customer, compliance)),
+            ComplianceStatus::Unknown => unchecked.push(wage_report_entry(customer, compliance)),
+            ComplianceStatus::Compliant => {},
         }
     }
-}
\ No newline at end of file
+    below.sort_by_key(|(_, compliance)| std::cmp::Reverse(compliance.shortfall));
+
+    Ok(Json(serde_json::json!({
+        "checked": active.len(),
+        "below_level_1": below.into_iter().map(|(c, compliance)| wage_report_entry(c, compliance)).collect::<Vec<_>>(),
+        "unchecked": unchecked
+    })))
+}
+
+#[cfg(test)]
+mod tests;
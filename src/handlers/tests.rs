@@ -0,0 +1,600 @@
+//! Handler tests over the in-memory repository, through the real routes.
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, HeaderMap, Method, Request, StatusCode},
+    Extension, Router,
+};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+use crate::config::{AppConfig, StorageBackend};
+use crate::middleware::auth::AuthUser;
+use crate::state::AppState;
+
+const UNKNOWN_ID: &str = "00000000-0000-0000-0000-000000000000";
+
+async fn app() -> Router {
+    let config = AppConfig { port: "0".to_string(), storage: StorageBackend::Memory, wage_data_dir: None };
+    let state = AppState::build(config).await.unwrap();
+    let user = AuthUser { id: "user-1".to_string(), email: Some("tester@example.com".to_string()) };
+    crate::customer_routes(&state).layer(Extension(user)).with_state(state)
+}
+
+async fn send(app: &Router, method: Method, uri: &str, body: Option<Value>) -> (StatusCode, HeaderMap, Value) {
+    send_with(app, Request::builder().method(method).uri(uri), body).await
+}
+
+async fn send_with(
+    app: &Router,
+    request: axum::http::request::Builder,
+    body: Option<Value>,
+) -> (StatusCode, HeaderMap, Value) {
+    let request = match body {
+        Some(body) => request.header(header::CONTENT_TYPE, "application/json").body(Body::from(body.to_string())),
+        None => request.body(Body::empty()),
+    };
+    let response = app.clone().oneshot(request.unwrap()).await.unwrap();
+    let (parts, body) = response.into_parts();
+    let bytes = to_bytes(body, usize::MAX).await.unwrap();
+    let body = if bytes.is_empty() { Value::Null } else { serde_json::from_slice(&bytes).unwrap() };
+    (parts.status, parts.headers, body)
+}
+
+fn new_customer(n: u32) -> Value {
+    json!({
+        "email": format!("jane{}@example.com", n),
+        "login_email": format!("jane{}.login@example.com", n),
+        "first_name": "Jane",
+        "last_name": "O'Neil",
+        "dob": "1990-05-01",
+        "sex": "Female",
+        "marital_status": "Single",
+        "phone": "+1-555-123-4567",
+        "emergency_contact_name": "Bob",
+        "emergency_contact_phone": "+1-555-765-4321",
+        "employment_start_date": "2024-02-01",
+        "street_name": "1 Main St",
+        "city": "Austin",
+        "state": "TX",
+        "zip": "73301",
+        "client_name": "Acme Corp",
+        "client_street_name": "2 Market St",
+        "client_city": "Dallas",
+        "client_state": "TX",
+        "client_zip": "75201",
+        "lca_title": "Software Developer",
+        "lca_salary": 120000,
+        "lca_code": "15-1252",
+        "receipt_number": format!("EAC24{:08}", n),
+        "h1b_start_date": "2024-10-01",
+        "h1b_end_date": "2027-09-30"
+    })
+}
+
+async fn create(app: &Router, n: u32) -> String {
+    let (status, _, body) = send(app, Method::POST, "/v1/customers", Some(new_customer(n))).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    body["customer_id"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn create_returns_the_stored_record() {
+    let app = app().await;
+    let (status, headers, body) = send(&app, Method::POST, "/v1/customers", Some(new_customer(1))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let id = body["customer_id"].as_str().unwrap();
+    assert_eq!(headers[header::LOCATION], format!("/v1/customers/{}", id));
+    assert_eq!(headers[header::ETAG], "\"1\"");
+    assert_eq!(body["email"], "jane1@example.com");
+    assert_eq!(body["h1b_status"], "Active");
+    assert_eq!(body["case_status"], "Draft");
+    assert_eq!(body["version"], 1);
+
+    let (status, _, body) = send(&app, Method::POST, "/h1b_customer/create", Some(new_customer(2))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body["rows_affected"], 1);
+    assert_eq!(body["created_record"]["email"], "jane2@example.com");
+}
+
+#[tokio::test]
+async fn create_rejects_invalid_fields_and_duplicates() {
+    let app = app().await;
+    let mut invalid = new_customer(1);
+    invalid["zip"] = json!("7870");
+    invalid["first_name"] = json!(" ");
+    let (status, _, body) = send(&app, Method::POST, "/v1/customers", Some(invalid)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "validation_failed");
+    let fields: Vec<&str> = body["errors"].as_array().unwrap().iter().map(|e| e["field"].as_str().unwrap()).collect();
+    assert_eq!(fields, ["first_name", "zip"]);
+
+    let id = create(&app, 1).await;
+    let mut duplicate = new_customer(2);
+    duplicate["email"] = json!("JANE1@example.com");
+    let (status, _, body) = send(&app, Method::POST, "/v1/customers", Some(duplicate)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "duplicate");
+    assert_eq!(body["existing_customer_id"], id.as_str());
+}
+
+#[tokio::test]
+async fn get_returns_the_customer_or_404() {
+    let app = app().await;
+    let id = create(&app, 1).await;
+
+    let (status, headers, body) = send(&app, Method::GET, &format!("/v1/customers/{}", id), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::ETAG], "\"1\"");
+    assert_eq!(body["customer_id"], id.as_str());
+    assert_eq!(body["receipt"]["service_center_code"], "EAC");
+
+    for uri in [format!("/v1/customers/{}", UNKNOWN_ID), "/v1/customers/not-a-uuid".to_string()] {
+        let (status, _, body) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{}", uri);
+        assert_eq!(body["code"], "not_found");
+    }
+
+    // The legacy route answers 200 with a message, as it always has
+    let (status, _, body) = send(&app, Method::GET, &format!("/get_customer_by_id/{}", UNKNOWN_ID), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["message"], "Data not found");
+    let (_, _, body) = send(&app, Method::GET, &format!("/get_customer_by_id/{}", id), None).await;
+    assert_eq!(body["customer_id"], id.as_str());
+}
+
+#[tokio::test]
+async fn update_replaces_the_record_and_bumps_the_version() {
+    let app = app().await;
+    let id = create(&app, 1).await;
+
+    let mut update = new_customer(1);
+    update["city"] = json!("Round Rock");
+    let (status, headers, body) = send(&app, Method::PUT, &format!("/update_customer_by_id/{}", id), Some(update.clone())).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(headers[header::ETAG], "\"2\"");
+    assert_eq!(body["version"], 2);
+
+    let (_, _, body) = send(&app, Method::GET, &format!("/v1/customers/{}", id), None).await;
+    assert_eq!(body["city"], "Round Rock");
+    assert_eq!(body["lca_salary"], "120000");
+    assert_eq!(body["version"], 2);
+
+    let (status, _, body) = send(&app, Method::PUT, &format!("/update_customer_by_id/{}", UNKNOWN_ID), Some(update)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["message"], "Customer not found");
+}
+
+#[tokio::test]
+async fn patch_changes_only_the_fields_sent() {
+    let app = app().await;
+    let id = create(&app, 1).await;
+    let uri = format!("/v1/customers/{}", id);
+
+    let (status, _, body) = send(&app, Method::PATCH, &uri, Some(json!({"city": "Plano", "lca_salary": 98765.5}))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["city"], "Plano");
+    assert_eq!(body["lca_salary"], "98765.5");
+    assert_eq!(body["first_name"], "Jane");
+
+    let stale = Request::builder().method(Method::PATCH).uri(&uri).header(header::IF_MATCH, "\"1\"");
+    let (status, headers, body) = send_with(&app, stale, Some(json!({"city": "Frisco"}))).await;
+    assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+    assert_eq!(body["current_version"], 2);
+    assert_eq!(headers[header::ETAG], "\"2\"");
+
+    let (status, _, _) = send(&app, Method::PATCH, &format!("/v1/customers/{}", UNKNOWN_ID), Some(json!({"city": "Plano"}))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn soft_delete_deactivates_once() {
+    let app = app().await;
+    let id = create(&app, 1).await;
+    let uri = format!("/soft_delete_customer_via_id/{}", id);
+
+    let (status, headers, body) = send(&app, Method::PATCH, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::ETAG], "\"2\"");
+    assert_eq!(body["updated_record"]["h1b_status"], "Inactive");
+
+    let (status, _, body) = send(&app, Method::PATCH, &uri, None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "status_unchanged");
+    assert_eq!(body["h1b_status"], "Inactive");
+
+    let (status, _, body) = send(&app, Method::POST, &format!("/v1/customers/{}:deactivate", id), None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "status_unchanged");
+
+    // The legacy lookup hides inactive customers; v1 does not
+    let (_, _, body) = send(&app, Method::GET, &format!("/get_customer_by_id/{}", id), None).await;
+    assert_eq!(body["message"], "Data not found");
+    let (_, _, body) = send(&app, Method::GET, &format!("/v1/customers/{}", id), None).await;
+    assert_eq!(body["h1b_status"], "Inactive");
+
+    let (status, _, body) = send(&app, Method::PATCH, &format!("/soft_delete_customer_via_id/{}", UNKNOWN_ID), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "not_found");
+}
+
+#[tokio::test]
+async fn activate_reactivates_once() {
+    let app = app().await;
+    let id = create(&app, 1).await;
+    let uri = format!("/h1b_customer/activate/{}", id);
+
+    let (status, _, body) = send(&app, Method::PATCH, &uri, None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "status_unchanged");
+    assert_eq!(body["h1b_status"], "Active");
+
+    let (status, _, body) = send(&app, Method::POST, &format!("/v1/customers/{}:deactivate", id), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["h1b_status"], "Inactive");
+
+    let (status, _, body) = send(&app, Method::PATCH, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["updated_record"]["h1b_status"], "Active");
+    assert_eq!(body["updated_record"]["version"], 3);
+
+    let (status, _, _) = send(&app, Method::POST, &format!("/v1/customers/{}:activate", UNKNOWN_ID), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _, _) = send(&app, Method::PATCH, &format!("/h1b_customer/activate/{}", UNKNOWN_ID), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn history_records_every_change() {
+    let app = app().await;
+    let id = create(&app, 1).await;
+    send(&app, Method::PATCH, &format!("/v1/customers/{}", id), Some(json!({"city": "Plano"}))).await;
+    send(&app, Method::POST, &format!("/v1/customers/{}:deactivate", id), None).await;
+
+    let (status, _, body) = send(&app, Method::GET, &format!("/v1/customers/{}/history", id), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let actions: Vec<&str> = body["versions"].as_array().unwrap().iter().map(|v| v["action"].as_str().unwrap()).collect();
+    assert_eq!(actions, ["create", "patch", "soft_delete"]);
+    assert_eq!(body["versions"][1]["changes"][0]["field"], "city");
+    assert_eq!(body["versions"][2]["actor"], "tester@example.com");
+}
+
+#[tokio::test]
+async fn transition_rejects_unknown_statuses() {
+    let app = app().await;
+    let id = create(&app, 1).await;
+    let uri = format!("/v1/customers/{}/transitions", id);
+
+    let (status, _, body) = send(&app, Method::POST, &uri, Some(json!({"to": "Pending", "reason": "filed"}))).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "invalid_enum_value");
+    assert_eq!(body["field"], "to");
+
+    let (status, _, body) = send(&app, Method::POST, &uri, Some(json!({"to": "Approved", "reason": "skip"}))).await;
+    assert_eq!(status, StatusCode::CONFLICT, "{}", body);
+
+    let (status, _, body) = send(&app, Method::POST, &uri, Some(json!({"to": "Filed", "reason": "sent to USCIS"}))).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["from_status"], "Draft");
+    assert_eq!(body["to_status"], "Filed");
+}
+
+#[tokio::test]
+async fn receipt_numbers_are_stored_in_capitals() {
+    let app = app().await;
+    let mut customer = new_customer(1);
+    customer["receipt_number"] = json!(" wac2412345678 ");
+    let (status, _, body) = send(&app, Method::POST, "/v1/customers", Some(customer)).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["receipt_number"], "WAC2412345678");
+    assert_eq!(body["receipt"]["fiscal_year"], 2024);
+
+    let id = body["customer_id"].as_str().unwrap();
+    let (status, _, body) = send(&app, Method::PATCH, &format!("/v1/customers/{}", id), Some(json!({"receipt_number": "lin2512345678"}))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["receipt_number"], "LIN2512345678");
+
+    let petition = json!({"receipt_number": " src2612345678 ", "h1b_start_date": "2024-10-01", "h1b_end_date": "2027-09-30"});
+    for uri in [format!("/v1/customers/{}/petition", id), format!("/customers/{}/petition", id)] {
+        let (status, _, body) = send(&app, Method::PUT, &uri, Some(petition.clone())).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["receipt_number"], "SRC2612345678", "{}", uri);
+    }
+    let (_, _, body) = send(&app, Method::GET, &format!("/v1/customers/{}", id), None).await;
+    assert_eq!(body["receipt_number"], "SRC2612345678");
+
+    // Stored in capitals, so the uniqueness check sees the same number typed differently
+    let mut duplicate = new_customer(2);
+    duplicate["receipt_number"] = json!("Src2612345678");
+    let (status, _, body) = send(&app, Method::POST, "/v1/customers", Some(duplicate)).await;
+    assert_eq!(status, StatusCode::CONFLICT, "{}", body);
+    assert_eq!(body["field"], "receipt_number");
+}
+
+#[tokio::test]
+async fn legacy_update_checks_the_whole_record() {
+    let app = app().await;
+    let id = create(&app, 1).await;
+
+    let mut update = new_customer(1);
+    update.as_object_mut().unwrap().remove("zip");
+    update["city"] = json!("");
+    let (status, _, body) = send(&app, Method::PUT, &format!("/update_customer_by_id/{}", id), Some(update)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let fields: Vec<&str> = body["errors"].as_array().unwrap().iter().map(|e| e["field"].as_str().unwrap()).collect();
+    assert_eq!(fields, ["city", "zip"]);
+
+    let (_, _, body) = send(&app, Method::GET, &format!("/v1/customers/{}", id), None).await;
+    assert_eq!(body["zip"], "73301");
+    assert_eq!(body["version"], 1);
+}
+
+#[tokio::test]
+async fn legacy_update_reads_the_salary_as_number_or_string() {
+    let app = app().await;
+    let id = create(&app, 1).await;
+    let uri = format!("/update_customer_by_id/{}", id);
+
+    for (salary, stored) in [(json!(98765.5), "98765.5"), (json!("101000.25"), "101000.25")] {
+        let mut update = new_customer(1);
+        update["lca_salary"] = salary;
+        let (status, _, body) = send(&app, Method::PUT, &uri, Some(update)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let (_, _, body) = send(&app, Method::GET, &format!("/v1/customers/{}", id), None).await;
+        assert_eq!(body["lca_salary"], stored);
+    }
+
+    let mut update = new_customer(1);
+    update["lca_salary"] = json!("lots");
+    let (status, _, body) = send(&app, Method::PUT, &uri, Some(update)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["field"], "lca_salary");
+
+    let mut update = new_customer(1);
+    update.as_object_mut().unwrap().remove("lca_salary");
+    let (status, _, body) = send(&app, Method::PUT, &uri, Some(update)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["errors"][0]["field"], "lca_salary");
+}
+
+mod cursors {
+    use super::super::{decode_cursor, encode_cursor, parse_sort, PageCursor};
+    use crate::models::Customer;
+    use crate::repository::{Position, SortKind, SortValue, SORT_FIELDS};
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use uuid::Uuid;
+
+    fn position(sort: &str) -> Position {
+        Position::of(&customer(), &parse_sort(sort).unwrap())
+    }
+
+    fn customer() -> Customer {
+        serde_json::from_value(serde_json::json!({
+            "customer_id": "00000000-0000-0000-0000-000000000007",
+            "email": "jane@example.com", "first_name": "Jane", "last_name": "O'Neil, Jr.",
+            "dob": "1990-05-01", "sex": "Female", "marital_status": "Single", "phone": "5551234567",
+            "emergency_contact_name": "Bob", "emergency_contact_phone": "5557654321",
+            "employment_start_date": "2024-02-01",
+            "street_name": "1 Main St", "city": "Austin", "state": "TX", "zip": "73301",
+            "client_name": "Acme", "client_street_name": "2 Market St", "client_city": "Austin",
+            "client_state": "TX", "client_zip": "78701",
+            "lca_title": "Software Developers", "lca_salary": "120000.50", "lca_code": "15-1252",
+            "receipt_number": "EAC2400000001", "h1b_start_date": "2024-10-01", "h1b_end_date": "2027-09-30",
+            "login_email": "jane@example.com", "h1b_status": "Active", "case_status": "Filed", "version": 1
+        }))
+        .unwrap()
+    }
+
+    fn reencode(cursor: &str, tamper: impl FnOnce(&mut PageCursor)) -> String {
+        let mut decoded: PageCursor = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(cursor).unwrap()).unwrap();
+        tamper(&mut decoded);
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(&decoded).unwrap())
+    }
+
+    #[test]
+    fn reads_every_sort_field_from_its_column() {
+        let customer = customer();
+        for field in SORT_FIELDS {
+            let value = field.value_of(&customer);
+            let kind = match &value {
+                SortValue::Text(_) => SortKind::Text,
+                SortValue::Date(_) => SortKind::Date,
+                SortValue::Decimal(_) => SortKind::Decimal,
+            };
+            assert_eq!(kind, field.kind, "{}", field.name);
+            assert_eq!(value.to_string(), serde_json::json!(customer)[field.name].as_str().unwrap(), "{}", field.name);
+        }
+    }
+
+    #[test]
+    fn round_trips_under_the_same_sort() {
+        for spec in ["", "-h1b_end_date,last_name", "lca_salary", "-last_name"] {
+            let sort = parse_sort(spec).unwrap();
+            let cursor = encode_cursor(&position(spec), &sort);
+            assert_eq!(decode_cursor(&cursor, &sort), Some(position(spec)), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn rejects_a_cursor_issued_for_another_sort() {
+        let issued = parse_sort("h1b_end_date").unwrap();
+        let cursor = encode_cursor(&position("h1b_end_date"), &issued);
+        for other in ["-h1b_end_date", "h1b_start_date", "h1b_end_date,last_name", ""] {
+            assert_eq!(decode_cursor(&cursor, &parse_sort(other).unwrap()), None, "{}", other);
+        }
+    }
+
+    #[test]
+    fn rejects_garbage_and_tampered_cursors() {
+        let sort = parse_sort("h1b_end_date,lca_salary").unwrap();
+        let cursor = encode_cursor(&position("h1b_end_date,lca_salary"), &sort);
+        for garbage in ["", "!!!", "bm90IGpzb24", &cursor[..cursor.len() / 2], &format!("{}=", cursor)] {
+            assert_eq!(decode_cursor(garbage, &sort), None, "{:?}", garbage);
+        }
+
+        let tampered = [
+            reencode(&cursor, |c| c.values[0] = "not a date".to_string()),
+            reencode(&cursor, |c| c.values[1] = "lots".to_string()),
+            reencode(&cursor, |c| c.values.truncate(1)),
+            reencode(&cursor, |c| c.sort = "lca_salary,h1b_end_date".to_string()),
+        ];
+        for cursor in tampered {
+            assert_eq!(decode_cursor(&cursor, &sort), None, "{}", cursor);
+        }
+
+        // Only the id changed: still a position, just another one
+        let moved = reencode(&cursor, |c| c.after = Uuid::nil());
+        assert_eq!(decode_cursor(&moved, &sort).unwrap().customer_id, Uuid::nil());
+    }
+}
+
+#[tokio::test]
+async fn list_rejects_bad_cursors_with_400() {
+    let app = app().await;
+    for n in 1..=3 {
+        create(&app, n).await;
+    }
+    let (status, _, body) = send(&app, Method::GET, "/v1/customers?limit=2&sort=last_name", None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let cursor = body["next_cursor"].as_str().unwrap().to_string();
+
+    let (status, _, body) = send(&app, Method::GET, &format!("/v1/customers?limit=2&sort=last_name&cursor={}", cursor), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    for uri in [
+        format!("/v1/customers?limit=2&sort=-last_name&cursor={}", cursor),
+        "/v1/customers?limit=2&cursor=garbage".to_string(),
+    ] {
+        let (status, _, body) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+        assert_eq!(body["field"], "cursor");
+    }
+}
+
+mod query_params {
+    use super::super::{parse_fields, parse_sort, sort_spec};
+
+    #[test]
+    fn sort_reads_direction_from_the_dash() {
+        let sort = parse_sort("-h1b_end_date, last_name").unwrap();
+        assert_eq!(sort.iter().map(|k| (k.field.name, k.descending)).collect::<Vec<_>>(), [("h1b_end_date", true), ("last_name", false)]);
+        assert_eq!(sort_spec(&sort), "-h1b_end_date,last_name");
+    }
+
+    #[test]
+    fn sort_skips_empty_segments() {
+        assert!(parse_sort("").unwrap().is_empty());
+        assert_eq!(sort_spec(&parse_sort(",last_name,, ,-lca_salary,").unwrap()), "last_name,-lca_salary");
+    }
+
+    #[test]
+    fn sort_rejects_unknown_fields_and_duplicates() {
+        for spec in ["dob", "phone", "-", "--last_name", "last name"] {
+            assert!(parse_sort(spec).unwrap_err().starts_with("cannot sort by"), "{}", spec);
+        }
+        assert_eq!(parse_sort("last_name,-last_name").unwrap_err(), "last_name appears more than once in sort");
+    }
+
+    #[test]
+    fn fields_always_include_customer_id() {
+        assert_eq!(parse_fields("").unwrap(), ["customer_id"]);
+        assert_eq!(parse_fields("first_name,h1b_end_date").unwrap(), ["customer_id", "first_name", "h1b_end_date"]);
+        assert_eq!(parse_fields("customer_id,receipt").unwrap(), ["customer_id", "receipt"]);
+    }
+
+    #[test]
+    fn fields_skip_empty_segments_and_repeats() {
+        assert_eq!(parse_fields(" first_name,,first_name, ").unwrap(), ["customer_id", "first_name"]);
+    }
+
+    #[test]
+    fn fields_reject_unknown_names() {
+        assert_eq!(parse_fields("first_name,ssn").unwrap_err(), "unknown field ssn");
+        assert_eq!(parse_fields("-first_name").unwrap_err(), "unknown field -first_name");
+    }
+}
+
+#[tokio::test]
+async fn list_projects_and_sorts() {
+    let app = app().await;
+    for n in 1..=2 {
+        create(&app, n).await;
+    }
+    let (status, _, body) = send(&app, Method::GET, "/v1/customers?fields=email&sort=-email", None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let rows = body["items"].as_array().unwrap();
+    assert_eq!(rows[0]["email"], "jane2@example.com");
+    assert_eq!(rows[0].as_object().unwrap().keys().collect::<Vec<_>>(), ["customer_id", "email"]);
+
+    for uri in ["/v1/customers?sort=dob", "/v1/customers?fields=ssn"] {
+        let (status, _, _) = send(&app, Method::GET, uri, None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn search_finds_substrings_below_the_similarity_threshold() {
+    let app = app().await;
+    let id = create(&app, 1).await;
+
+    let (status, _, body) = send(&app, Method::GET, "/v1/customers/search?q=c24", None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let hits = body["results"].as_array().unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0]["customer"]["customer_id"], id.as_str());
+    assert_eq!(hits[0]["matched_field"], "receipt_number");
+    assert_eq!(hits[0]["score"], 0.25);
+
+    let (_, _, body) = send(&app, Method::GET, "/v1/customers/search?q=zq", None).await;
+    assert!(body["results"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn v1_put_replaces_the_whole_record() {
+    let app = app().await;
+    let id = create(&app, 1).await;
+    let uri = format!("/v1/customers/{}", id);
+
+    let mut replacement = new_customer(1);
+    replacement["city"] = json!("Round Rock");
+    let (status, headers, body) = send(&app, Method::PUT, &uri, Some(replacement.clone())).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(headers[header::ETAG], "\"2\"");
+    assert_eq!(body["city"], "Round Rock");
+    assert_eq!(body["version"], 2);
+
+    // Nothing is blanked: a field left out is an error
+    let mut partial = replacement.clone();
+    partial.as_object_mut().unwrap().remove("zip");
+    let (status, _, body) = send(&app, Method::PUT, &uri, Some(partial)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", body);
+
+    let mut with_status = replacement.clone();
+    with_status["h1b_status"] = json!("Inactive");
+    let (status, _, _) = send(&app, Method::PUT, &uri, Some(with_status)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let stale = Request::builder().method(Method::PUT).uri(&uri).header(header::IF_MATCH, "\"1\"");
+    let (status, _, _) = send_with(&app, stale, Some(replacement.clone())).await;
+    assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+
+    let (status, _, body) = send(&app, Method::PUT, &format!("/v1/customers/{}", UNKNOWN_ID), Some(replacement)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "not_found");
+}
+
+#[tokio::test]
+async fn legacy_put_links_to_a_successor_taking_the_same_method() {
+    let app = app().await;
+    let id = create(&app, 1).await;
+
+    let (status, headers, _) = send(&app, Method::PUT, &format!("/update_customer_by_id/{}", id), Some(new_customer(1))).await;
+    assert_eq!(status, StatusCode::OK);
+    let link = headers["link"].to_str().unwrap();
+    assert_eq!(link, format!("</v1/customers/{}>; rel=\"successor-version\"", id));
+
+    let successor = link.trim_start_matches('<').split('>').next().unwrap();
+    let (status, _, body) = send(&app, Method::PUT, successor, Some(new_customer(1))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["version"], 3);
+}
@@ -8,22 +8,48 @@ use axum::http::HeaderValue;
 
 mod models;
 mod handlers;
+mod metrics;
+mod receipt;
 mod middleware;
+mod openapi;
+mod case_status;
 mod config;
+mod error;
+mod repository;
+mod search;
+mod soc;
+mod state;
+mod validation;
+mod wages;
 
 use handlers::*;
+use models::{ClientPlacement, LcaDetails, PersonDetails, PetitionDetails, ResidentialAddress};
 use middleware::auth::auth_middleware;
+use middleware::deprecation::deprecated_route;
+use middleware::request_logging::{log_requests, REQUEST_ID_HEADER};
+use openapi::{docs_redirect, openapi_json, swagger_ui};
+use config::AppConfig;
+use state::AppState;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenv::dotenv().ok();
-    run_local_server().await?;
+    // RUST_LOG picks what is logged, e.g. RUST_LOG=visa_api=debug for handler calls
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
+        .init();
+    if let Err(e) = run_local_server().await {
+        tracing::error!("Server failed to start: {}", e);
+        std::process::exit(1);
+    }
     Ok(())
 }
 
 async fn run_local_server() -> Result<(), Box<dyn std::error::Error>> {
-    // Database will be initialized on first use
-    let _pool = std::env::var("DB_HOST"); // Just check env vars exist
+    let config = AppConfig::from_env()?;
+    let state = AppState::build(config).await?;
 
     let cors = CorsLayer::new()
         .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE, Method::PATCH, Method::OPTIONS])
@@ -35,29 +61,77 @@ async fn run_local_server() -> Result<(), Box<dyn std::error::Error>> {
         .allow_headers(vec![
             axum::http::header::AUTHORIZATION,
             axum::http::header::CONTENT_TYPE,
+            axum::http::header::IF_MATCH,
+            axum::http::HeaderName::from_static("idempotency-key"),
+            axum::http::HeaderName::from_static(REQUEST_ID_HEADER),
+        ])
+        .expose_headers(vec![
+            axum::http::header::ETAG,
+            axum::http::header::LOCATION,
+            axum::http::HeaderName::from_static("idempotent-replayed"),
+            axum::http::HeaderName::from_static(REQUEST_ID_HEADER),
         ]);
-    let protected_routes = Router::new()
-        .route("/h1b_customer/create", post(create_visa_details))
-        .route("/customers", get(get_all_customers_with_status))
-        .route("/get_customer_by_id/:id", get(get_customer_by_id))
-        .route("/get_customer_by_email/:email", get(get_customer_by_email))
-        .route("/soft_delete_customer_via_id/:id", patch(soft_delete_customer_by_id))
-        .route("/update_customer_by_id/:id", put(update_customer_by_id))
-        .route("/h1b_customer/by_login_email/:login_email", get(get_customer_by_login_email))
-        .route("/h1b_customer/all", get(get_all_customers_no_filter))
-        .route("/h1b_customer/activate/:customer_id", patch(activate_customer_by_id))
-        .layer(axum::middleware::from_fn(auth_middleware));
+    let protected_routes = customer_routes(&state).layer(axum::middleware::from_fn(auth_middleware));
 
     let app = Router::new()
         .route("/health", get(health_check))
         .route("/hello", get(test_connection))
+        .route("/metrics", get(get_metrics))
+        .route("/openapi.json", get(openapi_json))
+        .route("/docs", get(docs_redirect))
+        .route("/docs/", get(swagger_ui))
+        .route("/docs/*path", get(swagger_ui))
         .merge(protected_routes)
+        .with_state(state.clone())
+        .layer(axum::middleware::from_fn(log_requests))
         .layer(cors);
 
-    let port = std::env::var("PORT").unwrap_or_else(|_| "3000".to_string());
-    let bind_addr = format!("0.0.0.0:{}", port);
-    println!("Starting server on {}", bind_addr);
+    let bind_addr = format!("0.0.0.0:{}", state.config.port);
+    tracing::info!("Starting server on {}", bind_addr);
     let listener = tokio::net::TcpListener::bind(&bind_addr).await?;
     axum::serve(listener, app).await?;
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Every customer route, v1 and legacy, without authentication; the caller
+/// adds it.
+fn customer_routes(state: &AppState) -> Router<AppState> {
+    let v1_routes = Router::new()
+        .route("/v1/customers", get(list_all_customers).post(create_customer))
+        .route("/v1/customers/search", get(search_customers))
+        .route("/v1/customers/:id", get(get_customer).put(replace_customer).patch(patch_customer).post(customer_action))
+        .route("/v1/customers/:id/history", get(get_customer_history))
+        .route("/v1/customers/:id/transitions", get(get_case_transitions).post(create_case_transition))
+        .route("/v1/customers/:id/wage-compliance", get(get_wage_compliance))
+        .route("/v1/reports/below-level-1", get(below_level_1_report))
+        .route("/v1/customers/:id/person", get(get_customer_section::<PersonDetails>).put(update_customer_section::<PersonDetails>))
+        .route("/v1/customers/:id/address", get(get_customer_section::<ResidentialAddress>).put(update_customer_section::<ResidentialAddress>))
+        .route("/v1/customers/:id/placement", get(get_customer_section::<ClientPlacement>).put(update_customer_section::<ClientPlacement>))
+        .route("/v1/customers/:id/lca", get(get_customer_section::<LcaDetails>).put(update_customer_section::<LcaDetails>))
+        .route("/v1/customers/:id/petition", get(get_customer_section::<PetitionDetails>).put(update_customer_section::<PetitionDetails>))
+        .route("/v1/occupations", get(lookup_occupations))
+        .route("/v1/occupations/:code", get(get_occupation));
+
+    // Unversioned aliases, kept until the sunset date in middleware::deprecation
+    let legacy_routes = Router::new()
+        .route("/h1b_customer/create", post(create_visa_details))
+        .route("/customers", get(get_all_customers_with_status))
+        .route("/get_customer_by_id/:id", get(get_customer_by_id))
+        .route("/get_customer_by_email/:email", get(get_customer_by_email))
+        .route("/soft_delete_customer_via_id/:id", patch(soft_delete_customer_by_id))
+        .route("/update_customer_by_id/:id", put(update_customer_by_id))
+        .route("/h1b_customer/by_login_email/:login_email", get(get_customer_by_login_email))
+        .route("/h1b_customer/all", get(get_all_customers_no_filter))
+        .route("/h1b_customer/activate/:customer_id", patch(activate_customer_by_id))
+        .route("/customers/search", get(search_customers))
+        .route("/customers/:id", patch(patch_customer))
+        .route("/customers/:id/history", get(get_customer_history))
+        .route("/customers/:id/person", get(get_customer_section::<PersonDetails>).put(update_customer_section::<PersonDetails>))
+        .route("/customers/:id/address", get(get_customer_section::<ResidentialAddress>).put(update_customer_section::<ResidentialAddress>))
+        .route("/customers/:id/placement", get(get_customer_section::<ClientPlacement>).put(update_customer_section::<ClientPlacement>))
+        .route("/customers/:id/lca", get(get_customer_section::<LcaDetails>).put(update_customer_section::<LcaDetails>))
+        .route("/customers/:id/petition", get(get_customer_section::<PetitionDetails>).put(update_customer_section::<PetitionDetails>))
+        .route_layer(axum::middleware::from_fn_with_state(state.metrics.clone(), deprecated_route));
+
+    v1_routes.merge(legacy_routes)
+}
@@ -0,0 +1,33 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// Request counters served at `GET /metrics` in the Prometheus text format.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// Calls to deprecated routes, by method and route template.
+    legacy_requests: Mutex<BTreeMap<(String, String), u64>>,
+}
+
+pub type SharedMetrics = Arc<Metrics>;
+
+impl Metrics {
+    pub fn record_legacy_request(&self, method: &str, route: &str) {
+        let mut counts = self.legacy_requests.lock().unwrap_or_else(|e| e.into_inner());
+        *counts.entry((method.to_string(), route.to_string())).or_default() += 1;
+    }
+
+    pub fn render(&self) -> String {
+        let counts = self.legacy_requests.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out = String::from(
+            "# HELP visa_api_legacy_requests_total Requests to deprecated unversioned routes.\n\
+             # TYPE visa_api_legacy_requests_total counter\n",
+        );
+        for ((method, route), count) in counts.iter() {
+            out.push_str(&format!(
+                "visa_api_legacy_requests_total{{method=\"{}\",route=\"{}\"}} {}\n",
+                method, route, count
+            ));
+        }
+        out
+    }
+}
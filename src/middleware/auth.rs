@@ -2,68 +2,71 @@ use axum::{
     middleware::Next,
     extract::Request,
     response::IntoResponse,
-    http::StatusCode,
-    Json,
 };
-use serde_json::json;
+use serde::Deserialize;
 
-pub async fn auth_middleware(request: Request, next: Next) -> impl IntoResponse {
+use crate::error::ApiError;
+
+/// The Supabase user behind a verified token, added to the request
+/// extensions for handlers that need to know who is acting.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AuthUser {
+    pub id: String,
+    pub email: Option<String>,
+}
+
+impl AuthUser {
+    /// Name recorded against changes: the email, or the user id without one.
+    pub fn actor(&self) -> &str {
+        self.email.as_deref().unwrap_or(&self.id)
+    }
+}
+
+pub async fn auth_middleware(mut request: Request, next: Next) -> impl IntoResponse {
     let headers = request.headers();
     
     let token = match headers.get("authorization") {
         Some(auth_header) => {
             match auth_header.to_str() {
                 Ok(auth_str) => {
-                    if auth_str.starts_with("Bearer ") {
-                        &auth_str[7..]
+                    if let Some(token) = auth_str.strip_prefix("Bearer ") {
+                        token.to_string()
                     } else {
-                        return (StatusCode::UNAUTHORIZED, Json(json!({
-                            "error": "wrong token",
-                            "message": "you are not authorized"
-                        }))).into_response();
+                        return ApiError::Unauthorized.into_response();
                     }
                 }
-                Err(_) => return (StatusCode::UNAUTHORIZED, Json(json!({
-                    "error": "wrong token",
-                    "message": "you are not authorized"
-                }))).into_response(),
+                Err(_) => return ApiError::Unauthorized.into_response(),
             }
         }
-        None => return (StatusCode::UNAUTHORIZED, Json(json!({
-            "error": "wrong token",
-            "message": "you are not authorized"
-        }))).into_response(),
+        None => return ApiError::Unauthorized.into_response(),
     };
     
-    // Temporarily bypass Supabase verification for testing
-    println!("Token received: {}", &token[..20]); // Log first 20 chars
-    
-    match verify_supabase_token(token).await {
-        true => next.run(request).await,
-        false => {
-            println!("Token verification failed");
-            (StatusCode::UNAUTHORIZED, Json(json!({
-                "error": "wrong token",
-                "message": "you are not authorized"
-            }))).into_response()
+    match verify_supabase_token(&token).await {
+        Some(user) => {
+            request.extensions_mut().insert(user);
+            next.run(request).await
+        }
+        None => {
+            tracing::debug!("Token verification failed");
+            ApiError::Unauthorized.into_response()
         }
     }
 }
 
-async fn verify_supabase_token(token: &str) -> bool {
+async fn verify_supabase_token(token: &str) -> Option<AuthUser> {
     let supabase_url = match std::env::var("SUPABASE_URL") {
         Ok(url) => url,
         Err(_) => {
-            eprintln!("SUPABASE_URL not found in environment");
-            return false;
+            tracing::error!("SUPABASE_URL not found in environment");
+            return None;
         }
     };
     
     let supabase_api_key = match std::env::var("SUPABASE_API_KEY") {
         Ok(key) => key,
         Err(_) => {
-            eprintln!("SUPABASE_API_KEY not found in environment");
-            return false;
+            tracing::error!("SUPABASE_API_KEY not found in environment");
+            return None;
         }
     };
     
@@ -83,12 +86,21 @@ async fn verify_supabase_token(token: &str) -> bool {
     {
         Ok(response) => {
             let status = response.status();
-            println!("Supabase response status: {}", status);
-            status.is_success()
+            tracing::debug!("Supabase response status: {}", status);
+            if !status.is_success() {
+                return None;
+            }
+            match response.json::<AuthUser>().await {
+                Ok(user) => Some(user),
+                Err(e) => {
+                    tracing::warn!("Unexpected Supabase user response: {}", e);
+                    None
+                }
+            }
         },
         Err(e) => {
-            eprintln!("Error verifying token with Supabase: {}", e);
-            false
+            tracing::error!("Error verifying token with Supabase: {}", e);
+            None
         }
     }
 }
\ No newline at end of file
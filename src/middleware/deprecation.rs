@@ -0,0 +1,75 @@
+use axum::{
+    extract::{MatchedPath, RawPathParams, Request, State},
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+
+use crate::metrics::SharedMetrics;
+
+/// When the unversioned routes were deprecated (RFC 9745 `@` Unix time),
+/// 2026-10-18T00:00:00Z.
+const DEPRECATED_AT: &str = "@1792281600";
+/// When they stop being served.
+const SUNSET: &str = "Sun, 18 Apr 2027 00:00:00 GMT";
+
+/// Each deprecated route template and its `/v1` successor, in which
+/// `{name}` stands for the legacy route's `:name` parameter.
+pub const LEGACY_ROUTES: [(&str, &str); 17] = [
+    ("/h1b_customer/create", "/v1/customers"),
+    ("/customers", "/v1/customers?h1b_status=Active"),
+    ("/h1b_customer/all", "/v1/customers"),
+    ("/get_customer_by_id/:id", "/v1/customers/{id}"),
+    ("/get_customer_by_email/:email", "/v1/customers?email={email}"),
+    ("/h1b_customer/by_login_email/:login_email", "/v1/customers?login_email={login_email}&h1b_status=Active"),
+    ("/update_customer_by_id/:id", "/v1/customers/{id}"),
+    ("/soft_delete_customer_via_id/:id", "/v1/customers/{id}:deactivate"),
+    ("/h1b_customer/activate/:customer_id", "/v1/customers/{customer_id}:activate"),
+    ("/customers/search", "/v1/customers/search"),
+    ("/customers/:id", "/v1/customers/{id}"),
+    ("/customers/:id/history", "/v1/customers/{id}/history"),
+    ("/customers/:id/person", "/v1/customers/{id}/person"),
+    ("/customers/:id/address", "/v1/customers/{id}/address"),
+    ("/customers/:id/placement", "/v1/customers/{id}/placement"),
+    ("/customers/:id/lca", "/v1/customers/{id}/lca"),
+    ("/customers/:id/petition", "/v1/customers/{id}/petition"),
+];
+
+/// The `/v1` URL replacing a call to `route` with these path parameters.
+fn successor(route: &str, params: Option<&RawPathParams>) -> Option<String> {
+    let (_, template) = LEGACY_ROUTES.iter().find(|(legacy, _)| *legacy == route)?;
+    let mut url = template.to_string();
+    for (name, value) in params.into_iter().flat_map(|p| p.iter()) {
+        url = url.replace(&format!("{{{}}}", name), value);
+    }
+    Some(url)
+}
+
+/// Counts calls to a legacy route and marks the response deprecated, with a
+/// `Link` to the `/v1` route replacing it. Must be added with `route_layer`
+/// so the matched route is known.
+pub async fn deprecated_route(
+    State(metrics): State<SharedMetrics>,
+    params: Option<RawPathParams>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().clone();
+    let route = request.extensions().get::<MatchedPath>().map(|p| p.as_str().to_string());
+
+    let mut response = next.run(request).await;
+    let Some(route) = route else {
+        return response;
+    };
+    metrics.record_legacy_request(method.as_str(), &route);
+
+    let headers = response.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static(DEPRECATED_AT));
+    headers.insert("sunset", HeaderValue::from_static(SUNSET));
+    let link = successor(&route, params.as_ref())
+        .and_then(|url| HeaderValue::from_str(&format!("<{}>; rel=\"successor-version\"", url)).ok());
+    if let Some(link) = link {
+        headers.insert("link", link);
+    }
+    response
+}
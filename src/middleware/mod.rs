@@ -1,2 +1,3 @@
 pub mod request_logging;
-pub mod auth;
\ No newline at end of file
+pub mod auth;
+pub mod deprecation;
\ No newline at end of file
@@ -1,21 +1,52 @@
 use axum::{
     middleware::Next,
     extract::Request,
+    http::HeaderValue,
     response::Response,
 };
 use std::time::Instant;
+use tracing::Instrument;
+use uuid::Uuid;
 
+/// Header carrying the request's correlation id, both ways.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Correlation id of the request being handled, when called while
+/// `log_requests` is running it.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Logs each request and response, and tags both with a correlation id: the
+/// caller's `X-Request-Id` if it sent a usable one, otherwise a new UUID.
 pub async fn log_requests(request: Request, next: Next) -> Response {
     let method = request.method().clone();
     let uri = request.uri().clone();
     let start = Instant::now();
-    
-    println!("🚀 Incoming {} request to: {}", method, uri);
-    
-    let response = next.run(request).await;
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| !v.is_empty() && v.len() <= 128)
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    // Everything logged while handling the request carries its id
+    let span = tracing::info_span!("request", request_id = %request_id);
+    span.in_scope(|| tracing::info!("Incoming {} request to: {}", method, uri));
+
+    let mut response = REQUEST_ID.scope(request_id.clone(), next.run(request)).instrument(span.clone()).await;
     let duration = start.elapsed();
-    
-    println!("✅ Response {} in {:?} for {} {}", response.status(), duration, method, uri);
-    
+
+    span.in_scope(|| tracing::info!("Response {} in {:?} for {} {}", response.status(), duration, method, uri));
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
     response
-}
\ No newline at end of file
+}
@@ -1,11 +1,15 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
-use chrono::NaiveDate;
+use chrono::{DateTime, NaiveDate, Utc};
 use utoipa::ToSchema;
 use rust_decimal::Decimal;
 use uuid::Uuid;
 
-#[derive(Debug, Deserialize, ToSchema)]
+use crate::case_status::CaseStatus;
+use crate::receipt::{ReceiptDetails, ReceiptNumber};
+use crate::validation::FieldError;
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
 pub struct CreateCompleteCustomerRequest {
     pub email: String,
     pub login_email: String,
@@ -36,8 +40,66 @@ pub struct CreateCompleteCustomerRequest {
     pub h1b_status: Option<String>,
 }
 
-#[derive(Debug, FromRow, Serialize, ToSchema)]
-pub struct CreateCustomer {
+pub const DEFAULT_H1B_STATUS: &str = "Active";
+
+/// Field names of `CustomerResponse` as serialized.
+pub const CUSTOMER_FIELDS: [&str; 31] = [
+    "customer_id", "email", "first_name", "last_name", "dob", "sex", "marital_status", "phone",
+    "emergency_contact_name", "emergency_contact_phone", "employment_start_date",
+    "street_name", "city", "state", "zip",
+    "client_name", "client_street_name", "client_city", "client_state", "client_zip",
+    "lca_title", "lca_salary", "lca_code", "receipt_number", "h1b_start_date", "h1b_end_date",
+    "login_email", "h1b_status", "case_status", "version", "receipt",
+];
+
+/// A row of the `global_visa_mgmt.h1bcustomer` view, and the record kept in
+/// each history snapshot.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct Customer {
+    pub customer_id: Uuid,
+    pub email: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub dob: NaiveDate,
+    pub sex: String,
+    pub marital_status: String,
+    pub phone: String,
+    pub emergency_contact_name: String,
+    pub emergency_contact_phone: String,
+    pub employment_start_date: NaiveDate,
+    pub street_name: String,
+    pub city: String,
+    pub state: String,
+    pub zip: String,
+    pub client_name: String,
+    pub client_street_name: String,
+    pub client_city: String,
+    pub client_state: String,
+    pub client_zip: String,
+    pub lca_title: String,
+    pub lca_salary: Decimal,
+    pub lca_code: String,
+    pub receipt_number: String,
+    pub h1b_start_date: NaiveDate,
+    pub h1b_end_date: NaiveDate,
+    pub login_email: String,
+    pub h1b_status: String,
+    /// Revisions written before `0008_case_status.sql` have none; those
+    /// customers had all been filed.
+    #[sqlx(try_from = "String")]
+    #[serde(default = "filed")]
+    pub case_status: CaseStatus,
+    /// Incremented on every change; sent as the record's ETag.
+    pub version: i64,
+}
+
+fn filed() -> CaseStatus {
+    CaseStatus::Filed
+}
+
+/// A customer record as every endpoint returns it, built from `Customer`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CustomerResponse {
     pub customer_id: Uuid,
     pub email: String,
     pub first_name: String,
@@ -64,11 +126,162 @@ pub struct CreateCustomer {
     pub receipt_number: String,
     pub h1b_start_date: NaiveDate,
     pub h1b_end_date: NaiveDate,
+    pub login_email: String,
     pub h1b_status: String,
+    /// Where the petition stands; changed only through
+    /// `POST /v1/customers/{id}/transitions`.
+    pub case_status: CaseStatus,
+    /// Incremented on every change; sent as the record's ETag.
+    pub version: i64,
+    /// Service center and fiscal year read from `receipt_number`; `null`
+    /// when it is not a valid receipt number.
+    pub receipt: Option<ReceiptDetails>,
+    /// Values just written that were accepted but look wrong, such as an
+    /// `lca_title` that does not fit `lca_code`. Only on create and update
+    /// responses, and left out when there are none.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<FieldError>,
+}
+
+impl CustomerResponse {
+    pub fn with_warnings(self, warnings: Vec<FieldError>) -> Self {
+        Self { warnings, ..self }
+    }
 }
 
-#[derive(Debug, Deserialize, ToSchema)]
+impl From<Customer> for CustomerResponse {
+    fn from(c: Customer) -> Self {
+        let receipt = c.receipt_number.parse::<ReceiptNumber>().ok().map(|r| r.details());
+        Self {
+            customer_id: c.customer_id,
+            email: c.email,
+            first_name: c.first_name,
+            last_name: c.last_name,
+            dob: c.dob,
+            sex: c.sex,
+            marital_status: c.marital_status,
+            phone: c.phone,
+            emergency_contact_name: c.emergency_contact_name,
+            emergency_contact_phone: c.emergency_contact_phone,
+            employment_start_date: c.employment_start_date,
+            street_name: c.street_name,
+            city: c.city,
+            state: c.state,
+            zip: c.zip,
+            client_name: c.client_name,
+            client_street_name: c.client_street_name,
+            client_city: c.client_city,
+            client_state: c.client_state,
+            client_zip: c.client_zip,
+            lca_title: c.lca_title,
+            lca_salary: c.lca_salary,
+            lca_code: c.lca_code,
+            receipt_number: c.receipt_number,
+            h1b_start_date: c.h1b_start_date,
+            h1b_end_date: c.h1b_end_date,
+            login_email: c.login_email,
+            h1b_status: c.h1b_status,
+            case_status: c.case_status,
+            version: c.version,
+            receipt,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+/// A search result: the customer and the field that matched best.
+#[derive(Debug, Clone, FromRow)]
+pub struct SearchHit {
+    #[sqlx(flatten)]
+    pub customer: Customer,
+    /// One of `search::SEARCH_FIELDS`.
+    pub matched_field: String,
+    pub matched_value: String,
+    /// Trigram word similarity between the query and `matched_value`, 0 to 1.
+    pub score: f32,
+}
+
+/// A customer as it was after one change, kept for history and `as_of` reads.
+#[derive(Debug, Clone, FromRow)]
+pub struct CustomerRevision {
+    pub version: i64,
+    pub changed_at: DateTime<Utc>,
+    pub actor: Option<String>,
+    /// Operation that produced this version, e.g. `update` or `activate`.
+    pub action: String,
+    pub snapshot: sqlx::types::Json<Customer>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FieldChange {
+    pub field: String,
+    #[schema(value_type = Option<Object>)]
+    pub old: Option<serde_json::Value>,
+    #[schema(value_type = Object)]
+    pub new: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CustomerHistoryEntry {
+    pub version: i64,
+    pub changed_at: DateTime<Utc>,
+    pub actor: Option<String>,
+    pub action: String,
+    pub changes: Vec<FieldChange>,
+}
+
+impl CustomerRevision {
+    /// Fields that differ from `previous`; every field for the first revision.
+    /// `version` itself is left out since it changes every time.
+    pub fn changes_since(&self, previous: Option<&CustomerRevision>) -> Vec<FieldChange> {
+        let serde_json::Value::Object(new) = serde_json::json!(self.snapshot.0) else {
+            return Vec::new();
+        };
+        let old = previous.map(|p| serde_json::json!(p.snapshot.0));
+        new.into_iter()
+            .filter(|(field, _)| field != "version")
+            .filter_map(|(field, new)| {
+                let old = old.as_ref().and_then(|o| o.get(&field)).cloned();
+                (old.as_ref() != Some(&new)).then_some(FieldChange { field, old, new })
+            })
+            .collect()
+    }
+}
+
+/// Body of `POST /v1/customers/{id}/transitions`.
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CaseTransitionRequest {
+    /// The case status to move to.
+    #[schema(example = "RFE Issued")]
+    pub to: String,
+    /// Why, e.g. what USCIS sent; kept with the transition.
+    #[schema(example = "RFE received 2024-05-02 asking for the client itinerary")]
+    pub reason: String,
+}
+
+/// One logged change of a customer's case status.
+#[derive(Debug, Clone, FromRow, Serialize, ToSchema)]
+pub struct CaseTransition {
+    /// Version of the customer the transition produced.
+    pub version: i64,
+    #[sqlx(try_from = "String")]
+    pub from_status: CaseStatus,
+    #[sqlx(try_from = "String")]
+    pub to_status: CaseStatus,
+    pub reason: String,
+    pub actor: Option<String>,
+    pub transitioned_at: DateTime<Utc>,
+}
+
+/// Body of `PATCH /customers/{id}`, an RFC 7396 merge patch: only the
+/// fields present are changed. Status changes go through the soft delete and
+/// activate endpoints instead, and case status changes through transitions.
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
 pub struct UpdateVisaDetailsRequest {
+    pub email: Option<String>,
+    pub login_email: Option<String>,
     pub first_name: Option<String>,
     pub last_name: Option<String>,
     pub dob: Option<NaiveDate>,
@@ -93,10 +306,401 @@ pub struct UpdateVisaDetailsRequest {
     pub receipt_number: Option<String>,
     pub h1b_start_date: Option<NaiveDate>,
     pub h1b_end_date: Option<NaiveDate>,
-    pub h1b_status: Option<String>,
 }
 
-#[derive(Debug, Deserialize, ToSchema)]
-pub struct SoftDeleteRequest {
+impl UpdateVisaDetailsRequest {
+    /// Reads a merge patch, returning the offending field and why on error.
+    /// Every customer field is required, so removing one with `null` is
+    /// rejected rather than blanking it.
+    pub fn from_merge_patch(patch: serde_json::Value) -> Result<Self, (Option<String>, String)> {
+        let serde_json::Value::Object(members) = &patch else {
+            return Err((None, "patch must be a JSON object".to_string()));
+        };
+        for (field, value) in members {
+            if value.is_null() {
+                return Err((Some(field.clone()), format!("{} cannot be removed", field)));
+            }
+            // Checked one member at a time so the error names the field.
+            let single = serde_json::json!({ field.as_str(): value });
+            serde_json::from_value::<Self>(single).map_err(|e| (Some(field.clone()), format!("{}: {}", field, e)))?;
+        }
+        serde_json::from_value(patch).map_err(|e| (None, e.to_string()))
+    }
+
+    /// Overwrites the fields present in the patch.
+    pub fn apply(self, update: &mut CustomerUpdate) {
+        fn set<T>(target: &mut T, value: Option<T>) {
+            if let Some(value) = value {
+                *target = value;
+            }
+        }
+        set(&mut update.email, self.email);
+        set(&mut update.login_email, self.login_email);
+        set(&mut update.first_name, self.first_name);
+        set(&mut update.last_name, self.last_name);
+        set(&mut update.dob, self.dob);
+        set(&mut update.sex, self.sex);
+        set(&mut update.marital_status, self.marital_status);
+        set(&mut update.phone, self.phone);
+        set(&mut update.emergency_contact_name, self.emergency_contact_name);
+        set(&mut update.emergency_contact_phone, self.emergency_contact_phone);
+        set(&mut update.employment_start_date, self.employment_start_date);
+        set(&mut update.street_name, self.street_name);
+        set(&mut update.city, self.city);
+        set(&mut update.state, self.state);
+        set(&mut update.zip, self.zip);
+        set(&mut update.client_name, self.client_name);
+        set(&mut update.client_street_name, self.client_street_name);
+        set(&mut update.client_city, self.client_city);
+        set(&mut update.client_state, self.client_state);
+        set(&mut update.client_zip, self.client_zip);
+        set(&mut update.lca_title, self.lca_title);
+        set(&mut update.lca_salary, self.lca_salary);
+        set(&mut update.lca_code, self.lca_code);
+        set(&mut update.receipt_number, self.receipt_number);
+        set(&mut update.h1b_start_date, self.h1b_start_date);
+        set(&mut update.h1b_end_date, self.h1b_end_date);
+    }
+}
+
+/// Every column written by a full customer update, and the body of
+/// `PUT /v1/customers/{id}`. `h1b_status` is only changed through the
+/// activate / soft delete endpoints.
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CustomerUpdate {
+    pub email: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub dob: NaiveDate,
+    pub sex: String,
+    pub marital_status: String,
+    pub phone: String,
+    pub emergency_contact_name: String,
+    pub emergency_contact_phone: String,
+    pub employment_start_date: NaiveDate,
+    pub street_name: String,
+    pub city: String,
+    pub state: String,
+    pub zip: String,
+    pub client_name: String,
+    pub client_street_name: String,
+    pub client_city: String,
+    pub client_state: String,
+    pub client_zip: String,
+    pub lca_title: String,
+    pub lca_salary: Decimal,
+    pub lca_code: String,
+    pub receipt_number: String,
+    pub h1b_start_date: NaiveDate,
+    pub h1b_end_date: NaiveDate,
+    pub login_email: String,
+}
+
+/// Personal and employment details stored in `global_visa_mgmt.person`.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct PersonDetails {
     pub email: String,
-}
\ No newline at end of file
+    pub login_email: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub dob: NaiveDate,
+    pub sex: String,
+    pub marital_status: String,
+    pub phone: String,
+    pub emergency_contact_name: String,
+    pub emergency_contact_phone: String,
+    pub employment_start_date: NaiveDate,
+}
+
+/// Home address stored in `global_visa_mgmt.residential_address`.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct ResidentialAddress {
+    pub street_name: String,
+    pub city: String,
+    pub state: String,
+    pub zip: String,
+}
+
+/// End-client worksite stored in `global_visa_mgmt.client_placement`.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct ClientPlacement {
+    pub client_name: String,
+    pub client_street_name: String,
+    pub client_city: String,
+    pub client_state: String,
+    pub client_zip: String,
+}
+
+/// Labor condition application stored in `global_visa_mgmt.lca`.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct LcaDetails {
+    pub lca_title: String,
+    pub lca_salary: Decimal,
+    pub lca_code: String,
+}
+
+/// Petition receipt and validity stored in `global_visa_mgmt.petition`.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct PetitionDetails {
+    pub receipt_number: String,
+    pub h1b_start_date: NaiveDate,
+    pub h1b_end_date: NaiveDate,
+}
+
+/// One independently addressable part of a customer record.
+#[derive(Debug, Clone)]
+pub enum CustomerSection {
+    Person(PersonDetails),
+    Address(ResidentialAddress),
+    Placement(ClientPlacement),
+    Lca(LcaDetails),
+    Petition(PetitionDetails),
+}
+
+impl CustomerSection {
+    /// Copies this section's fields onto a flat customer record.
+    pub fn apply(self, customer: &mut Customer) {
+        match self {
+            CustomerSection::Person(p) => {
+                customer.email = p.email;
+                customer.login_email = p.login_email;
+                customer.first_name = p.first_name;
+                customer.last_name = p.last_name;
+                customer.dob = p.dob;
+                customer.sex = p.sex;
+                customer.marital_status = p.marital_status;
+                customer.phone = p.phone;
+                customer.emergency_contact_name = p.emergency_contact_name;
+                customer.emergency_contact_phone = p.emergency_contact_phone;
+                customer.employment_start_date = p.employment_start_date;
+            }
+            CustomerSection::Address(a) => {
+                customer.street_name = a.street_name;
+                customer.city = a.city;
+                customer.state = a.state;
+                customer.zip = a.zip;
+            }
+            CustomerSection::Placement(c) => {
+                customer.client_name = c.client_name;
+                customer.client_street_name = c.client_street_name;
+                customer.client_city = c.client_city;
+                customer.client_state = c.client_state;
+                customer.client_zip = c.client_zip;
+            }
+            CustomerSection::Lca(l) => {
+                customer.lca_title = l.lca_title;
+                customer.lca_salary = l.lca_salary;
+                customer.lca_code = l.lca_code;
+            }
+            CustomerSection::Petition(t) => {
+                customer.receipt_number = t.receipt_number;
+                customer.h1b_start_date = t.h1b_start_date;
+                customer.h1b_end_date = t.h1b_end_date;
+            }
+        }
+    }
+}
+
+impl From<&Customer> for PersonDetails {
+    fn from(c: &Customer) -> Self {
+        Self {
+            email: c.email.clone(),
+            login_email: c.login_email.clone(),
+            first_name: c.first_name.clone(),
+            last_name: c.last_name.clone(),
+            dob: c.dob,
+            sex: c.sex.clone(),
+            marital_status: c.marital_status.clone(),
+            phone: c.phone.clone(),
+            emergency_contact_name: c.emergency_contact_name.clone(),
+            emergency_contact_phone: c.emergency_contact_phone.clone(),
+            employment_start_date: c.employment_start_date,
+        }
+    }
+}
+
+impl From<&Customer> for ResidentialAddress {
+    fn from(c: &Customer) -> Self {
+        Self {
+            street_name: c.street_name.clone(),
+            city: c.city.clone(),
+            state: c.state.clone(),
+            zip: c.zip.clone(),
+        }
+    }
+}
+
+impl From<&Customer> for ClientPlacement {
+    fn from(c: &Customer) -> Self {
+        Self {
+            client_name: c.client_name.clone(),
+            client_street_name: c.client_street_name.clone(),
+            client_city: c.client_city.clone(),
+            client_state: c.client_state.clone(),
+            client_zip: c.client_zip.clone(),
+        }
+    }
+}
+
+impl From<&Customer> for LcaDetails {
+    fn from(c: &Customer) -> Self {
+        Self {
+            lca_title: c.lca_title.clone(),
+            lca_salary: c.lca_salary,
+            lca_code: c.lca_code.clone(),
+        }
+    }
+}
+
+impl From<&Customer> for PetitionDetails {
+    fn from(c: &Customer) -> Self {
+        Self {
+            receipt_number: c.receipt_number.clone(),
+            h1b_start_date: c.h1b_start_date,
+            h1b_end_date: c.h1b_end_date,
+        }
+    }
+}
+
+impl From<PersonDetails> for CustomerSection {
+    fn from(p: PersonDetails) -> Self {
+        CustomerSection::Person(p)
+    }
+}
+
+impl From<ResidentialAddress> for CustomerSection {
+    fn from(a: ResidentialAddress) -> Self {
+        CustomerSection::Address(a)
+    }
+}
+
+impl From<ClientPlacement> for CustomerSection {
+    fn from(c: ClientPlacement) -> Self {
+        CustomerSection::Placement(c)
+    }
+}
+
+impl From<LcaDetails> for CustomerSection {
+    fn from(l: LcaDetails) -> Self {
+        CustomerSection::Lca(l)
+    }
+}
+
+/// Every write of a petition comes through here, so receipt numbers are
+/// stored the way they are compared.
+impl From<PetitionDetails> for CustomerSection {
+    fn from(t: PetitionDetails) -> Self {
+        CustomerSection::Petition(PetitionDetails { receipt_number: ReceiptNumber::normalize(&t.receipt_number), ..t })
+    }
+}
+
+impl CustomerUpdate {
+    pub fn into_sections(self) -> Vec<CustomerSection> {
+        vec![
+            CustomerSection::Person(PersonDetails {
+                email: self.email,
+                login_email: self.login_email,
+                first_name: self.first_name,
+                last_name: self.last_name,
+                dob: self.dob,
+                sex: self.sex,
+                marital_status: self.marital_status,
+                phone: self.phone,
+                emergency_contact_name: self.emergency_contact_name,
+                emergency_contact_phone: self.emergency_contact_phone,
+                employment_start_date: self.employment_start_date,
+            }),
+            CustomerSection::Address(ResidentialAddress {
+                street_name: self.street_name,
+                city: self.city,
+                state: self.state,
+                zip: self.zip,
+            }),
+            CustomerSection::Placement(ClientPlacement {
+                client_name: self.client_name,
+                client_street_name: self.client_street_name,
+                client_city: self.client_city,
+                client_state: self.client_state,
+                client_zip: self.client_zip,
+            }),
+            CustomerSection::Lca(LcaDetails {
+                lca_title: self.lca_title,
+                lca_salary: self.lca_salary,
+                lca_code: self.lca_code,
+            }),
+            PetitionDetails {
+                receipt_number: self.receipt_number,
+                h1b_start_date: self.h1b_start_date,
+                h1b_end_date: self.h1b_end_date,
+            }
+            .into(),
+        ]
+    }
+}
+
+impl From<&CreateCompleteCustomerRequest> for CustomerUpdate {
+    fn from(r: &CreateCompleteCustomerRequest) -> Self {
+        Self {
+            email: r.email.clone(),
+            first_name: r.first_name.clone(),
+            last_name: r.last_name.clone(),
+            dob: r.dob,
+            sex: r.sex.clone(),
+            marital_status: r.marital_status.clone(),
+            phone: r.phone.clone(),
+            emergency_contact_name: r.emergency_contact_name.clone(),
+            emergency_contact_phone: r.emergency_contact_phone.clone(),
+            employment_start_date: r.employment_start_date,
+            street_name: r.street_name.clone(),
+            city: r.city.clone(),
+            state: r.state.clone(),
+            zip: r.zip.clone(),
+            client_name: r.client_name.clone(),
+            client_street_name: r.client_street_name.clone(),
+            client_city: r.client_city.clone(),
+            client_state: r.client_state.clone(),
+            client_zip: r.client_zip.clone(),
+            lca_title: r.lca_title.clone(),
+            lca_salary: r.lca_salary,
+            lca_code: r.lca_code.clone(),
+            receipt_number: r.receipt_number.clone(),
+            h1b_start_date: r.h1b_start_date,
+            h1b_end_date: r.h1b_end_date,
+            login_email: r.login_email.clone(),
+        }
+    }
+}
+
+impl From<&Customer> for CustomerUpdate {
+    fn from(c: &Customer) -> Self {
+        Self {
+            email: c.email.clone(),
+            first_name: c.first_name.clone(),
+            last_name: c.last_name.clone(),
+            dob: c.dob,
+            sex: c.sex.clone(),
+            marital_status: c.marital_status.clone(),
+            phone: c.phone.clone(),
+            emergency_contact_name: c.emergency_contact_name.clone(),
+            emergency_contact_phone: c.emergency_contact_phone.clone(),
+            employment_start_date: c.employment_start_date,
+            street_name: c.street_name.clone(),
+            city: c.city.clone(),
+            state: c.state.clone(),
+            zip: c.zip.clone(),
+            client_name: c.client_name.clone(),
+            client_street_name: c.client_street_name.clone(),
+            client_city: c.client_city.clone(),
+            client_state: c.client_state.clone(),
+            client_zip: c.client_zip.clone(),
+            lca_title: c.lca_title.clone(),
+            lca_salary: c.lca_salary,
+            lca_code: c.lca_code.clone(),
+            receipt_number: c.receipt_number.clone(),
+            h1b_start_date: c.h1b_start_date,
+            h1b_end_date: c.h1b_end_date,
+            login_email: c.login_email.clone(),
+        }
+    }
+}
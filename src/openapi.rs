@@ -0,0 +1,481 @@
+use std::sync::{Arc, OnceLock};
+
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Redirect, Response},
+};
+use serde::Serialize;
+use utoipa::openapi::path::{Operation, OperationBuilder, ParameterBuilder, ParameterIn, PathItemType};
+use utoipa::openapi::request_body::RequestBodyBuilder;
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityRequirement, SecurityScheme};
+use utoipa::openapi::{
+    Content, Deprecated, KnownFormat, ObjectBuilder, PathItem, Ref, RefOr, Required, ResponseBuilder, SchemaFormat,
+    SchemaType,
+};
+use utoipa::{Modify, OpenApi, ToSchema};
+use utoipa_swagger_ui::Config;
+use uuid::Uuid;
+
+use crate::case_status::CaseStatus;
+use crate::error::Problem;
+use crate::handlers::{self, SectionResource};
+use crate::models::{
+    CaseTransition, CaseTransitionRequest, ClientPlacement, CreateCompleteCustomerRequest, CustomerHistoryEntry,
+    CustomerResponse, CustomerUpdate, FieldChange, LcaDetails, PersonDetails, PetitionDetails, ResidentialAddress,
+    UpdateVisaDetailsRequest,
+};
+use crate::receipt::ReceiptDetails;
+use crate::soc::Occupation;
+use crate::validation::FieldError;
+use crate::wages::{ComplianceStatus, LevelWages, WageArea, WageCompliance};
+
+const PROBLEM_JSON: &str = "application/problem+json";
+
+// Response bodies the handlers build with `json!`, described here for the spec.
+
+#[allow(dead_code)]
+#[derive(Serialize, ToSchema)]
+pub struct HealthStatus {
+    #[schema(example = "OK")]
+    status: String,
+    message: String,
+}
+
+#[allow(dead_code)]
+#[derive(Serialize, ToSchema)]
+pub struct ConnectionStatus {
+    status: String,
+    /// Latest applied migration, if any.
+    schema_version: Option<i64>,
+}
+
+#[allow(dead_code)]
+#[derive(Serialize, ToSchema)]
+pub struct CreatedMessage {
+    message: String,
+    email: String,
+    customer_id: Uuid,
+    /// 0 when an earlier request with the same `Idempotency-Key` created it.
+    rows_affected: u64,
+    created_record: CustomerResponse,
+}
+
+#[allow(dead_code)]
+#[derive(Serialize, ToSchema)]
+pub struct UpdatedMessage {
+    message: String,
+    customer_id: String,
+    rows_affected: u64,
+    version: i64,
+}
+
+#[allow(dead_code)]
+#[derive(Serialize, ToSchema)]
+pub struct StatusChange {
+    message: String,
+    customer_id: String,
+    rows_affected: u64,
+    updated_record: CustomerResponse,
+}
+
+#[allow(dead_code)]
+#[derive(Serialize, ToSchema)]
+pub struct CustomerPage {
+    items: Vec<CustomerResponse>,
+    /// Pass as `cursor` to fetch the next page; `null` on the last one.
+    next_cursor: Option<String>,
+    /// Number of matching customers, when `include_total=true`.
+    total: Option<i64>,
+}
+
+#[allow(dead_code)]
+#[derive(Serialize, ToSchema)]
+pub struct SearchResult {
+    score: f32,
+    matched_field: String,
+    /// `[start, end)` character ranges of `matched_value` that match the query.
+    highlight: Vec<Vec<usize>>,
+    matched_value: String,
+    customer: CustomerResponse,
+}
+
+#[allow(dead_code)]
+#[derive(Serialize, ToSchema)]
+pub struct SearchResults {
+    query: String,
+    results: Vec<SearchResult>,
+}
+
+#[allow(dead_code)]
+#[derive(Serialize, ToSchema)]
+pub struct OccupationResults {
+    query: String,
+    occupations: Vec<Occupation>,
+}
+
+#[allow(dead_code)]
+#[derive(Serialize, ToSchema)]
+pub struct WageReportEntry {
+    customer_id: Uuid,
+    first_name: String,
+    last_name: String,
+    email: String,
+    client_name: String,
+    lca_title: String,
+    compliance: WageCompliance,
+}
+
+#[allow(dead_code)]
+#[derive(Serialize, ToSchema)]
+pub struct BelowLevel1Report {
+    /// Active customers checked.
+    checked: usize,
+    /// Largest shortfall first.
+    below_level_1: Vec<WageReportEntry>,
+    /// Customers with no wage to compare against; `compliance.reason` says why.
+    unchecked: Vec<WageReportEntry>,
+}
+
+#[allow(dead_code)]
+#[derive(Serialize, ToSchema)]
+pub struct CustomerHistory {
+    customer_id: Uuid,
+    versions: Vec<CustomerHistoryEntry>,
+}
+
+#[allow(dead_code)]
+#[derive(Serialize, ToSchema)]
+pub struct CaseTransitions {
+    customer_id: Uuid,
+    case_status: CaseStatus,
+    /// Statuses the case can move to next; empty once it is final.
+    allowed_transitions: Vec<CaseStatus>,
+    /// Oldest first.
+    transitions: Vec<CaseTransition>,
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Visa API",
+        description = "H-1B customer records. Errors are `application/problem+json` bodies carrying a stable `code` \
+                       and the request's `x-request-id`."
+    ),
+    paths(
+        handlers::health_check,
+        handlers::test_connection,
+        handlers::get_metrics,
+        handlers::list_all_customers,
+        handlers::create_customer,
+        handlers::search_customers,
+        handlers::get_customer,
+        handlers::replace_customer,
+        handlers::patch_customer,
+        handlers::customer_action,
+        handlers::get_customer_history,
+        handlers::create_case_transition,
+        handlers::get_case_transitions,
+        handlers::get_wage_compliance,
+        handlers::below_level_1_report,
+        handlers::lookup_occupations,
+        handlers::get_occupation,
+        handlers::create_visa_details,
+        handlers::get_all_customers_with_status,
+        handlers::get_all_customers_no_filter,
+        handlers::get_customer_by_id,
+        handlers::get_customer_by_email,
+        handlers::get_customer_by_login_email,
+        handlers::update_customer_by_id,
+        handlers::soft_delete_customer_by_id,
+        handlers::activate_customer_by_id
+    ),
+    components(schemas(
+        CustomerResponse,
+        ReceiptDetails,
+        CreateCompleteCustomerRequest,
+        CustomerUpdate,
+        UpdateVisaDetailsRequest,
+        PersonDetails,
+        ResidentialAddress,
+        ClientPlacement,
+        LcaDetails,
+        PetitionDetails,
+        CustomerHistoryEntry,
+        FieldChange,
+        Problem,
+        FieldError,
+        HealthStatus,
+        ConnectionStatus,
+        CreatedMessage,
+        UpdatedMessage,
+        StatusChange,
+        CustomerPage,
+        SearchResult,
+        SearchResults,
+        CustomerHistory,
+        CaseStatus,
+        CaseTransitionRequest,
+        CaseTransition,
+        CaseTransitions,
+        Occupation,
+        OccupationResults,
+        WageCompliance,
+        ComplianceStatus,
+        WageArea,
+        LevelWages,
+        WageReportEntry,
+        BelowLevel1Report
+    )),
+    modifiers(&BearerAuth, &SectionRoutes, &ActivateRoute, &LegacyAliases, &ErrorResponses),
+    tags(
+        (name = "customers", description = "Customer records"),
+        (name = "occupations", description = "The 2018 SOC occupations `lca_code` is checked against"),
+        (name = "reports", description = "Reports across all customers"),
+        (name = "legacy", description = "Unversioned routes, deprecated in favour of `/v1`; see their `Link` header"),
+        (name = "system", description = "Health and metrics")
+    )
+)]
+pub struct ApiDoc;
+
+/// The `bearer_auth` scheme named by every protected operation.
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let mut scheme = Http::new(HttpAuthScheme::Bearer);
+        scheme.bearer_format = Some("JWT".to_string());
+        openapi
+            .components
+            .get_or_insert_with(Default::default)
+            .add_security_scheme("bearer_auth", SecurityScheme::Http(scheme));
+    }
+}
+
+fn bearer_auth() -> SecurityRequirement {
+    SecurityRequirement::new("bearer_auth", Vec::<String>::new())
+}
+
+fn problem(description: &str) -> utoipa::openapi::Response {
+    ResponseBuilder::new()
+        .description(description)
+        .content(PROBLEM_JSON, Content::new(Ref::from_schema_name("Problem")))
+        .build()
+}
+
+/// `GET` and `PUT` for each `/v1/customers/{id}/<section>` route. Their
+/// handlers are generic over the section, which `#[utoipa::path]` can't
+/// describe, so the operations are built here.
+struct SectionRoutes;
+
+impl SectionRoutes {
+    fn add<S: SectionResource>(openapi: &mut utoipa::openapi::OpenApi) {
+        let (schema, _) = S::schema();
+        let body = || Content::new(Ref::from_schema_name(schema));
+        let id = ParameterBuilder::new()
+            .name("id")
+            .parameter_in(ParameterIn::Path)
+            .required(Required::True)
+            .description(Some("Customer id"))
+            .schema(Some(
+                ObjectBuilder::new()
+                    .schema_type(SchemaType::String)
+                    .format(Some(SchemaFormat::KnownFormat(KnownFormat::Uuid))),
+            ))
+            .build();
+
+        let get = OperationBuilder::new()
+            .tag("customers")
+            .operation_id(Some(format!("get_customer_{}", S::NAME)))
+            .summary(Some(format!("The customer's `{}` section.", S::NAME)))
+            .parameter(id.clone())
+            .response(
+                "200",
+                ResponseBuilder::new()
+                    .description("The section; `ETag` carries the record version")
+                    .content("application/json", body())
+                    .build(),
+            )
+            .response("404", problem("Unknown customer"))
+            .security(bearer_auth())
+            .build();
+        let put = OperationBuilder::new()
+            .tag("customers")
+            .operation_id(Some(format!("update_customer_{}", S::NAME)))
+            .summary(Some(format!("Replace the customer's `{}` section.", S::NAME)))
+            .parameter(id)
+            .request_body(Some(
+                RequestBodyBuilder::new().content("application/json", body()).required(Some(Required::True)).build(),
+            ))
+            .response(
+                "200",
+                ResponseBuilder::new()
+                    .description(
+                        "The updated section, with `warnings` as on `CustomerResponse` when a value looks wrong; \
+                         `ETag` carries the new record version",
+                    )
+                    .content("application/json", body())
+                    .build(),
+            )
+            .response("404", problem("Unknown customer"))
+            .response("409", problem("`email` or `receipt_number` already belongs to another customer"))
+            .response("412", problem("`If-Match` does not name the current version"))
+            .response("422", problem("A field is missing, has the wrong type or breaks a validation rule"))
+            .security(bearer_auth())
+            .build();
+
+        let mut item = PathItem::new(PathItemType::Get, get);
+        item.operations.insert(PathItemType::Put, put);
+        openapi.paths.paths.insert(format!("/v1/customers/{{id}}/{}", S::NAME), item);
+    }
+}
+
+impl Modify for SectionRoutes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        Self::add::<PersonDetails>(openapi);
+        Self::add::<ResidentialAddress>(openapi);
+        Self::add::<ClientPlacement>(openapi);
+        Self::add::<LcaDetails>(openapi);
+        Self::add::<PetitionDetails>(openapi);
+    }
+}
+
+/// `customer_action` serves both `:deactivate` and `:activate`; its
+/// annotation describes the first, and this copies it for the second.
+struct ActivateRoute;
+
+impl Modify for ActivateRoute {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let Some(operation) = operation_mut(openapi, "/v1/customers/{id}:deactivate", PathItemType::Post) else {
+            return;
+        };
+        operation.operation_id = Some("deactivate_customer".to_string());
+        operation.summary = Some("Set the customer's status to `Inactive`.".to_string());
+
+        let mut activate = operation.clone();
+        activate.operation_id = Some("activate_customer".to_string());
+        activate.summary = Some("Set the customer's status to `Active`.".to_string());
+        openapi
+            .paths
+            .paths
+            .insert("/v1/customers/{id}:activate".to_string(), PathItem::new(PathItemType::Post, activate));
+    }
+}
+
+/// Legacy routes served by the same handler as a `/v1` route, and that route.
+const LEGACY_ALIASES: [(&str, PathItemType, &str); 13] = [
+    ("/customers/search", PathItemType::Get, "/v1/customers/search"),
+    ("/customers/{id}", PathItemType::Patch, "/v1/customers/{id}"),
+    ("/customers/{id}/history", PathItemType::Get, "/v1/customers/{id}/history"),
+    ("/customers/{id}/person", PathItemType::Get, "/v1/customers/{id}/person"),
+    ("/customers/{id}/person", PathItemType::Put, "/v1/customers/{id}/person"),
+    ("/customers/{id}/address", PathItemType::Get, "/v1/customers/{id}/address"),
+    ("/customers/{id}/address", PathItemType::Put, "/v1/customers/{id}/address"),
+    ("/customers/{id}/placement", PathItemType::Get, "/v1/customers/{id}/placement"),
+    ("/customers/{id}/placement", PathItemType::Put, "/v1/customers/{id}/placement"),
+    ("/customers/{id}/lca", PathItemType::Get, "/v1/customers/{id}/lca"),
+    ("/customers/{id}/lca", PathItemType::Put, "/v1/customers/{id}/lca"),
+    ("/customers/{id}/petition", PathItemType::Get, "/v1/customers/{id}/petition"),
+    ("/customers/{id}/petition", PathItemType::Put, "/v1/customers/{id}/petition"),
+];
+
+/// Adds the legacy aliases of `/v1` operations and marks every `legacy`
+/// operation deprecated.
+struct LegacyAliases;
+
+impl Modify for LegacyAliases {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        for (legacy, method, v1) in LEGACY_ALIASES {
+            let Some(operation) = operation_mut(openapi, v1, method.clone()) else {
+                continue;
+            };
+            let mut alias = operation.clone();
+            alias.tags = Some(vec!["legacy".to_string()]);
+            alias.operation_id = alias.operation_id.map(|id| format!("{}_legacy", id));
+            openapi.paths.paths.entry(legacy.to_string()).or_default().operations.insert(method, alias);
+        }
+
+        for operation in operations_mut(openapi) {
+            if operation.tags.iter().flatten().any(|tag| tag == "legacy") {
+                operation.deprecated = Some(Deprecated::True);
+            }
+        }
+    }
+}
+
+/// Responses every operation can give but no annotation lists: 401 on
+/// protected routes, 500 and 503 when storage fails. Also serves each
+/// `Problem` body as `application/problem+json`, which `#[utoipa::path]`
+/// can't be told without repeating it on every response.
+struct ErrorResponses;
+
+impl Modify for ErrorResponses {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        for operation in operations_mut(openapi) {
+            let responses = &mut operation.responses.responses;
+            if operation.security.is_some() {
+                responses.entry("401".to_string()).or_insert_with(|| problem("Missing or invalid bearer token").into());
+                responses.entry("500".to_string()).or_insert_with(|| problem("Unexpected server error").into());
+                responses
+                    .entry("503".to_string())
+                    .or_insert_with(|| problem("Storage is unavailable; retry after `Retry-After` seconds").into());
+            }
+
+            for (status, response) in responses.iter_mut() {
+                let RefOr::T(response) = response else {
+                    continue;
+                };
+                if !(status.starts_with('4') || status.starts_with('5')) {
+                    continue;
+                }
+                response.content = std::mem::take(&mut response.content)
+                    .into_iter()
+                    .map(|(content_type, content)| match content_type.as_str() {
+                        "application/json" => (PROBLEM_JSON.to_string(), content),
+                        _ => (content_type, content),
+                    })
+                    .collect();
+            }
+        }
+    }
+}
+
+fn operation_mut<'a>(
+    openapi: &'a mut utoipa::openapi::OpenApi,
+    path: &str,
+    method: PathItemType,
+) -> Option<&'a mut Operation> {
+    openapi.paths.paths.get_mut(path)?.operations.get_mut(&method)
+}
+
+fn operations_mut(openapi: &mut utoipa::openapi::OpenApi) -> impl Iterator<Item = &mut Operation> {
+    openapi.paths.paths.values_mut().flat_map(|item| item.operations.values_mut())
+}
+
+/// The OpenAPI document, built once.
+fn spec() -> &'static utoipa::openapi::OpenApi {
+    static SPEC: OnceLock<utoipa::openapi::OpenApi> = OnceLock::new();
+    SPEC.get_or_init(ApiDoc::openapi)
+}
+
+pub async fn openapi_json() -> Json<&'static utoipa::openapi::OpenApi> {
+    Json(spec())
+}
+
+pub async fn docs_redirect() -> Redirect {
+    Redirect::permanent("/docs/")
+}
+
+/// Swagger UI's files, pointed at `/openapi.json`.
+pub async fn swagger_ui(path: Option<Path<String>>) -> Response {
+    let config = Arc::new(Config::from("/openapi.json"));
+    let file = path.map(|Path(file)| file).unwrap_or_default();
+    match utoipa_swagger_ui::serve(&file, config) {
+        Ok(Some(file)) => ([(header::CONTENT_TYPE, file.content_type)], file.bytes.into_owned()).into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            tracing::error!("Failed to serve Swagger UI file {}: {}", file, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
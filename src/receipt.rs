@@ -0,0 +1,166 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Receipt prefixes USCIS issues, the office each stands for, and whether
+/// the first two digits are the fiscal year of receipt. ELIS (`IOE`)
+/// numbers are sequential and carry no year.
+const SERVICE_CENTERS: [(&str, &str, bool); 12] = [
+    ("EAC", "Vermont Service Center", true),
+    ("VSC", "Vermont Service Center", true),
+    ("WAC", "California Service Center", true),
+    ("CSC", "California Service Center", true),
+    ("LIN", "Nebraska Service Center", true),
+    ("NSC", "Nebraska Service Center", true),
+    ("SRC", "Texas Service Center", true),
+    ("TSC", "Texas Service Center", true),
+    ("YSC", "Potomac Service Center", true),
+    ("MSC", "National Benefits Center", true),
+    ("NBC", "National Benefits Center", true),
+    ("IOE", "USCIS Electronic Immigration System (ELIS)", false),
+];
+
+/// A USCIS case receipt number such as `EAC2412345678`: a three-letter
+/// service center code followed by ten digits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReceiptNumber(String);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReceiptNumberError {
+    /// Not three letters and ten digits.
+    Malformed,
+    /// Well-formed, but USCIS issues no receipts with this prefix.
+    UnknownServiceCenter(String),
+}
+
+impl fmt::Display for ReceiptNumberError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReceiptNumberError::Malformed => write!(
+                f,
+                "must be a USCIS receipt number: a service center code such as EAC, WAC, LIN, SRC or IOE followed by 10 digits"
+            ),
+            ReceiptNumberError::UnknownServiceCenter(code) => {
+                write!(f, "{} is not a USCIS service center code", code)
+            },
+        }
+    }
+}
+
+impl FromStr for ReceiptNumber {
+    type Err = ReceiptNumberError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = ReceiptNumber::normalize(value);
+        let well_formed = value.len() == 13
+            && value.bytes().take(3).all(|b| b.is_ascii_uppercase())
+            && value.bytes().skip(3).all(|b| b.is_ascii_digit());
+        if !well_formed {
+            return Err(ReceiptNumberError::Malformed);
+        }
+        let code = &value[..3];
+        if !SERVICE_CENTERS.iter().any(|(c, _, _)| *c == code) {
+            return Err(ReceiptNumberError::UnknownServiceCenter(code.to_string()));
+        }
+        Ok(ReceiptNumber(value))
+    }
+}
+
+impl fmt::Display for ReceiptNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl ReceiptNumber {
+    /// How receipt numbers are stored: USCIS prints them in capitals, but
+    /// they are often typed or pasted otherwise.
+    pub fn normalize(value: &str) -> String {
+        value.trim().to_ascii_uppercase()
+    }
+
+    fn center(&self) -> (&'static str, &'static str, bool) {
+        *SERVICE_CENTERS
+            .iter()
+            .find(|(code, _, _)| *code == self.service_center_code())
+            .expect("checked when parsed")
+    }
+
+    pub fn service_center_code(&self) -> &str {
+        &self.0[..3]
+    }
+
+    pub fn service_center(&self) -> &'static str {
+        self.center().1
+    }
+
+    /// USCIS fiscal year (October to September, named for the year it ends
+    /// in) the case was received in, where the number records it.
+    pub fn fiscal_year(&self) -> Option<i32> {
+        let (_, _, dated) = self.center();
+        dated.then(|| 2000 + self.0[3..5].parse::<i32>().expect("checked when parsed"))
+    }
+
+    pub fn details(&self) -> ReceiptDetails {
+        ReceiptDetails {
+            service_center_code: self.service_center_code().to_string(),
+            service_center: self.service_center().to_string(),
+            fiscal_year: self.fiscal_year(),
+        }
+    }
+}
+
+/// What a receipt number says about the case, returned alongside it.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ReceiptDetails {
+    #[schema(example = "EAC")]
+    pub service_center_code: String,
+    #[schema(example = "Vermont Service Center")]
+    pub service_center: String,
+    /// `null` for ELIS (`IOE`) receipts, which do not record it.
+    #[schema(example = 2024)]
+    pub fiscal_year: Option<i32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_service_center() {
+        for (code, name, dated) in SERVICE_CENTERS {
+            let receipt = format!("{}2412345678", code).parse::<ReceiptNumber>().unwrap();
+            assert_eq!(receipt.service_center_code(), code);
+            assert_eq!(receipt.service_center(), name);
+            assert_eq!(receipt.fiscal_year(), dated.then_some(2024), "{}", code);
+        }
+    }
+
+    #[test]
+    fn reads_the_fiscal_year_from_dated_receipts() {
+        assert_eq!("LIN0912345678".parse::<ReceiptNumber>().unwrap().fiscal_year(), Some(2009));
+        assert_eq!("IOE0912345678".parse::<ReceiptNumber>().unwrap().fiscal_year(), None);
+    }
+
+    #[test]
+    fn trims_and_uppercases() {
+        let receipt = " eac2412345678 ".parse::<ReceiptNumber>().unwrap();
+        assert_eq!(receipt.to_string(), "EAC2412345678");
+        assert_eq!(receipt.details().service_center, "Vermont Service Center");
+    }
+
+    #[test]
+    fn rejects_malformed_numbers() {
+        for value in ["", "EAC241234567", "EAC24123456789", "EAC24123456X8", "EAC 2412345678", "EA12412345678", "1232412345678", "ÉAC2412345678"] {
+            assert_eq!(value.parse::<ReceiptNumber>(), Err(ReceiptNumberError::Malformed), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_service_centers() {
+        assert_eq!("ABC2412345678".parse::<ReceiptNumber>(), Err(ReceiptNumberError::UnknownServiceCenter("ABC".to_string())));
+        assert_eq!("xyz2412345678".parse::<ReceiptNumber>(), Err(ReceiptNumberError::UnknownServiceCenter("XYZ".to_string())));
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::types::Json;
+use uuid::Uuid;
+
+use crate::case_status::CaseStatus;
+use crate::models::{
+    CaseTransition, CreateCompleteCustomerRequest, Customer, CustomerRevision, CustomerSection, CustomerUpdate,
+    SearchHit, DEFAULT_H1B_STATUS,
+};
+use crate::receipt::ReceiptNumber;
+use crate::search::{self, word_similarity, MATCH_THRESHOLD, SEARCH_FIELDS};
+
+use super::{
+    compare_positions, ChangeContext, CustomerListQuery, CustomerPage, CustomerRepository, IdempotencyKey,
+    IdempotentCreate, Position, RepositoryError, RepositoryResult, IDEMPOTENCY_KEY_TTL_HOURS,
+};
+
+/// `CustomerRepository` that keeps records in process memory. Used for running
+/// the API without a database and for exercising handlers in isolation.
+#[derive(Default)]
+pub struct InMemoryCustomerRepository {
+    customers: RwLock<Vec<Customer>>,
+    /// Always locked after `customers`.
+    revisions: RwLock<Vec<(Uuid, CustomerRevision)>>,
+    /// Always locked after `customers`.
+    transitions: RwLock<Vec<(Uuid, CaseTransition)>>,
+    /// Creates by actor and `Idempotency-Key`. Always locked after `customers`.
+    idempotency_keys: RwLock<HashMap<(String, String), IdempotentCreate>>,
+}
+
+impl InMemoryCustomerRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn record_revision(&self, customer: &Customer, change: ChangeContext<'_>) {
+        self.revisions.write().unwrap().push((customer.customer_id, CustomerRevision {
+            version: customer.version,
+            changed_at: Utc::now(),
+            actor: change.actor.map(str::to_string),
+            action: change.action.to_string(),
+            snapshot: Json(customer.clone()),
+        }));
+    }
+}
+
+fn idempotency_key_live(created_at: DateTime<Utc>) -> bool {
+    created_at > Utc::now() - Duration::hours(IDEMPOTENCY_KEY_TTL_HOURS)
+}
+
+fn bump_version(customer: &mut Customer, expected_version: Option<i64>) -> RepositoryResult<()> {
+    if expected_version.is_some_and(|expected| expected != customer.version) {
+        return Err(RepositoryError::VersionMismatch { current_version: customer.version });
+    }
+    customer.version += 1;
+    Ok(())
+}
+
+/// Mirrors the unique indexes on email (case-insensitive) and receipt number,
+/// which ignore blank values.
+fn check_unique(customers: &[Customer], candidate: &Customer) -> RepositoryResult<()> {
+    for other in customers.iter().filter(|c| c.customer_id != candidate.customer_id) {
+        if !candidate.email.is_empty() && other.email.eq_ignore_ascii_case(&candidate.email) {
+            return Err(RepositoryError::Duplicate { field: "email", customer_id: other.customer_id });
+        }
+        if !candidate.receipt_number.is_empty() && other.receipt_number == candidate.receipt_number {
+            return Err(RepositoryError::Duplicate { field: "receipt_number", customer_id: other.customer_id });
+        }
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl CustomerRepository for InMemoryCustomerRepository {
+    async fn schema_version(&self) -> RepositoryResult<Option<i64>> {
+        Ok(None)
+    }
+
+    async fn create(
+        &self,
+        request: &CreateCompleteCustomerRequest,
+        change: ChangeContext<'_>,
+        idempotency: Option<IdempotencyKey<'_>>,
+    ) -> RepositoryResult<Customer> {
+        let customer = Customer {
+            customer_id: Uuid::new_v4(),
+            email: request.email.clone(),
+            first_name: request.first_name.clone(),
+            last_name: request.last_name.clone(),
+            dob: request.dob,
+            sex: request.sex.clone(),
+            marital_status: request.marital_status.clone(),
+            phone: request.phone.clone(),
+            emergency_contact_name: request.emergency_contact_name.clone(),
+            emergency_contact_phone: request.emergency_contact_phone.clone(),
+            employment_start_date: request.employment_start_date,
+            street_name: request.street_name.clone(),
+            city: request.city.clone(),
+            state: request.state.clone(),
+            zip: request.zip.clone(),
+            client_name: request.client_name.clone(),
+            client_street_name: request.client_street_name.clone(),
+            client_city: request.client_city.clone(),
+            client_state: request.client_state.clone(),
+            client_zip: request.client_zip.clone(),
+            lca_title: request.lca_title.clone(),
+            lca_salary: request.lca_salary,
+            lca_code: request.lca_code.clone(),
+            receipt_number: ReceiptNumber::normalize(&request.receipt_number),
+            h1b_start_date: request.h1b_start_date,
+            h1b_end_date: request.h1b_end_date,
+            login_email: request.login_email.clone(),
+            h1b_status: request.h1b_status.clone().unwrap_or_else(|| DEFAULT_H1B_STATUS.to_string()),
+            case_status: CaseStatus::Draft,
+            version: 1,
+        };
+        let mut customers = self.customers.write().unwrap();
+        let mut idempotency_keys = self.idempotency_keys.write().unwrap();
+        let claim = idempotency.map(|i| (change.actor.unwrap_or_default().to_string(), i.key.to_string()));
+        if let Some(claim) = &claim {
+            if idempotency_keys.get(claim).is_some_and(|earlier| idempotency_key_live(earlier.created_at)) {
+                return Err(RepositoryError::IdempotencyKeyInUse);
+            }
+        }
+        check_unique(&customers, &customer)?;
+        customers.push(customer.clone());
+        if let (Some(claim), Some(idempotency)) = (claim, idempotency) {
+            idempotency_keys.insert(claim, IdempotentCreate {
+                request: idempotency.request.clone(),
+                customer_id: customer.customer_id,
+                created_at: Utc::now(),
+            });
+        }
+        drop(idempotency_keys);
+        self.record_revision(&customer, change);
+        Ok(customer)
+    }
+
+    async fn find_idempotent_create(&self, actor: &str, key: &str) -> RepositoryResult<Option<IdempotentCreate>> {
+        let idempotency_keys = self.idempotency_keys.read().unwrap();
+        Ok(idempotency_keys
+            .get(&(actor.to_string(), key.to_string()))
+            .filter(|earlier| idempotency_key_live(earlier.created_at))
+            .cloned())
+    }
+
+    async fn find_by_id(&self, customer_id: Uuid) -> RepositoryResult<Option<Customer>> {
+        let customers = self.customers.read().unwrap();
+        Ok(customers.iter().find(|c| c.customer_id == customer_id).cloned())
+    }
+
+    async fn find_by_email(&self, email: &str) -> RepositoryResult<Vec<Customer>> {
+        let customers = self.customers.read().unwrap();
+        Ok(customers
+            .iter()
+            .filter(|c| c.email == email || c.login_email == email)
+            .cloned()
+            .collect())
+    }
+
+    async fn find_by_login_email(&self, login_email: &str, h1b_status: Option<&str>) -> RepositoryResult<Vec<Customer>> {
+        let customers = self.customers.read().unwrap();
+        Ok(customers
+            .iter()
+            .filter(|c| c.login_email == login_email)
+            .filter(|c| h1b_status.is_none_or(|status| c.h1b_status == status))
+            .cloned()
+            .collect())
+    }
+
+    async fn list(&self, query: &CustomerListQuery) -> RepositoryResult<CustomerPage> {
+        let customers = self.customers.read().unwrap();
+        let mut matching: Vec<(Position, &Customer)> = customers
+            .iter()
+            .filter(|c| query.filter.matches(c))
+            .map(|c| (Position::of(c, &query.sort), c))
+            .collect();
+        matching.sort_by(|a, b| compare_positions(&a.0, &b.0, &query.sort));
+
+        let total = query.with_total.then_some(matching.len() as i64);
+        let page = matching
+            .into_iter()
+            .filter(|(position, _)| {
+                query.after.as_ref().is_none_or(|after| compare_positions(position, after, &query.sort).is_gt())
+            })
+            .take(query.limit.map_or(usize::MAX, |limit| limit as usize + 1))
+            .map(|(_, c)| c.clone())
+            .collect();
+        Ok(CustomerPage::from_rows(page, query, total))
+    }
+
+    async fn search(&self, query: &str, limit: u32) -> RepositoryResult<Vec<SearchHit>> {
+        let customers = self.customers.read().unwrap();
+        let mut hits: Vec<SearchHit> = customers
+            .iter()
+            .filter_map(|customer| {
+                let values = [
+                    &customer.first_name,
+                    &customer.last_name,
+                    &customer.email,
+                    &customer.login_email,
+                    &customer.client_name,
+                    &customer.receipt_number,
+                ];
+                SEARCH_FIELDS
+                    .iter()
+                    .zip(values)
+                    .map(|(field, value)| (field, value, word_similarity(query, value)))
+                    // As on Postgres, substrings too short to clear the threshold match too
+                    .filter(|(_, value, score)| *score >= MATCH_THRESHOLD || search::contains(value, query))
+                    .max_by(|a, b| a.2.total_cmp(&b.2))
+                    .map(|(field, value, score)| SearchHit {
+                        customer: customer.clone(),
+                        matched_field: field.to_string(),
+                        matched_value: value.clone(),
+                        score,
+                    })
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.customer.customer_id.cmp(&b.customer.customer_id)));
+        hits.truncate(limit as usize);
+        Ok(hits)
+    }
+
+    async fn update(&self, customer_id: Uuid, update: &CustomerUpdate, expected_version: Option<i64>, change: ChangeContext<'_>) -> RepositoryResult<Option<Customer>> {
+        let mut customers = self.customers.write().unwrap();
+        let Some(index) = customers.iter().position(|c| c.customer_id == customer_id) else {
+            return Ok(None);
+        };
+        let mut customer = customers[index].clone();
+        bump_version(&mut customer, expected_version)?;
+        for section in update.clone().into_sections() {
+            section.apply(&mut customer);
+        }
+        check_unique(&customers, &customer)?;
+        customers[index] = customer.clone();
+        self.record_revision(&customer, change);
+        Ok(Some(customer))
+    }
+
+    async fn update_section(&self, customer_id: Uuid, section: CustomerSection, expected_version: Option<i64>, change: ChangeContext<'_>) -> RepositoryResult<Option<Customer>> {
+        let mut customers = self.customers.write().unwrap();
+        let Some(index) = customers.iter().position(|c| c.customer_id == customer_id) else {
+            return Ok(None);
+        };
+        let mut customer = customers[index].clone();
+        bump_version(&mut customer, expected_version)?;
+        section.apply(&mut customer);
+        check_unique(&customers, &customer)?;
+        customers[index] = customer.clone();
+        self.record_revision(&customer, change);
+        Ok(Some(customer))
+    }
+
+    async fn set_status(&self, customer_id: Uuid, h1b_status: &str, change: ChangeContext<'_>) -> RepositoryResult<Option<Customer>> {
+        let mut customers = self.customers.write().unwrap();
+        let Some(customer) = customers.iter_mut().find(|c| c.customer_id == customer_id) else {
+            return Ok(None);
+        };
+        if customer.h1b_status == h1b_status {
+            return Err(RepositoryError::StatusUnchanged { h1b_status: customer.h1b_status.clone() });
+        }
+        bump_version(customer, None)?;
+        customer.h1b_status = h1b_status.to_string();
+        self.record_revision(customer, change);
+        Ok(Some(customer.clone()))
+    }
+
+    async fn transition(
+        &self,
+        customer_id: Uuid,
+        to: CaseStatus,
+        reason: &str,
+        expected_version: Option<i64>,
+        change: ChangeContext<'_>,
+    ) -> RepositoryResult<Option<(Customer, CaseTransition)>> {
+        let mut customers = self.customers.write().unwrap();
+        let Some(index) = customers.iter().position(|c| c.customer_id == customer_id) else {
+            return Ok(None);
+        };
+        let mut customer = customers[index].clone();
+        bump_version(&mut customer, expected_version)?;
+        let from = customer.case_status;
+        if !from.can_move_to(to) {
+            return Err(RepositoryError::IllegalTransition { from, to });
+        }
+        customer.case_status = to;
+        let transition = CaseTransition {
+            version: customer.version,
+            from_status: from,
+            to_status: to,
+            reason: reason.to_string(),
+            actor: change.actor.map(str::to_string),
+            transitioned_at: Utc::now(),
+        };
+        customers[index] = customer.clone();
+        self.transitions.write().unwrap().push((customer_id, transition.clone()));
+        self.record_revision(&customer, change);
+        Ok(Some((customer, transition)))
+    }
+
+    async fn transitions(&self, customer_id: Uuid) -> RepositoryResult<Vec<CaseTransition>> {
+        let transitions = self.transitions.read().unwrap();
+        Ok(transitions
+            .iter()
+            .filter(|(id, _)| *id == customer_id)
+            .map(|(_, transition)| transition.clone())
+            .collect())
+    }
+
+    async fn history(&self, customer_id: Uuid) -> RepositoryResult<Vec<CustomerRevision>> {
+        let revisions = self.revisions.read().unwrap();
+        Ok(revisions
+            .iter()
+            .filter(|(id, _)| *id == customer_id)
+            .map(|(_, revision)| revision.clone())
+            .collect())
+    }
+
+    async fn find_as_of(&self, customer_id: Uuid, at: DateTime<Utc>) -> RepositoryResult<Option<Customer>> {
+        let revisions = self.revisions.read().unwrap();
+        Ok(revisions
+            .iter()
+            .rfind(|(id, revision)| *id == customer_id && revision.changed_at <= at)
+            .map(|(_, revision)| revision.snapshot.0.clone()))
+    }
+}
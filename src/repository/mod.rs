@@ -0,0 +1,367 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::case_status::CaseStatus;
+use crate::models::{
+    CaseTransition, CreateCompleteCustomerRequest, Customer, CustomerRevision, CustomerSection, CustomerUpdate, SearchHit,
+};
+
+pub mod memory;
+pub mod postgres;
+
+pub use memory::InMemoryCustomerRepository;
+pub use postgres::PgCustomerRepository;
+
+#[derive(Debug)]
+pub enum RepositoryError {
+    Database(sqlx::Error),
+    /// The caller's expected version is stale.
+    VersionMismatch { current_version: i64 },
+    /// A status change found the record already in the requested status.
+    StatusUnchanged { h1b_status: String },
+    /// The case status cannot move from `from` to `to`.
+    IllegalTransition { from: CaseStatus, to: CaseStatus },
+    /// A unique field (`email`, `receipt_number`) already belongs to another
+    /// customer.
+    Duplicate { field: &'static str, customer_id: Uuid },
+    /// The actor already made a create with this `Idempotency-Key` within
+    /// `IDEMPOTENCY_KEY_TTL_HOURS`.
+    IdempotencyKeyInUse,
+}
+
+impl std::fmt::Display for RepositoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RepositoryError::Database(e) => write!(f, "database error: {}", e),
+            RepositoryError::VersionMismatch { current_version } => {
+                write!(f, "record has changed, current version is {}", current_version)
+            }
+            RepositoryError::StatusUnchanged { h1b_status } => write!(f, "record is already {}", h1b_status),
+            RepositoryError::IllegalTransition { from, to } => write!(f, "case cannot move from {} to {}", from, to),
+            RepositoryError::Duplicate { field, customer_id } => {
+                write!(f, "{} already used by customer {}", field, customer_id)
+            }
+            RepositoryError::IdempotencyKeyInUse => write!(f, "idempotency key already used"),
+        }
+    }
+}
+
+impl std::error::Error for RepositoryError {}
+
+impl From<sqlx::Error> for RepositoryError {
+    fn from(e: sqlx::Error) -> Self {
+        RepositoryError::Database(e)
+    }
+}
+
+pub type RepositoryResult<T> = Result<T, RepositoryError>;
+
+/// Inclusive date bounds; either end may be open.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DateRange {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+impl DateRange {
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.from.is_none_or(|from| date >= from) && self.to.is_none_or(|to| date <= to)
+    }
+}
+
+/// Conditions on the list endpoints; all given ones must hold.
+#[derive(Debug, Clone, Default)]
+pub struct CustomerFilter {
+    pub h1b_status: Option<String>,
+    pub case_status: Option<CaseStatus>,
+    /// Exact `email` or `login_email`, as `find_by_email` matches.
+    pub email: Option<String>,
+    pub login_email: Option<String>,
+    /// Residential state, case-insensitive.
+    pub state: Option<String>,
+    /// Case-insensitive substring of the client name.
+    pub client_name: Option<String>,
+    pub lca_code: Option<String>,
+    /// Case-insensitive substring of the LCA job title.
+    pub lca_title: Option<String>,
+    pub employment_start_date: DateRange,
+    pub h1b_end_date: DateRange,
+}
+
+impl CustomerFilter {
+    pub fn matches(&self, customer: &Customer) -> bool {
+        let contains = |value: &str, needle: &str| value.to_lowercase().contains(&needle.to_lowercase());
+        self.h1b_status.as_deref().is_none_or(|status| customer.h1b_status == status)
+            && self.case_status.is_none_or(|status| customer.case_status == status)
+            && self.email.as_deref().is_none_or(|email| customer.email == email || customer.login_email == email)
+            && self.login_email.as_deref().is_none_or(|login| customer.login_email == login)
+            && self.state.as_deref().is_none_or(|state| customer.state.eq_ignore_ascii_case(state))
+            && self.client_name.as_deref().is_none_or(|name| contains(&customer.client_name, name))
+            && self.lca_code.as_deref().is_none_or(|code| customer.lca_code == code)
+            && self.lca_title.as_deref().is_none_or(|title| contains(&customer.lca_title, title))
+            && self.employment_start_date.contains(customer.employment_start_date)
+            && self.h1b_end_date.contains(customer.h1b_end_date)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKind {
+    Text,
+    Date,
+    Decimal,
+}
+
+/// A column the lists can be sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortField {
+    pub name: &'static str,
+    pub kind: SortKind,
+}
+
+/// Whitelist for `sort=`.
+pub const SORT_FIELDS: [SortField; 14] = [
+    SortField { name: "first_name", kind: SortKind::Text },
+    SortField { name: "last_name", kind: SortKind::Text },
+    SortField { name: "email", kind: SortKind::Text },
+    SortField { name: "login_email", kind: SortKind::Text },
+    SortField { name: "h1b_status", kind: SortKind::Text },
+    SortField { name: "state", kind: SortKind::Text },
+    SortField { name: "client_name", kind: SortKind::Text },
+    SortField { name: "lca_title", kind: SortKind::Text },
+    SortField { name: "lca_code", kind: SortKind::Text },
+    SortField { name: "lca_salary", kind: SortKind::Decimal },
+    SortField { name: "receipt_number", kind: SortKind::Text },
+    SortField { name: "employment_start_date", kind: SortKind::Date },
+    SortField { name: "h1b_start_date", kind: SortKind::Date },
+    SortField { name: "h1b_end_date", kind: SortKind::Date },
+];
+
+/// A sort column's value for one customer.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SortValue {
+    Text(String),
+    Date(NaiveDate),
+    Decimal(Decimal),
+}
+
+impl std::fmt::Display for SortValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SortValue::Text(v) => write!(f, "{}", v),
+            SortValue::Date(v) => write!(f, "{}", v),
+            SortValue::Decimal(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+impl SortField {
+    pub fn by_name(name: &str) -> Option<Self> {
+        SORT_FIELDS.into_iter().find(|f| f.name == name)
+    }
+
+    /// Reads a value written by `SortValue`'s `Display`.
+    pub fn parse(&self, raw: &str) -> Option<SortValue> {
+        match self.kind {
+            SortKind::Text => Some(SortValue::Text(raw.to_string())),
+            SortKind::Date => raw.parse().ok().map(SortValue::Date),
+            SortKind::Decimal => raw.parse().ok().map(SortValue::Decimal),
+        }
+    }
+
+    pub fn value_of(&self, customer: &Customer) -> SortValue {
+        let text = |value: &String| SortValue::Text(value.clone());
+        match self.name {
+            "first_name" => text(&customer.first_name),
+            "last_name" => text(&customer.last_name),
+            "email" => text(&customer.email),
+            "login_email" => text(&customer.login_email),
+            "h1b_status" => text(&customer.h1b_status),
+            "state" => text(&customer.state),
+            "client_name" => text(&customer.client_name),
+            "lca_title" => text(&customer.lca_title),
+            "lca_code" => text(&customer.lca_code),
+            "lca_salary" => SortValue::Decimal(customer.lca_salary),
+            "receipt_number" => text(&customer.receipt_number),
+            "employment_start_date" => SortValue::Date(customer.employment_start_date),
+            "h1b_start_date" => SortValue::Date(customer.h1b_start_date),
+            "h1b_end_date" => SortValue::Date(customer.h1b_end_date),
+            other => unreachable!("{} is in SORT_FIELDS but has no column here", other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortKey {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+/// Where a page ended: the last row's sort values and id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Position {
+    pub values: Vec<SortValue>,
+    pub customer_id: Uuid,
+}
+
+impl Position {
+    pub fn of(customer: &Customer, sort: &[SortKey]) -> Self {
+        Self {
+            values: sort.iter().map(|key| key.field.value_of(customer)).collect(),
+            customer_id: customer.customer_id,
+        }
+    }
+}
+
+/// Orders two positions by `sort`, then by ascending `customer_id`.
+pub fn compare_positions(a: &Position, b: &Position, sort: &[SortKey]) -> std::cmp::Ordering {
+    sort.iter()
+        .zip(a.values.iter().zip(&b.values))
+        .map(|(key, (a, b))| if key.descending { b.cmp(a) } else { a.cmp(b) })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or_else(|| a.customer_id.cmp(&b.customer_id))
+}
+
+/// Which customers `list` returns, in what order, and how many at a time.
+#[derive(Debug, Clone, Default)]
+pub struct CustomerListQuery {
+    pub filter: CustomerFilter,
+    /// Sort keys, most significant first. `customer_id` always breaks ties,
+    /// so the order is total and pages never overlap or skip rows.
+    pub sort: Vec<SortKey>,
+    /// Page size; `None` returns every match.
+    pub limit: Option<u32>,
+    /// Continue after this position, which must come from the same `sort`.
+    pub after: Option<Position>,
+    /// Also count every match, ignoring `limit` and `after`.
+    pub with_total: bool,
+}
+
+#[derive(Debug)]
+pub struct CustomerPage {
+    pub customers: Vec<Customer>,
+    /// Where the next page starts, if there is one.
+    pub next_after: Option<Position>,
+    pub total: Option<i64>,
+}
+
+impl CustomerPage {
+    /// Builds a page from up to `limit + 1` rows; the extra row only signals
+    /// that more follow.
+    pub fn from_rows(mut customers: Vec<Customer>, query: &CustomerListQuery, total: Option<i64>) -> Self {
+        let mut next_after = None;
+        if let Some(limit) = query.limit.map(|l| l as usize) {
+            if customers.len() > limit {
+                customers.truncate(limit);
+                next_after = customers.last().map(|c| Position::of(c, &query.sort));
+            }
+        }
+        Self { customers, next_after, total }
+    }
+}
+
+/// Who is making a change and through which operation. Stored with the
+/// revision every mutation writes.
+#[derive(Debug, Clone, Copy)]
+pub struct ChangeContext<'a> {
+    pub actor: Option<&'a str>,
+    pub action: &'a str,
+}
+
+/// How long a create's `Idempotency-Key` is remembered.
+pub const IDEMPOTENCY_KEY_TTL_HOURS: i64 = 24;
+
+/// An `Idempotency-Key` sent with a create, and the request body it came
+/// with. Keys are scoped to the change's actor.
+#[derive(Debug, Clone, Copy)]
+pub struct IdempotencyKey<'a> {
+    pub key: &'a str,
+    pub request: &'a serde_json::Value,
+}
+
+/// A create remembered under an `Idempotency-Key`.
+#[derive(Debug, Clone)]
+pub struct IdempotentCreate {
+    pub request: serde_json::Value,
+    pub customer_id: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Storage for H1B customer records. Handlers only talk to this trait, so the
+/// Postgres implementation can be swapped for the in-memory one.
+#[async_trait]
+pub trait CustomerRepository: Send + Sync {
+    /// Applied schema migration version, if the backing store has one. Also
+    /// serves as the connectivity check.
+    async fn schema_version(&self) -> RepositoryResult<Option<i64>>;
+
+    /// With `idempotency`, also claims the key for the change's actor in the
+    /// same write, failing with `IdempotencyKeyInUse` if it is already taken.
+    async fn create(
+        &self,
+        request: &CreateCompleteCustomerRequest,
+        change: ChangeContext<'_>,
+        idempotency: Option<IdempotencyKey<'_>>,
+    ) -> RepositoryResult<Customer>;
+
+    /// The create `actor` made with `key` in the last
+    /// `IDEMPOTENCY_KEY_TTL_HOURS`, if any.
+    async fn find_idempotent_create(&self, actor: &str, key: &str) -> RepositoryResult<Option<IdempotentCreate>>;
+
+    async fn find_by_id(&self, customer_id: Uuid) -> RepositoryResult<Option<Customer>>;
+
+    /// Customers whose `email` or `login_email` matches.
+    async fn find_by_email(&self, email: &str) -> RepositoryResult<Vec<Customer>>;
+
+    async fn find_by_login_email(&self, login_email: &str, h1b_status: Option<&str>) -> RepositoryResult<Vec<Customer>>;
+
+    /// One page of customers matching `query`, in `query.sort` order.
+    async fn list(&self, query: &CustomerListQuery) -> RepositoryResult<CustomerPage>;
+
+    /// Customers with a name, email, client or receipt number resembling
+    /// `query`, best match first, each with its best-matching field.
+    async fn search(&self, query: &str, limit: u32) -> RepositoryResult<Vec<SearchHit>>;
+
+    /// Returns the updated record, or `None` if the id is unknown. With
+    /// `expected_version` set, fails with `VersionMismatch` unless the stored
+    /// version still matches.
+    async fn update(&self, customer_id: Uuid, update: &CustomerUpdate, expected_version: Option<i64>, change: ChangeContext<'_>) -> RepositoryResult<Option<Customer>>;
+
+    /// Replaces one part of the record, leaving the others untouched.
+    /// Versioning works as in `update`.
+    async fn update_section(&self, customer_id: Uuid, section: CustomerSection, expected_version: Option<i64>, change: ChangeContext<'_>) -> RepositoryResult<Option<Customer>>;
+
+    /// Changes the status only if it differs, checked and written atomically.
+    /// Returns the updated record, `None` if the id is unknown, or
+    /// `StatusUnchanged` if the record already has that status.
+    async fn set_status(&self, customer_id: Uuid, h1b_status: &str, change: ChangeContext<'_>) -> RepositoryResult<Option<Customer>>;
+
+    /// Moves the case status to `to` if `CaseStatus::next` allows it from
+    /// the current one, failing with `IllegalTransition` otherwise. The check,
+    /// the change and its log entry are one write. Versioning works as in
+    /// `update`; `None` if the id is unknown.
+    async fn transition(
+        &self,
+        customer_id: Uuid,
+        to: CaseStatus,
+        reason: &str,
+        expected_version: Option<i64>,
+        change: ChangeContext<'_>,
+    ) -> RepositoryResult<Option<(Customer, CaseTransition)>>;
+
+    /// Every case status change of a customer, oldest first.
+    async fn transitions(&self, customer_id: Uuid) -> RepositoryResult<Vec<CaseTransition>>;
+
+    /// Every stored revision of a customer, oldest first. Empty if the id is
+    /// unknown.
+    async fn history(&self, customer_id: Uuid) -> RepositoryResult<Vec<CustomerRevision>>;
+
+    /// The customer as it was at `at`: the latest revision written no later
+    /// than that. `None` if the customer did not exist yet.
+    async fn find_as_of(&self, customer_id: Uuid, at: DateTime<Utc>) -> RepositoryResult<Option<Customer>>;
+}
+
+pub type SharedCustomerRepository = Arc<dyn CustomerRepository>;
@@ -0,0 +1,748 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{
+    postgres::{PgArguments, PgRow},
+    types::Json,
+    Arguments, Encode, FromRow, PgConnection, PgPool, Postgres, Type,
+};
+use uuid::Uuid;
+
+use crate::config::database::{schema_version, PoolerMode};
+use crate::case_status::CaseStatus;
+use crate::models::{
+    CaseTransition, CreateCompleteCustomerRequest, Customer, CustomerRevision, CustomerSection, CustomerUpdate,
+    PersonDetails, SearchHit, DEFAULT_H1B_STATUS,
+};
+use crate::search::{MATCH_THRESHOLD, SEARCH_FIELDS};
+
+use super::{
+    ChangeContext, CustomerFilter, CustomerListQuery, CustomerPage, CustomerRepository, IdempotencyKey, IdempotentCreate,
+    Position, RepositoryError, RepositoryResult, SortField, SortKey, SortValue, IDEMPOTENCY_KEY_TTL_HOURS,
+};
+
+/// Columns of the `global_visa_mgmt.h1bcustomer` view, which joins the
+/// person, residential_address, client_placement, lca and petition tables.
+const CUSTOMER_COLUMNS: &str = "customer_id, email, first_name, last_name, dob, sex::text, marital_status::text, phone,
+    emergency_contact_name, emergency_contact_phone, employment_start_date,
+    street_name, city, state, zip,
+    client_name, client_street_name, client_city, client_state, client_zip,
+    lca_title, lca_salary, lca_code, receipt_number, h1b_start_date, h1b_end_date, login_email, h1b_status::text,
+    case_status::text, version";
+
+/// Unique indexes from `0005_unique_email_receipt.sql`: index name, API
+/// field, and how to find the customer already holding a value.
+const UNIQUE_FIELDS: &[(&str, &str, &str)] = &[
+    ("person_email_key", "email", "SELECT customer_id FROM global_visa_mgmt.person WHERE lower(email) = lower($1)"),
+    ("petition_receipt_number_key", "receipt_number", "SELECT customer_id FROM global_visa_mgmt.petition WHERE receipt_number = $1"),
+];
+
+/// `CustomerRepository` backed by the normalized `global_visa_mgmt` tables.
+/// Reads go through the `h1bcustomer` view; writes touch each part's table.
+/// Every value is sent as a bound parameter.
+pub struct PgCustomerRepository {
+    pool: PgPool,
+    /// False behind a transaction pooler, see `PoolerMode`.
+    persistent: bool,
+    replica: Option<ReadReplica>,
+}
+
+struct ReadReplica {
+    pool: PgPool,
+    persistent: bool,
+}
+
+/// Errors meaning the replica could not be reached, as opposed to the query
+/// itself failing.
+fn replica_unavailable(e: &sqlx::Error) -> bool {
+    matches!(
+        e,
+        sqlx::Error::Io(_) | sqlx::Error::Tls(_) | sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed | sqlx::Error::WorkerCrashed
+    )
+}
+
+/// A WHERE clause assembled from predicates whose values are all sent as
+/// bound parameters.
+#[derive(Default, Clone)]
+struct Conditions {
+    predicates: Vec<String>,
+    args: PgArguments,
+}
+
+impl Conditions {
+    /// Binds `value` and returns its placeholder (`$n`).
+    fn bind<T>(&mut self, value: T) -> RepositoryResult<String>
+    where
+        T: for<'q> Encode<'q, Postgres> + Type<Postgres> + Send + 'static,
+    {
+        self.args.add(value).map_err(sqlx::Error::Encode)?;
+        Ok(format!("${}", self.args.len()))
+    }
+
+    /// Adds `predicate`, with `{}` standing for the bound `value`.
+    fn push<T>(&mut self, predicate: &str, value: T) -> RepositoryResult<()>
+    where
+        T: for<'q> Encode<'q, Postgres> + Type<Postgres> + Send + 'static,
+    {
+        let placeholder = self.bind(value)?;
+        self.predicates.push(predicate.replace("{}", &placeholder));
+        Ok(())
+    }
+
+    fn from_filter(filter: &CustomerFilter) -> RepositoryResult<Self> {
+        let mut conditions = Self::default();
+        if let Some(h1b_status) = &filter.h1b_status {
+            conditions.push("h1b_status::text = {}", h1b_status.clone())?;
+        }
+        if let Some(case_status) = filter.case_status {
+            conditions.push("case_status::text = {}", case_status.as_str())?;
+        }
+        if let Some(email) = &filter.email {
+            conditions.push("{} IN (email, login_email)", email.clone())?;
+        }
+        if let Some(login_email) = &filter.login_email {
+            conditions.push("login_email = {}", login_email.clone())?;
+        }
+        if let Some(state) = &filter.state {
+            conditions.push("lower(state) = lower({})", state.clone())?;
+        }
+        if let Some(client_name) = &filter.client_name {
+            conditions.push("client_name ILIKE {}", like_pattern(client_name))?;
+        }
+        if let Some(lca_code) = &filter.lca_code {
+            conditions.push("lca_code = {}", lca_code.clone())?;
+        }
+        if let Some(lca_title) = &filter.lca_title {
+            conditions.push("lca_title ILIKE {}", like_pattern(lca_title))?;
+        }
+        for (column, range) in [
+            ("employment_start_date", filter.employment_start_date),
+            ("h1b_end_date", filter.h1b_end_date),
+        ] {
+            if let Some(from) = range.from {
+                conditions.push(&format!("{} >= {{}}", column), from)?;
+            }
+            if let Some(to) = range.to {
+                conditions.push(&format!("{} <= {{}}", column), to)?;
+            }
+        }
+        Ok(conditions)
+    }
+
+    fn bind_sort_value(&mut self, value: &SortValue) -> RepositoryResult<String> {
+        match value {
+            SortValue::Text(v) => self.bind(v.clone()),
+            SortValue::Date(v) => self.bind(*v),
+            SortValue::Decimal(v) => self.bind(*v),
+        }
+    }
+
+    /// Keyset condition for rows after `after` in `sort` order (ties broken
+    /// by `customer_id`): `k1 > v1 OR (k1 = v1 AND k2 > v2) OR ...`, with `<`
+    /// for descending keys.
+    fn push_after(&mut self, after: &Position, sort: &[SortKey]) -> RepositoryResult<()> {
+        let mut alternatives = Vec::new();
+        let mut equal_prefix: Vec<String> = Vec::new();
+        for (key, value) in sort.iter().zip(&after.values) {
+            let column = sort_column(key.field);
+            let placeholder = self.bind_sort_value(value)?;
+            let op = if key.descending { "<" } else { ">" };
+            let mut alternative = equal_prefix.clone();
+            alternative.push(format!("{} {} {}", column, op, placeholder));
+            alternatives.push(alternative.join(" AND "));
+            equal_prefix.push(format!("{} = {}", column, placeholder));
+        }
+        let placeholder = self.bind(after.customer_id)?;
+        equal_prefix.push(format!("customer_id > {}", placeholder));
+        alternatives.push(equal_prefix.join(" AND "));
+
+        let alternatives: Vec<String> = alternatives.into_iter().map(|a| format!("({})", a)).collect();
+        self.predicates.push(format!("({})", alternatives.join(" OR ")));
+        Ok(())
+    }
+
+    fn where_clause(&self) -> String {
+        if self.predicates.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", self.predicates.join(" AND "))
+        }
+    }
+}
+
+/// Runs `sql` with `settings` in effect. Settings are scoped to a
+/// transaction (`set_config(.., true)`) so they never leak to other users of
+/// the connection, including behind a transaction pooler.
+async fn fetch_all_with<T>(
+    pool: &PgPool,
+    persistent: bool,
+    settings: &[(&str, String)],
+    sql: &str,
+    args: PgArguments,
+) -> Result<Vec<T>, sqlx::Error>
+where
+    T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+{
+    if settings.is_empty() {
+        return sqlx::query_as_with(sql, args).persistent(persistent).fetch_all(pool).await;
+    }
+    let mut tx = pool.begin().await?;
+    for (name, value) in settings {
+        sqlx::query("SELECT set_config($1, $2, true)")
+            .bind(name)
+            .bind(value)
+            .persistent(persistent)
+            .execute(&mut *tx)
+            .await?;
+    }
+    let rows = sqlx::query_as_with(sql, args).persistent(persistent).fetch_all(&mut *tx).await?;
+    tx.commit().await?;
+    Ok(rows)
+}
+
+/// Table holding each of `SEARCH_FIELDS`; each column has a trigram index.
+fn search_table(field: &str) -> &'static str {
+    match field {
+        "client_name" => "global_visa_mgmt.client_placement",
+        "receipt_number" => "global_visa_mgmt.petition",
+        _ => "global_visa_mgmt.person",
+    }
+}
+
+/// SQL for a sort column. The status enum sorts by its text, as in the
+/// in-memory store.
+fn sort_column(field: SortField) -> &'static str {
+    if field.name == "h1b_status" {
+        "h1b_status::text"
+    } else {
+        field.name
+    }
+}
+
+/// `%needle%` for ILIKE, with the needle's own wildcards escaped.
+fn like_pattern(needle: &str) -> String {
+    let escaped = needle.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+impl PgCustomerRepository {
+    pub fn new(pool: PgPool, pooler_mode: PoolerMode) -> Self {
+        Self { pool, persistent: pooler_mode.persistent_statements(), replica: None }
+    }
+
+    /// Sends list and lookup queries to `pool`. Writes, and reads that must
+    /// see them (the re-read after a write, history), stay on the primary.
+    pub fn with_replica(mut self, pool: PgPool, pooler_mode: PoolerMode) -> Self {
+        self.replica = Some(ReadReplica { pool, persistent: pooler_mode.persistent_statements() });
+        self
+    }
+
+    /// Runs a read query on the replica if there is one, falling back to the
+    /// primary when the replica is unreachable.
+    async fn read_rows<T>(&self, sql: &str, args: PgArguments) -> RepositoryResult<Vec<T>>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        self.read_rows_with(&[], sql, args).await
+    }
+
+    /// `read_rows` with server settings applied for this query only.
+    async fn read_rows_with<T>(&self, settings: &[(&str, String)], sql: &str, args: PgArguments) -> RepositoryResult<Vec<T>>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        if let Some(replica) = &self.replica {
+            match fetch_all_with(&replica.pool, replica.persistent, settings, sql, args.clone()).await {
+                Err(e) if replica_unavailable(&e) => tracing::warn!("Read replica unavailable, using primary: {}", e),
+                result => return Ok(result?),
+            }
+        }
+        Ok(fetch_all_with(&self.pool, self.persistent, settings, sql, args).await?)
+    }
+
+    async fn fetch_customer(&self, conn: &mut PgConnection, customer_id: Uuid) -> RepositoryResult<Option<Customer>> {
+        let sql = format!("SELECT {} FROM global_visa_mgmt.h1bcustomer WHERE customer_id = $1", CUSTOMER_COLUMNS);
+        let customer = sqlx::query_as::<_, Customer>(&sql)
+            .bind(customer_id)
+            .persistent(self.persistent)
+            .fetch_optional(conn)
+            .await?;
+        Ok(customer)
+    }
+
+    async fn insert_person(&self, conn: &mut PgConnection, person: &PersonDetails, h1b_status: &str) -> RepositoryResult<Uuid> {
+        let customer_id = sqlx::query_scalar::<_, Uuid>("INSERT INTO global_visa_mgmt.person (
+                email, login_email, first_name, last_name, dob, sex, marital_status, phone,
+                emergency_contact_name, emergency_contact_phone, employment_start_date, h1b_status
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6::global_visa_mgmt.sex_enum, $7::global_visa_mgmt.marital_status_enum, $8,
+                $9, $10, $11, $12::global_visa_mgmt.h1b_status_enum
+            ) RETURNING customer_id")
+            .bind(&person.email)
+            .bind(&person.login_email)
+            .bind(&person.first_name)
+            .bind(&person.last_name)
+            .bind(person.dob)
+            .bind(&person.sex)
+            .bind(&person.marital_status)
+            .bind(&person.phone)
+            .bind(&person.emergency_contact_name)
+            .bind(&person.emergency_contact_phone)
+            .bind(person.employment_start_date)
+            .bind(h1b_status)
+            .persistent(self.persistent)
+            .fetch_one(conn)
+            .await;
+        match customer_id {
+            Ok(customer_id) => Ok(customer_id),
+            Err(e) => Err(self.duplicate_or(e, &person.email).await),
+        }
+    }
+
+    /// Turns a violation of one of the `UNIQUE_FIELDS` indexes into
+    /// `Duplicate`, naming the customer that already holds `value`. Other
+    /// errors pass through unchanged.
+    async fn duplicate_or(&self, e: sqlx::Error, value: &str) -> RepositoryError {
+        let constraint = match e.as_database_error() {
+            Some(db) if db.is_unique_violation() => db.constraint(),
+            _ => None,
+        };
+        let Some((_, field, sql)) = UNIQUE_FIELDS.iter().find(|(name, ..)| Some(*name) == constraint) else {
+            return e.into();
+        };
+        // The failed statement aborted the caller's transaction, so look on the pool
+        let existing = sqlx::query_scalar::<_, Uuid>(sql)
+            .bind(value)
+            .persistent(self.persistent)
+            .fetch_optional(&self.pool)
+            .await;
+        match existing {
+            Ok(Some(customer_id)) => RepositoryError::Duplicate { field, customer_id },
+            Ok(None) => e.into(),
+            Err(lookup) => lookup.into(),
+        }
+    }
+
+    /// Increments the person's version, locking the row for the rest of the
+    /// transaction. Returns false if the customer is unknown and
+    /// `VersionMismatch` if `expected_version` is stale.
+    async fn bump_version(&self, conn: &mut PgConnection, customer_id: Uuid, expected_version: Option<i64>) -> RepositoryResult<bool> {
+        let bumped = sqlx::query_scalar::<_, i64>("UPDATE global_visa_mgmt.person SET version = version + 1
+            WHERE customer_id = $1 AND ($2::bigint IS NULL OR version = $2)
+            RETURNING version")
+            .bind(customer_id)
+            .bind(expected_version)
+            .persistent(self.persistent)
+            .fetch_optional(&mut *conn)
+            .await?;
+        if bumped.is_some() {
+            return Ok(true);
+        }
+
+        let current_version = sqlx::query_scalar::<_, i64>("SELECT version FROM global_visa_mgmt.person WHERE customer_id = $1")
+            .bind(customer_id)
+            .persistent(self.persistent)
+            .fetch_optional(conn)
+            .await?;
+        match current_version {
+            Some(current_version) => Err(RepositoryError::VersionMismatch { current_version }),
+            None => Ok(false),
+        }
+    }
+
+    /// Stores the freshly written state as a revision. Runs inside the
+    /// mutation's transaction so history and data cannot drift apart.
+    async fn record_revision(&self, conn: &mut PgConnection, customer: &Customer, change: ChangeContext<'_>) -> RepositoryResult<()> {
+        sqlx::query("INSERT INTO global_visa_mgmt.customer_revision (customer_id, version, actor, action, snapshot)
+            VALUES ($1, $2, $3, $4, $5)")
+            .bind(customer.customer_id)
+            .bind(customer.version)
+            .bind(change.actor)
+            .bind(change.action)
+            .bind(Json(customer))
+            .persistent(self.persistent)
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Takes `idempotency.key` for `actor`, replacing an expired claim. Fails
+    /// with `IdempotencyKeyInUse` if a live one exists; a claim still being
+    /// written by another transaction is waited for.
+    async fn claim_idempotency_key(&self, conn: &mut PgConnection, actor: &str, idempotency: IdempotencyKey<'_>) -> RepositoryResult<()> {
+        let claimed = sqlx::query("INSERT INTO global_visa_mgmt.idempotency_key (actor, key, request)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (actor, key) DO UPDATE SET request = EXCLUDED.request, customer_id = NULL, created_at = now()
+            WHERE idempotency_key.created_at <= now() - make_interval(hours => $4)")
+            .bind(actor)
+            .bind(idempotency.key)
+            .bind(idempotency.request)
+            .bind(IDEMPOTENCY_KEY_TTL_HOURS as i32)
+            .persistent(self.persistent)
+            .execute(conn)
+            .await?;
+        if claimed.rows_affected() == 0 {
+            return Err(RepositoryError::IdempotencyKeyInUse);
+        }
+        Ok(())
+    }
+
+    /// Re-reads the record after a write, records the revision and commits.
+    async fn finish_change(
+        &self,
+        mut tx: sqlx::Transaction<'_, sqlx::Postgres>,
+        customer_id: Uuid,
+        change: ChangeContext<'_>,
+    ) -> RepositoryResult<Option<Customer>> {
+        let Some(customer) = self.fetch_customer(&mut tx, customer_id).await? else {
+            return Ok(None);
+        };
+        self.record_revision(&mut tx, &customer, change).await?;
+        tx.commit().await?;
+        Ok(Some(customer))
+    }
+
+    /// Writes one section. The person row must already exist; the other
+    /// parts are upserted. Returns false if the customer is unknown.
+    async fn write_section(&self, conn: &mut PgConnection, customer_id: Uuid, section: &CustomerSection) -> RepositoryResult<bool> {
+        let query = match section {
+            CustomerSection::Person(p) => sqlx::query("UPDATE global_visa_mgmt.person SET
+                    email = $2, login_email = $3, first_name = $4, last_name = $5, dob = $6,
+                    sex = $7::global_visa_mgmt.sex_enum, marital_status = $8::global_visa_mgmt.marital_status_enum,
+                    phone = $9, emergency_contact_name = $10, emergency_contact_phone = $11, employment_start_date = $12
+                WHERE customer_id = $1")
+                .bind(customer_id)
+                .bind(&p.email)
+                .bind(&p.login_email)
+                .bind(&p.first_name)
+                .bind(&p.last_name)
+                .bind(p.dob)
+                .bind(&p.sex)
+                .bind(&p.marital_status)
+                .bind(&p.phone)
+                .bind(&p.emergency_contact_name)
+                .bind(&p.emergency_contact_phone)
+                .bind(p.employment_start_date),
+            CustomerSection::Address(a) => sqlx::query("INSERT INTO global_visa_mgmt.residential_address (customer_id, street_name, city, state, zip)
+                SELECT $1, $2, $3, $4, $5 WHERE EXISTS (SELECT 1 FROM global_visa_mgmt.person WHERE customer_id = $1)
+                ON CONFLICT (customer_id) DO UPDATE SET
+                    street_name = EXCLUDED.street_name, city = EXCLUDED.city, state = EXCLUDED.state, zip = EXCLUDED.zip")
+                .bind(customer_id)
+                .bind(&a.street_name)
+                .bind(&a.city)
+                .bind(&a.state)
+                .bind(&a.zip),
+            CustomerSection::Placement(c) => sqlx::query("INSERT INTO global_visa_mgmt.client_placement (
+                    customer_id, client_name, client_street_name, client_city, client_state, client_zip
+                )
+                SELECT $1, $2, $3, $4, $5, $6 WHERE EXISTS (SELECT 1 FROM global_visa_mgmt.person WHERE customer_id = $1)
+                ON CONFLICT (customer_id) DO UPDATE SET
+                    client_name = EXCLUDED.client_name, client_street_name = EXCLUDED.client_street_name,
+                    client_city = EXCLUDED.client_city, client_state = EXCLUDED.client_state, client_zip = EXCLUDED.client_zip")
+                .bind(customer_id)
+                .bind(&c.client_name)
+                .bind(&c.client_street_name)
+                .bind(&c.client_city)
+                .bind(&c.client_state)
+                .bind(&c.client_zip),
+            CustomerSection::Lca(l) => sqlx::query("INSERT INTO global_visa_mgmt.lca (customer_id, lca_title, lca_salary, lca_code)
+                SELECT $1, $2, $3, $4 WHERE EXISTS (SELECT 1 FROM global_visa_mgmt.person WHERE customer_id = $1)
+                ON CONFLICT (customer_id) DO UPDATE SET
+                    lca_title = EXCLUDED.lca_title, lca_salary = EXCLUDED.lca_salary, lca_code = EXCLUDED.lca_code")
+                .bind(customer_id)
+                .bind(&l.lca_title)
+                .bind(l.lca_salary)
+                .bind(&l.lca_code),
+            CustomerSection::Petition(t) => sqlx::query("INSERT INTO global_visa_mgmt.petition (customer_id, receipt_number, h1b_start_date, h1b_end_date)
+                SELECT $1, $2, $3, $4 WHERE EXISTS (SELECT 1 FROM global_visa_mgmt.person WHERE customer_id = $1)
+                ON CONFLICT (customer_id) DO UPDATE SET
+                    receipt_number = EXCLUDED.receipt_number, h1b_start_date = EXCLUDED.h1b_start_date,
+                    h1b_end_date = EXCLUDED.h1b_end_date")
+                .bind(customer_id)
+                .bind(&t.receipt_number)
+                .bind(t.h1b_start_date)
+                .bind(t.h1b_end_date),
+        };
+
+        match query.persistent(self.persistent).execute(conn).await {
+            Ok(result) => Ok(result.rows_affected() > 0),
+            Err(e) => {
+                let unique_value = match section {
+                    CustomerSection::Person(p) => p.email.as_str(),
+                    CustomerSection::Petition(t) => t.receipt_number.as_str(),
+                    _ => "",
+                };
+                Err(self.duplicate_or(e, unique_value).await)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl CustomerRepository for PgCustomerRepository {
+    async fn schema_version(&self) -> RepositoryResult<Option<i64>> {
+        Ok(schema_version(&self.pool).await?)
+    }
+
+    async fn create(
+        &self,
+        request: &CreateCompleteCustomerRequest,
+        change: ChangeContext<'_>,
+        idempotency: Option<IdempotencyKey<'_>>,
+    ) -> RepositoryResult<Customer> {
+        let h1b_status = request.h1b_status.as_deref().unwrap_or(DEFAULT_H1B_STATUS);
+        let mut sections = CustomerUpdate::from(request).into_sections().into_iter();
+        let Some(CustomerSection::Person(person)) = sections.next() else {
+            unreachable!("into_sections starts with the person section");
+        };
+
+        let mut tx = self.pool.begin().await?;
+        // Claimed first, so a concurrent retry waits on the key rather than
+        // failing on the email it is about to insert
+        let actor = change.actor.unwrap_or_default();
+        if let Some(idempotency) = idempotency {
+            self.claim_idempotency_key(&mut tx, actor, idempotency).await?;
+        }
+        let customer_id = self.insert_person(&mut tx, &person, h1b_status).await?;
+        for section in sections {
+            self.write_section(&mut tx, customer_id, &section).await?;
+        }
+        if let Some(idempotency) = idempotency {
+            sqlx::query("UPDATE global_visa_mgmt.idempotency_key SET customer_id = $3 WHERE actor = $1 AND key = $2")
+                .bind(actor)
+                .bind(idempotency.key)
+                .bind(customer_id)
+                .persistent(self.persistent)
+                .execute(&mut *tx)
+                .await?;
+        }
+        let customer = self.finish_change(tx, customer_id, change).await?;
+        Ok(customer.ok_or(sqlx::Error::RowNotFound)?)
+    }
+
+    async fn find_idempotent_create(&self, actor: &str, key: &str) -> RepositoryResult<Option<IdempotentCreate>> {
+        let row = sqlx::query_as::<_, (serde_json::Value, Uuid, DateTime<Utc>)>("SELECT request, customer_id, created_at
+            FROM global_visa_mgmt.idempotency_key
+            WHERE actor = $1 AND key = $2 AND customer_id IS NOT NULL AND created_at > now() - make_interval(hours => $3)")
+            .bind(actor)
+            .bind(key)
+            .bind(IDEMPOTENCY_KEY_TTL_HOURS as i32)
+            .persistent(self.persistent)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|(request, customer_id, created_at)| IdempotentCreate { request, customer_id, created_at }))
+    }
+
+    async fn find_by_id(&self, customer_id: Uuid) -> RepositoryResult<Option<Customer>> {
+        let mut conn = self.pool.acquire().await?;
+        self.fetch_customer(&mut conn, customer_id).await
+    }
+
+    async fn find_by_email(&self, email: &str) -> RepositoryResult<Vec<Customer>> {
+        let sql = format!("SELECT {} FROM global_visa_mgmt.h1bcustomer WHERE (email = $1 OR login_email = $1)", CUSTOMER_COLUMNS);
+        let mut args = PgArguments::default();
+        args.add(email).map_err(sqlx::Error::Encode)?;
+        self.read_rows(&sql, args).await
+    }
+
+    async fn find_by_login_email(&self, login_email: &str, h1b_status: Option<&str>) -> RepositoryResult<Vec<Customer>> {
+        let sql = format!("SELECT {} FROM global_visa_mgmt.h1bcustomer
+            WHERE login_email = $1 AND ($2::text IS NULL OR h1b_status::text = $2)", CUSTOMER_COLUMNS);
+        let mut args = PgArguments::default();
+        args.add(login_email).map_err(sqlx::Error::Encode)?;
+        args.add(h1b_status).map_err(sqlx::Error::Encode)?;
+        self.read_rows(&sql, args).await
+    }
+
+    async fn list(&self, query: &CustomerListQuery) -> RepositoryResult<CustomerPage> {
+        let filters = Conditions::from_filter(&query.filter)?;
+
+        let total = if query.with_total {
+            let sql = format!("SELECT COUNT(*) FROM global_visa_mgmt.h1bcustomer{}", filters.where_clause());
+            let counts = self.read_rows::<(i64,)>(&sql, filters.args.clone()).await?;
+            counts.first().map(|(count,)| *count)
+        } else {
+            None
+        };
+
+        let mut page = filters;
+        if let Some(after) = &query.after {
+            page.push_after(after, &query.sort)?;
+        }
+        let order_by: Vec<String> = query.sort
+            .iter()
+            .map(|key| format!("{} {}", sort_column(key.field), if key.descending { "DESC" } else { "ASC" }))
+            .chain(["customer_id ASC".to_string()])
+            .collect();
+        let mut sql = format!("SELECT {} FROM global_visa_mgmt.h1bcustomer{} ORDER BY {}",
+            CUSTOMER_COLUMNS, page.where_clause(), order_by.join(", "));
+        if let Some(limit) = query.limit {
+            // One extra row tells whether another page follows
+            let placeholder = page.bind(i64::from(limit) + 1)?;
+            sql.push_str(&format!(" LIMIT {}", placeholder));
+        }
+
+        let customers = self.read_rows::<Customer>(&sql, page.args).await?;
+        Ok(CustomerPage::from_rows(customers, query, total))
+    }
+
+    async fn search(&self, query: &str, limit: u32) -> RepositoryResult<Vec<SearchHit>> {
+        // One indexed lookup per field, then each customer's best field.
+        // `<%` is pg_trgm's fuzzy word match, at `MATCH_THRESHOLD` rather than
+        // the stricter server default; ILIKE adds substrings too short to
+        // clear it.
+        let matches = SEARCH_FIELDS
+            .iter()
+            .map(|field| format!("SELECT customer_id, '{field}' AS matched_field, {field} AS matched_value,
+                    word_similarity($1, {field}) AS score
+                FROM {table} WHERE $1 <% {field} OR {field} ILIKE $2", field = field, table = search_table(field)))
+            .collect::<Vec<_>>()
+            .join(" UNION ALL ");
+        let sql = format!("WITH matches AS ({}),
+            best AS (SELECT DISTINCT ON (customer_id) * FROM matches ORDER BY customer_id, score DESC)
+            SELECT {}, best.matched_field, best.matched_value, best.score
+            FROM best JOIN global_visa_mgmt.h1bcustomer USING (customer_id)
+            ORDER BY best.score DESC, customer_id
+            LIMIT $3", matches, CUSTOMER_COLUMNS);
+
+        let mut args = PgArguments::default();
+        args.add(query).map_err(sqlx::Error::Encode)?;
+        args.add(like_pattern(query)).map_err(sqlx::Error::Encode)?;
+        args.add(i64::from(limit)).map_err(sqlx::Error::Encode)?;
+        let settings = [("pg_trgm.word_similarity_threshold", MATCH_THRESHOLD.to_string())];
+        self.read_rows_with(&settings, &sql, args).await
+    }
+
+    async fn update(&self, customer_id: Uuid, update: &CustomerUpdate, expected_version: Option<i64>, change: ChangeContext<'_>) -> RepositoryResult<Option<Customer>> {
+        let mut tx = self.pool.begin().await?;
+        if !self.bump_version(&mut tx, customer_id, expected_version).await? {
+            return Ok(None);
+        }
+        for section in update.clone().into_sections() {
+            if !self.write_section(&mut tx, customer_id, &section).await? {
+                return Ok(None);
+            }
+        }
+        self.finish_change(tx, customer_id, change).await
+    }
+
+    async fn update_section(&self, customer_id: Uuid, section: CustomerSection, expected_version: Option<i64>, change: ChangeContext<'_>) -> RepositoryResult<Option<Customer>> {
+        let mut tx = self.pool.begin().await?;
+        if !self.bump_version(&mut tx, customer_id, expected_version).await? {
+            return Ok(None);
+        }
+        if !self.write_section(&mut tx, customer_id, &section).await? {
+            return Ok(None);
+        }
+        self.finish_change(tx, customer_id, change).await
+    }
+
+    async fn set_status(&self, customer_id: Uuid, h1b_status: &str, change: ChangeContext<'_>) -> RepositoryResult<Option<Customer>> {
+        let mut tx = self.pool.begin().await?;
+        // The status check is part of the UPDATE, so of two concurrent
+        // requests only one can match; the other re-checks after the first
+        // commits and finds nothing to change.
+        let result = sqlx::query("UPDATE global_visa_mgmt.person
+            SET h1b_status = $2::global_visa_mgmt.h1b_status_enum, version = version + 1
+            WHERE customer_id = $1 AND h1b_status <> $2::global_visa_mgmt.h1b_status_enum")
+            .bind(customer_id)
+            .bind(h1b_status)
+            .persistent(self.persistent)
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            let current = sqlx::query_scalar::<_, String>("SELECT h1b_status::text FROM global_visa_mgmt.person WHERE customer_id = $1")
+                .bind(customer_id)
+                .persistent(self.persistent)
+                .fetch_optional(&mut *tx)
+                .await?;
+            return match current {
+                Some(h1b_status) => Err(RepositoryError::StatusUnchanged { h1b_status }),
+                None => Ok(None),
+            };
+        }
+        self.finish_change(tx, customer_id, change).await
+    }
+
+    async fn transition(
+        &self,
+        customer_id: Uuid,
+        to: CaseStatus,
+        reason: &str,
+        expected_version: Option<i64>,
+        change: ChangeContext<'_>,
+    ) -> RepositoryResult<Option<(Customer, CaseTransition)>> {
+        let mut tx = self.pool.begin().await?;
+        // Locks the row, so the status checked is the one replaced.
+        let current = sqlx::query_as::<_, (String, i64)>("SELECT case_status::text, version
+            FROM global_visa_mgmt.person WHERE customer_id = $1 FOR UPDATE")
+            .bind(customer_id)
+            .persistent(self.persistent)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let Some((from, current_version)) = current else {
+            return Ok(None);
+        };
+        if expected_version.is_some_and(|expected| expected != current_version) {
+            return Err(RepositoryError::VersionMismatch { current_version });
+        }
+        let from = CaseStatus::try_from(from).map_err(|e| RepositoryError::Database(sqlx::Error::Decode(e.into())))?;
+        if !from.can_move_to(to) {
+            return Err(RepositoryError::IllegalTransition { from, to });
+        }
+
+        sqlx::query("UPDATE global_visa_mgmt.person
+            SET case_status = $2::global_visa_mgmt.case_status_enum, version = version + 1
+            WHERE customer_id = $1")
+            .bind(customer_id)
+            .bind(to.as_str())
+            .persistent(self.persistent)
+            .execute(&mut *tx)
+            .await?;
+        let transition = sqlx::query_as::<_, CaseTransition>("INSERT INTO global_visa_mgmt.case_transition (
+                customer_id, version, from_status, to_status, reason, actor
+            ) VALUES (
+                $1, $2, $3::global_visa_mgmt.case_status_enum, $4::global_visa_mgmt.case_status_enum, $5, $6
+            ) RETURNING version, from_status::text, to_status::text, reason, actor, transitioned_at")
+            .bind(customer_id)
+            .bind(current_version + 1)
+            .bind(from.as_str())
+            .bind(to.as_str())
+            .bind(reason)
+            .bind(change.actor)
+            .persistent(self.persistent)
+            .fetch_one(&mut *tx)
+            .await?;
+        Ok(self.finish_change(tx, customer_id, change).await?.map(|customer| (customer, transition)))
+    }
+
+    async fn transitions(&self, customer_id: Uuid) -> RepositoryResult<Vec<CaseTransition>> {
+        let transitions = sqlx::query_as::<_, CaseTransition>("SELECT version, from_status::text, to_status::text, reason, actor, transitioned_at
+            FROM global_visa_mgmt.case_transition WHERE customer_id = $1 ORDER BY version")
+            .bind(customer_id)
+            .persistent(self.persistent)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(transitions)
+    }
+
+    async fn history(&self, customer_id: Uuid) -> RepositoryResult<Vec<CustomerRevision>> {
+        let revisions = sqlx::query_as::<_, CustomerRevision>("SELECT version, changed_at, actor, action, snapshot
+            FROM global_visa_mgmt.customer_revision WHERE customer_id = $1 ORDER BY version")
+            .bind(customer_id)
+            .persistent(self.persistent)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(revisions)
+    }
+
+    async fn find_as_of(&self, customer_id: Uuid, at: DateTime<Utc>) -> RepositoryResult<Option<Customer>> {
+        let snapshot = sqlx::query_scalar::<_, Json<Customer>>("SELECT snapshot FROM global_visa_mgmt.customer_revision
+            WHERE customer_id = $1 AND changed_at <= $2 ORDER BY version DESC LIMIT 1")
+            .bind(customer_id)
+            .bind(at)
+            .persistent(self.persistent)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(snapshot.map(|Json(customer)| customer))
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashSet;
+
+/// Customer fields `GET /customers/search` looks at.
+pub const SEARCH_FIELDS: [&str; 6] = ["first_name", "last_name", "email", "login_email", "client_name", "receipt_number"];
+
+/// Minimum score for a fuzzy match. Lower than pg_trgm's default word
+/// similarity threshold (0.6) so one or two typos in a short name still match.
+pub const MATCH_THRESHOLD: f32 = 0.3;
+
+/// Weakest similarity at which a word is still highlighted.
+const HIGHLIGHT_THRESHOLD: f32 = 0.3;
+
+fn lower(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// Alphanumeric runs of `text` as char offset ranges, the way pg_trgm splits
+/// words.
+fn words(text: &str) -> Vec<(usize, usize)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in text.chars().enumerate() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                words.push((s, i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        words.push((s, text.chars().count()));
+    }
+    words
+}
+
+/// Lower-cased trigrams of every word in order, padded with two leading and
+/// one trailing blank like pg_trgm.
+fn trigram_list(text: &str) -> Vec<[char; 3]> {
+    let chars: Vec<char> = text.chars().map(lower).collect();
+    let mut list = Vec::new();
+    for (start, end) in words(text) {
+        let padded: Vec<char> = [' ', ' '].into_iter().chain(chars[start..end].iter().copied()).chain([' ']).collect();
+        list.extend(padded.windows(3).map(|w| [w[0], w[1], w[2]]));
+    }
+    list
+}
+
+fn trigrams(text: &str) -> HashSet<[char; 3]> {
+    trigram_list(text).into_iter().collect()
+}
+
+/// pg_trgm `similarity`: shared trigrams over all distinct trigrams.
+pub fn similarity(a: &str, b: &str) -> f32 {
+    let (a, b) = (trigrams(a), trigrams(b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f32 / union as f32
+}
+
+/// pg_trgm `word_similarity(query, value)`: the greatest similarity between
+/// the query's trigrams and any continuous extent of the value's trigrams.
+pub fn word_similarity(query: &str, value: &str) -> f32 {
+    let query = trigrams(query);
+    let value = trigram_list(value);
+    let mut best = 0.0f32;
+    for start in 0..value.len() {
+        // An extent opening on an unshared trigram is beaten by the one after it
+        if !query.contains(&value[start]) {
+            continue;
+        }
+        let mut extent = HashSet::new();
+        let (mut shared, mut unshared) = (0, 0);
+        for trigram in &value[start..] {
+            if extent.insert(*trigram) {
+                if query.contains(trigram) {
+                    shared += 1;
+                } else {
+                    unshared += 1;
+                }
+            }
+            best = best.max(shared as f32 / (query.len() + unshared) as f32);
+        }
+    }
+    best
+}
+
+/// Whether `needle` occurs in `haystack`, ignoring case, as ILIKE
+/// `%needle%` matches.
+pub fn contains(haystack: &str, needle: &str) -> bool {
+    !find_all(needle, haystack).is_empty()
+}
+
+/// Case-insensitive occurrences of `needle` in `haystack`, as char ranges.
+fn find_all(needle: &str, haystack: &str) -> Vec<(usize, usize)> {
+    let needle: Vec<char> = needle.chars().map(lower).collect();
+    let haystack: Vec<char> = haystack.chars().map(lower).collect();
+    if needle.is_empty() || needle.len() > haystack.len() {
+        return Vec::new();
+    }
+    haystack
+        .windows(needle.len())
+        .enumerate()
+        .filter(|(_, window)| *window == needle.as_slice())
+        .map(|(i, _)| (i, i + needle.len()))
+        .collect()
+}
+
+/// Char ranges of `value` to highlight for `query`: literal occurrences of
+/// each query term, or else the word most similar to it.
+pub fn highlight(query: &str, value: &str) -> Vec<(usize, usize)> {
+    let chars: Vec<char> = value.chars().collect();
+    let mut ranges = Vec::new();
+    for term in query.split_whitespace() {
+        let exact = find_all(term, value);
+        if !exact.is_empty() {
+            ranges.extend(exact);
+            continue;
+        }
+        let closest = words(value)
+            .into_iter()
+            .map(|(start, end)| ((start, end), similarity(term, &chars[start..end].iter().collect::<String>())))
+            .filter(|(_, score)| *score >= HIGHLIGHT_THRESHOLD)
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        ranges.extend(closest.map(|(range, _)| range));
+    }
+
+    ranges.sort();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn scores_like_pg_trgm() {
+        // The examples in the pg_trgm documentation
+        assert!(close(similarity("word", "two words"), 4.0 / 11.0));
+        assert!(close(word_similarity("word", "two words"), 0.8));
+        assert!(close(word_similarity("word", "Word"), 1.0));
+        // A substring inside a longer word shares few trigrams with it
+        assert!(close(word_similarity("c24", "EAC2400000001"), 0.25));
+        assert_eq!(word_similarity("zq", "EAC2400000001"), 0.0);
+    }
+
+    #[test]
+    fn contains_ignores_case() {
+        assert!(contains("EAC2400000001", "c24"));
+        assert!(contains("O'Neil", "o'n"));
+        assert!(!contains("EAC2400000001", "c25"));
+    }
+}
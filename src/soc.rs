@@ -0,0 +1,237 @@
+use std::fmt;
+use std::sync::OnceLock;
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::search::{similarity, word_similarity, MATCH_THRESHOLD};
+
+/// The bundled taxonomy; see the header of the file for what it covers.
+const SOC_2018: &str = include_str!("../data/soc_2018.tsv");
+
+/// 2010 SOC computer occupations still found on older LCAs, and the 2018
+/// occupation that replaced each.
+const SOC_2010_SUCCESSORS: [(&str, &str); 13] = [
+    ("15-1111", "15-1221"),
+    ("15-1121", "15-1211"),
+    ("15-1122", "15-1212"),
+    ("15-1131", "15-1251"),
+    ("15-1132", "15-1252"),
+    ("15-1133", "15-1252"),
+    ("15-1134", "15-1254"),
+    ("15-1141", "15-1242"),
+    ("15-1142", "15-1244"),
+    ("15-1143", "15-1241"),
+    ("15-1151", "15-1232"),
+    ("15-1152", "15-1231"),
+    ("15-1199", "15-1299"),
+];
+
+/// Seniority and filler words that say nothing about the occupation.
+const TITLE_NOISE: [&str; 16] = [
+    "senior", "sr", "junior", "jr", "lead", "principal", "staff", "level", "i", "ii", "iii", "iv", "and", "of",
+    "the", "for",
+];
+
+/// How close a title word must be to a word of the occupation's names.
+const TITLE_MATCH_THRESHOLD: f32 = 0.5;
+
+/// A detailed occupation of the 2018 Standard Occupational Classification.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Occupation {
+    #[schema(example = "15-1252")]
+    pub code: String,
+    #[schema(example = "Software Developers")]
+    pub title: String,
+    /// Job titles commonly filed under the occupation.
+    #[schema(example = json!(["Software Engineer", "Application Developer"]))]
+    pub common_titles: Vec<String>,
+}
+
+impl Occupation {
+    fn names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.title.as_str()).chain(self.common_titles.iter().map(String::as_str))
+    }
+
+    /// Whether a job title plausibly belongs to this occupation: some word
+    /// of it, seniority aside, resembles a word of the SOC title or of a
+    /// common title.
+    pub fn fits_title(&self, title: &str) -> bool {
+        let known: Vec<String> = self.names().flat_map(words).collect();
+        words(title)
+            .filter(|word| !TITLE_NOISE.contains(&word.as_str()))
+            .any(|word| known.iter().any(|k| similarity(&word, k) >= TITLE_MATCH_THRESHOLD))
+    }
+}
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).map(str::to_lowercase)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SocCodeError {
+    /// Not `NN-NNNN`, optionally with an O*NET `.NN` suffix.
+    Malformed,
+    /// A 2010 code the 2018 SOC replaced.
+    Retired { code: String, successor: &'static str },
+    /// Well-formed, but not a detailed occupation in the taxonomy.
+    Unknown(String),
+}
+
+impl fmt::Display for SocCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SocCodeError::Malformed => {
+                write!(f, "must be a SOC occupation code like 15-1252, or an O*NET code like 15-1252.00")
+            },
+            SocCodeError::Retired { code, successor } => {
+                let title = taxonomy().find(successor).map(|o| o.title.as_str()).unwrap_or_default();
+                write!(f, "{} is a 2010 SOC code; the 2018 SOC replaced it with {} {}", code, successor, title)
+            },
+            SocCodeError::Unknown(code) => write!(f, "{} is not a 2018 SOC detailed occupation", code),
+        }
+    }
+}
+
+/// The occupations, in code order.
+#[derive(Debug)]
+pub struct SocTaxonomy {
+    occupations: Vec<Occupation>,
+}
+
+/// The bundled taxonomy, parsed on first use.
+pub fn taxonomy() -> &'static SocTaxonomy {
+    static TAXONOMY: OnceLock<SocTaxonomy> = OnceLock::new();
+    TAXONOMY.get_or_init(|| SocTaxonomy::parse(SOC_2018))
+}
+
+impl SocTaxonomy {
+    /// Tab-separated `code`, `title` and `;`-separated common titles, after
+    /// a header row; `#` lines are comments.
+    fn parse(text: &str) -> Self {
+        let mut occupations: Vec<Occupation> = text
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+            .skip(1)
+            .map(|line| {
+                let mut columns = line.split('\t');
+                let mut next = || columns.next().unwrap_or_default().trim().to_string();
+                let (code, title, common) = (next(), next(), next());
+                Occupation {
+                    code,
+                    title,
+                    common_titles: common.split(';').map(str::trim).filter(|t| !t.is_empty()).map(String::from).collect(),
+                }
+            })
+            .collect();
+        occupations.sort_by(|a, b| a.code.cmp(&b.code));
+        Self { occupations }
+    }
+
+    /// The occupation a SOC code, or the O*NET code under it, names.
+    pub fn find(&self, code: &str) -> Result<&Occupation, SocCodeError> {
+        let (soc, onet) = match code.split_once('.') {
+            Some((soc, suffix)) => (soc, Some(suffix)),
+            None => (code, None),
+        };
+        let digits = |s: &str, n: usize| s.len() == n && s.bytes().all(|b| b.is_ascii_digit());
+        let well_formed = matches!(soc.split_once('-'), Some((major, detail)) if digits(major, 2) && digits(detail, 4))
+            && onet.is_none_or(|suffix| digits(suffix, 2));
+        if !well_formed {
+            return Err(SocCodeError::Malformed);
+        }
+        if let Some((_, successor)) = SOC_2010_SUCCESSORS.iter().find(|(retired, _)| *retired == soc) {
+            return Err(SocCodeError::Retired { code: soc.to_string(), successor });
+        }
+        self.occupations
+            .binary_search_by(|o| o.code.as_str().cmp(soc))
+            .map(|i| &self.occupations[i])
+            .map_err(|_| SocCodeError::Unknown(soc.to_string()))
+    }
+
+    /// Occupations for an autocomplete box: by code prefix when `query` is
+    /// digits and dashes, otherwise by closeness of the title or a common
+    /// title, best first.
+    pub fn lookup(&self, query: &str, limit: usize) -> Vec<&Occupation> {
+        let query = query.trim();
+        if query.chars().all(|c| c.is_ascii_digit() || c == '-' || c == '.') {
+            let prefix = query.split('.').next().unwrap_or_default();
+            return self.occupations.iter().filter(|o| o.code.starts_with(prefix)).take(limit).collect();
+        }
+        let mut scored: Vec<(&Occupation, f32)> = self
+            .occupations
+            .iter()
+            .map(|o| (o, o.names().map(|name| word_similarity(query, name)).fold(0.0, f32::max)))
+            .filter(|(_, score)| *score >= MATCH_THRESHOLD)
+            .collect();
+        // Stable, so equal scores stay in code order
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.into_iter().take(limit).map(|(o, _)| o).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_plain_and_onet_codes() {
+        let plain = taxonomy().find("15-1252").unwrap();
+        assert_eq!(plain.title, "Software Developers");
+        let onet = taxonomy().find("15-1252.00").unwrap();
+        assert_eq!(onet.code, "15-1252");
+    }
+
+    #[test]
+    fn rejects_malformed_codes() {
+        for code in ["", "151252", "15-125", "15-12522", "1a-1252", "15-1252.0", "15-1252.ab", "15-1252."] {
+            assert_eq!(taxonomy().find(code).unwrap_err(), SocCodeError::Malformed, "{:?}", code);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_codes() {
+        assert_eq!(taxonomy().find("15-1200").unwrap_err(), SocCodeError::Unknown("15-1200".to_string()));
+        assert_eq!(taxonomy().find("12-3456").unwrap_err(), SocCodeError::Unknown("12-3456".to_string()));
+    }
+
+    #[test]
+    fn names_the_successor_of_retired_codes() {
+        let err = taxonomy().find("15-1132").unwrap_err();
+        assert_eq!(err, SocCodeError::Retired { code: "15-1132".to_string(), successor: "15-1252" });
+        assert!(err.to_string().contains("15-1252 Software Developers"));
+    }
+
+    #[test]
+    fn covers_every_major_group() {
+        assert_eq!(taxonomy().find("41-9031").unwrap().title, "Sales Engineers");
+        assert_eq!(taxonomy().find("53-2011.00").unwrap().code, "53-2011");
+        assert_eq!(taxonomy().find("55-3016").unwrap().title, "Infantry");
+        for code in ["41-9039", "53-2019"] {
+            assert_eq!(taxonomy().find(code).unwrap_err(), SocCodeError::Unknown(code.to_string()));
+        }
+    }
+
+    #[test]
+    fn title_fits_by_soc_title_or_common_title() {
+        let developers = taxonomy().find("15-1252").unwrap();
+        assert!(developers.fits_title("Senior Software Engineer"));
+        assert!(developers.fits_title("Application Developer II"));
+        assert!(!developers.fits_title("Registered Nurse"));
+        assert!(!developers.fits_title("Senior Lead"));
+
+        let nurses = taxonomy().find("29-1141").unwrap();
+        assert!(nurses.fits_title("Staff Nurse"));
+        assert!(!nurses.fits_title("Java Developer"));
+    }
+
+    #[test]
+    fn looks_up_by_code_prefix_and_title() {
+        let by_code = taxonomy().lookup("15-125", 10);
+        assert!(by_code.iter().all(|o| o.code.starts_with("15-125")));
+        assert_eq!(by_code.first().map(|o| o.code.as_str()), Some("15-1251"));
+
+        let by_title = taxonomy().lookup("software developer", 5);
+        assert_eq!(by_title.first().map(|o| o.code.as_str()), Some("15-1252"));
+    }
+}
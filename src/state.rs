@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use axum::extract::FromRef;
+
+use crate::config::database::{connect_replica, initialize_database, run_migrations};
+use crate::config::{AppConfig, StorageBackend};
+use crate::metrics::SharedMetrics;
+use crate::repository::{InMemoryCustomerRepository, PgCustomerRepository, SharedCustomerRepository};
+use crate::wages::{PrevailingWages, SharedWages};
+
+/// Everything handlers need, built once at startup and shared through axum
+/// `State`.
+#[derive(Clone)]
+pub struct AppState {
+    pub config: Arc<AppConfig>,
+    pub customers: SharedCustomerRepository,
+    pub metrics: SharedMetrics,
+    pub wages: SharedWages,
+}
+
+impl AppState {
+    /// Connects to the configured storage (running migrations if enabled).
+    /// Any failure here aborts startup.
+    pub async fn build(config: AppConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let customers: SharedCustomerRepository = match &config.storage {
+            StorageBackend::Postgres(database) => {
+                let pool = initialize_database(database).await?;
+                if database.migrate_on_start {
+                    match run_migrations(&pool).await? {
+                        Some(version) => tracing::info!("Database schema at migration version {}", version),
+                        None => tracing::info!("Database schema has no applied migrations"),
+                    }
+                }
+                let mut repository = PgCustomerRepository::new(pool, database.pooler_mode);
+                if let Some(replica) = &database.replica {
+                    repository = repository.with_replica(connect_replica(replica), replica.pooler_mode);
+                }
+                Arc::new(repository)
+            }
+            StorageBackend::Memory => {
+                tracing::info!("Using in-memory customer storage");
+                Arc::new(InMemoryCustomerRepository::new())
+            }
+        };
+
+        let wages = match &config.wage_data_dir {
+            Some(dir) => {
+                let wages = PrevailingWages::load(dir)?;
+                tracing::info!(
+                    "Loaded {} prevailing wages for {} areas from {}",
+                    wages.wage_count(),
+                    wages.area_count(),
+                    dir.display()
+                );
+                Some(Arc::new(wages))
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            config: Arc::new(config),
+            customers,
+            metrics: SharedMetrics::default(),
+            wages,
+        })
+    }
+}
+
+impl FromRef<AppState> for SharedCustomerRepository {
+    fn from_ref(state: &AppState) -> Self {
+        state.customers.clone()
+    }
+}
+
+impl FromRef<AppState> for SharedWages {
+    fn from_ref(state: &AppState) -> Self {
+        state.wages.clone()
+    }
+}
+
+impl FromRef<AppState> for SharedMetrics {
+    fn from_ref(state: &AppState) -> Self {
+        state.metrics.clone()
+    }
+}
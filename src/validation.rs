@@ -0,0 +1,398 @@
+use chrono::{NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::models::{
+    CaseTransitionRequest, ClientPlacement, CreateCompleteCustomerRequest, CustomerSection, CustomerUpdate, LcaDetails,
+    PersonDetails, PetitionDetails, ResidentialAddress,
+};
+use crate::receipt::ReceiptNumber;
+use crate::soc::taxonomy;
+
+/// Values of the `global_visa_mgmt` enum types, as created by
+/// `0001_create_global_visa_mgmt.sql`.
+pub const SEX_VALUES: [&str; 3] = ["Male", "Female", "Other"];
+pub const MARITAL_STATUSES: [&str; 3] = ["Single", "Married", "Divorced"];
+pub const H1B_STATUSES: [&str; 2] = ["Active", "Inactive"];
+
+/// USPS codes of the states, DC and the inhabited territories.
+const US_STATES: [&str; 56] = [
+    "AL", "AK", "AZ", "AR", "CA", "CO", "CT", "DE", "FL", "GA", "HI", "ID", "IL", "IN", "IA", "KS", "KY", "LA", "ME",
+    "MD", "MA", "MI", "MN", "MS", "MO", "MT", "NE", "NV", "NH", "NJ", "NM", "NY", "NC", "ND", "OH", "OK", "OR", "PA",
+    "RI", "SC", "SD", "TN", "TX", "UT", "VT", "VA", "WA", "WV", "WI", "WY", "DC", "PR", "GU", "VI", "AS", "MP",
+];
+
+/// One rejected field of a request body.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FieldError {
+    #[schema(example = "zip")]
+    pub field: String,
+    #[schema(example = "must be a ZIP code like 78701 or 78701-1234")]
+    pub message: String,
+}
+
+/// Runs field rules and collects every failure rather than stopping at the
+/// first. Rules only run for fields in scope: all of them, or with `only`
+/// just the ones a request sent. Some rules only warn: the value is
+/// accepted and the warning returned with the response.
+#[derive(Debug, Default)]
+pub struct Validator {
+    scope: Option<Vec<String>>,
+    errors: Vec<FieldError>,
+    warnings: Vec<FieldError>,
+}
+
+impl Validator {
+    pub fn only<I, S>(fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self { scope: Some(fields.into_iter().map(Into::into).collect()), ..Self::default() }
+    }
+
+    fn in_scope(&self, field: &str) -> bool {
+        self.scope.as_ref().is_none_or(|scope| scope.iter().any(|f| f == field))
+    }
+
+    fn check(&mut self, field: &str, ok: bool, message: impl FnOnce() -> String) {
+        if self.in_scope(field) && !ok {
+            self.errors.push(FieldError { field: field.to_string(), message: message() });
+        }
+    }
+
+    pub fn not_blank(&mut self, field: &str, value: &str) {
+        self.check(field, !value.trim().is_empty(), || "must not be blank".to_string());
+    }
+
+    pub fn email(&mut self, field: &str, value: &str) {
+        self.check(field, is_email(value), || "must be an email address like name@example.com".to_string());
+    }
+
+    pub fn phone(&mut self, field: &str, value: &str) {
+        self.check(field, is_phone(value), || {
+            "must be a phone number of 10 to 15 digits, optionally starting with +".to_string()
+        });
+    }
+
+    pub fn us_state(&mut self, field: &str, value: &str) {
+        self.check(field, US_STATES.contains(&value), || "must be a two-letter US state code like TX".to_string());
+    }
+
+    pub fn zip(&mut self, field: &str, value: &str) {
+        self.check(field, is_zip(value), || "must be a ZIP code like 78701 or 78701-1234".to_string());
+    }
+
+    pub fn receipt_number(&mut self, field: &str, value: &str) {
+        let parsed = value.parse::<ReceiptNumber>();
+        self.check(field, parsed.is_ok(), || parsed.err().map(|e| e.to_string()).unwrap_or_default());
+    }
+
+    pub fn soc_code(&mut self, field: &str, value: &str) {
+        let found = taxonomy().find(value);
+        self.check(field, found.is_ok(), || found.err().map(|e| e.to_string()).unwrap_or_default());
+    }
+
+    /// Warns when `title` does not look like a job in the occupation `code`
+    /// names. Runs when either field is in scope; an invalid code is left
+    /// to `soc_code`.
+    pub fn title_fits_occupation(&mut self, title_field: &str, title: &str, code_field: &str, code: &str) {
+        if !(self.in_scope(title_field) || self.in_scope(code_field)) || title.trim().is_empty() {
+            return;
+        }
+        if let Ok(occupation) = taxonomy().find(code) {
+            if !occupation.fits_title(title) {
+                self.warnings.push(FieldError {
+                    field: title_field.to_string(),
+                    message: format!(
+                        "does not look like a title for SOC {} {}; check {}",
+                        occupation.code, occupation.title, code_field
+                    ),
+                });
+            }
+        }
+    }
+
+    pub fn one_of(&mut self, field: &str, value: &str, allowed: &[&str]) {
+        self.check(field, allowed.contains(&value), || format!("must be one of {}", allowed.join(", ")));
+    }
+
+    pub fn in_past(&mut self, field: &str, value: NaiveDate) {
+        self.check(field, value < Utc::now().date_naive(), || "must be in the past".to_string());
+    }
+
+    pub fn positive(&mut self, field: &str, value: Decimal) {
+        self.check(field, value > Decimal::ZERO, || "must be greater than 0".to_string());
+    }
+
+    /// `later` must come after `earlier`. Runs when either field is in
+    /// scope, and is reported against `later_field`.
+    pub fn after(&mut self, later_field: &str, later: NaiveDate, earlier_field: &str, earlier: NaiveDate) {
+        if (self.in_scope(later_field) || self.in_scope(earlier_field)) && later <= earlier {
+            self.errors.push(FieldError {
+                field: later_field.to_string(),
+                message: format!("must be after {}", earlier_field),
+            });
+        }
+    }
+
+    /// The warnings when every rule passed, otherwise the errors.
+    pub fn finish(self) -> Result<Vec<FieldError>, Vec<FieldError>> {
+        if self.errors.is_empty() {
+            Ok(self.warnings)
+        } else {
+            Err(self.errors)
+        }
+    }
+}
+
+fn is_email(value: &str) -> bool {
+    let Some((local, domain)) = value.split_once('@') else {
+        return false;
+    };
+    let labels: Vec<&str> = domain.split('.').collect();
+    value.len() <= 254
+        && !local.is_empty()
+        && !local.chars().any(|c| c.is_whitespace() || c.is_control())
+        && labels.len() >= 2
+        && labels.iter().all(|label| !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+}
+
+/// Digits with optional separators (space, `-`, `.`, parentheses), e.g.
+/// `(512) 555-0100` or `+91 98765 43210`.
+fn is_phone(value: &str) -> bool {
+    let value = value.trim();
+    let number = value.strip_prefix('+').unwrap_or(value);
+    let digits = number.chars().filter(char::is_ascii_digit).count();
+    number.chars().all(|c| c.is_ascii_digit() || " -.()".contains(c)) && (10..=15).contains(&digits)
+}
+
+fn is_zip(value: &str) -> bool {
+    let digits = |s: &str, n: usize| s.len() == n && s.bytes().all(|b| b.is_ascii_digit());
+    match value.split_once('-') {
+        Some((zip, plus4)) => digits(zip, 5) && digits(plus4, 4),
+        None => digits(value, 5),
+    }
+}
+
+/// Field rules for a request body, or one part of the customer record.
+pub trait Validate {
+    fn check(&self, v: &mut Validator);
+
+    /// Every rule, on every field; warnings when it passes.
+    fn validate(&self) -> Result<Vec<FieldError>, Vec<FieldError>> {
+        let mut v = Validator::default();
+        self.check(&mut v);
+        v.finish()
+    }
+}
+
+impl Validate for PersonDetails {
+    fn check(&self, v: &mut Validator) {
+        v.email("email", &self.email);
+        v.email("login_email", &self.login_email);
+        v.not_blank("first_name", &self.first_name);
+        v.not_blank("last_name", &self.last_name);
+        v.in_past("dob", self.dob);
+        v.one_of("sex", &self.sex, &SEX_VALUES);
+        v.one_of("marital_status", &self.marital_status, &MARITAL_STATUSES);
+        v.phone("phone", &self.phone);
+        v.phone("emergency_contact_phone", &self.emergency_contact_phone);
+        v.after("employment_start_date", self.employment_start_date, "dob", self.dob);
+    }
+}
+
+impl Validate for ResidentialAddress {
+    fn check(&self, v: &mut Validator) {
+        v.not_blank("street_name", &self.street_name);
+        v.not_blank("city", &self.city);
+        v.us_state("state", &self.state);
+        v.zip("zip", &self.zip);
+    }
+}
+
+impl Validate for ClientPlacement {
+    fn check(&self, v: &mut Validator) {
+        v.not_blank("client_name", &self.client_name);
+        v.not_blank("client_street_name", &self.client_street_name);
+        v.not_blank("client_city", &self.client_city);
+        v.us_state("client_state", &self.client_state);
+        v.zip("client_zip", &self.client_zip);
+    }
+}
+
+impl Validate for LcaDetails {
+    fn check(&self, v: &mut Validator) {
+        v.not_blank("lca_title", &self.lca_title);
+        v.positive("lca_salary", self.lca_salary);
+        v.soc_code("lca_code", &self.lca_code);
+        v.title_fits_occupation("lca_title", &self.lca_title, "lca_code", &self.lca_code);
+    }
+}
+
+impl Validate for PetitionDetails {
+    fn check(&self, v: &mut Validator) {
+        v.receipt_number("receipt_number", &self.receipt_number);
+        v.after("h1b_end_date", self.h1b_end_date, "h1b_start_date", self.h1b_start_date);
+    }
+}
+
+impl Validate for CustomerSection {
+    fn check(&self, v: &mut Validator) {
+        match self {
+            CustomerSection::Person(p) => p.check(v),
+            CustomerSection::Address(a) => a.check(v),
+            CustomerSection::Placement(c) => c.check(v),
+            CustomerSection::Lca(l) => l.check(v),
+            CustomerSection::Petition(t) => t.check(v),
+        }
+    }
+}
+
+impl Validate for CustomerUpdate {
+    fn check(&self, v: &mut Validator) {
+        for section in self.clone().into_sections() {
+            section.check(v);
+        }
+    }
+}
+
+impl Validate for CreateCompleteCustomerRequest {
+    fn check(&self, v: &mut Validator) {
+        CustomerUpdate::from(self).check(v);
+        if let Some(h1b_status) = &self.h1b_status {
+            v.one_of("h1b_status", h1b_status, &H1B_STATUSES);
+        }
+    }
+}
+
+impl Validate for CaseTransitionRequest {
+    fn check(&self, v: &mut Validator) {
+        v.not_blank("reason", &self.reason);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lca(title: &str, code: &str) -> LcaDetails {
+        LcaDetails { lca_title: title.to_string(), lca_salary: Decimal::from(100_000), lca_code: code.to_string() }
+    }
+
+    fn date(value: &str) -> NaiveDate {
+        value.parse().unwrap()
+    }
+
+    fn fields(errors: &[FieldError]) -> Vec<&str> {
+        errors.iter().map(|e| e.field.as_str()).collect()
+    }
+
+    #[test]
+    fn accepts_email_addresses() {
+        for value in ["jane@example.com", "jane.o'neil+h1b@mail.example.co.in", "x@a-b.io"] {
+            assert!(is_email(value), "{}", value);
+        }
+        for value in ["", "jane", "@example.com", "jane@", "jane@example", "jane@example..com", "jane doe@example.com", "jane@exa_mple.com"] {
+            assert!(!is_email(value), "{}", value);
+        }
+        assert!(!is_email(&format!("{}@example.com", "a".repeat(250))));
+    }
+
+    #[test]
+    fn accepts_phone_numbers() {
+        for value in ["5125550100", "(512) 555-0100", "+91 98765 43210", "512.555.0100", " +1-555-123-4567 "] {
+            assert!(is_phone(value), "{}", value);
+        }
+        for value in ["", "555-0100", "1234567890123456", "512-555-0100 x12", "++15125550100", "512+5550100"] {
+            assert!(!is_phone(value), "{}", value);
+        }
+    }
+
+    #[test]
+    fn accepts_zip_codes() {
+        for value in ["78701", "78701-1234", "02108"] {
+            assert!(is_zip(value), "{}", value);
+        }
+        for value in ["", "7870", "787011", "78701-123", "78701 1234", "7870a", "78701-"] {
+            assert!(!is_zip(value), "{}", value);
+        }
+    }
+
+    #[test]
+    fn reports_every_broken_rule() {
+        let address = ResidentialAddress {
+            street_name: " ".to_string(),
+            city: "Austin".to_string(),
+            state: "Texas".to_string(),
+            zip: "7870".to_string(),
+        };
+        let errors = address.validate().unwrap_err();
+        assert_eq!(fields(&errors), ["street_name", "state", "zip"]);
+        assert_eq!(errors[1].message, "must be a two-letter US state code like TX");
+    }
+
+    #[test]
+    fn only_checks_fields_in_scope() {
+        let mut v = Validator::only(["zip"]);
+        v.not_blank("street_name", "");
+        v.us_state("state", "Texas");
+        v.zip("zip", "7870");
+        assert_eq!(fields(&v.finish().unwrap_err()), ["zip"]);
+
+        let mut v = Validator::only(Vec::<String>::new());
+        v.zip("zip", "7870");
+        assert!(v.finish().unwrap().is_empty());
+    }
+
+    #[test]
+    fn orders_dates_when_either_is_in_scope() {
+        let petition = |start: &str, end: &str| PetitionDetails {
+            receipt_number: "EAC2412345678".to_string(),
+            h1b_start_date: date(start),
+            h1b_end_date: date(end),
+        };
+        assert!(petition("2024-10-01", "2027-09-30").validate().is_ok());
+        for end in ["2024-10-01", "2024-09-30"] {
+            let errors = petition("2024-10-01", end).validate().unwrap_err();
+            assert_eq!(fields(&errors), ["h1b_end_date"]);
+            assert_eq!(errors[0].message, "must be after h1b_start_date");
+        }
+
+        // Sending only the start date still checks it against the stored end date
+        let mut v = Validator::only(["h1b_start_date"]);
+        petition("2028-01-01", "2027-09-30").check(&mut v);
+        assert_eq!(fields(&v.finish().unwrap_err()), ["h1b_end_date"]);
+
+        let mut v = Validator::only(["receipt_number"]);
+        petition("2028-01-01", "2027-09-30").check(&mut v);
+        assert!(v.finish().is_ok());
+    }
+
+    #[test]
+    fn warns_when_lca_title_does_not_fit_lca_code() {
+        assert!(lca("Senior Software Engineer", "15-1252").validate().unwrap().is_empty());
+
+        let warnings = lca("Registered Nurse", "15-1252").validate().unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].field, "lca_title");
+        assert!(warnings[0].message.contains("SOC 15-1252 Software Developers"));
+    }
+
+    #[test]
+    fn invalid_lca_code_is_an_error_without_a_title_warning() {
+        let errors = lca("Registered Nurse", "15-1132").validate().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "lca_code");
+    }
+
+    #[test]
+    fn lca_codes_of_every_major_group_are_checked() {
+        assert!(lca("Heavy Truck Driver", "53-3032").validate().unwrap().is_empty());
+        let errors = lca("Heavy Truck Driver", "53-3039").validate().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "lca_code");
+        assert!(errors[0].message.contains("not a 2018 SOC detailed occupation"));
+    }
+}
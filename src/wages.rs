@@ -0,0 +1,437 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::models::Customer;
+
+/// OFLC Online Wage Library export of All Industries wages: hourly Level I
+/// to IV wages per `Area` and `SocCode`.
+pub const WAGE_FILE: &str = "ALC_Export.csv";
+/// OFLC Online Wage Library export mapping counties (towns in New England)
+/// to wage areas.
+pub const GEOGRAPHY_FILE: &str = "Geography.csv";
+/// `zip`, `state`, `county`: the county (or New England town) of each ZIP
+/// code, named as in `Geography.csv`.
+pub const ZIP_FILE: &str = "zip_county.csv";
+
+/// Hours in the work year OFLC uses to annualize hourly wages.
+const HOURS_PER_YEAR: u32 = 2080;
+
+/// Suffixes `Geography.csv` and ZIP crosswalks disagree on, dropped before
+/// county names are compared.
+const COUNTY_SUFFIXES: [&str; 6] = [" city and borough", " census area", " municipality", " borough", " parish", " county"];
+
+#[derive(Debug)]
+pub enum WageDataError {
+    Read(PathBuf, csv::Error),
+    MissingColumn(PathBuf, &'static str),
+    InvalidWage { path: PathBuf, line: u64, value: String },
+}
+
+impl fmt::Display for WageDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WageDataError::Read(path, e) => write!(f, "{}: {}", path.display(), e),
+            WageDataError::MissingColumn(path, column) => write!(f, "{}: no {} column", path.display(), column),
+            WageDataError::InvalidWage { path, line, value } => {
+                write!(f, "{}:{}: {:?} is not an hourly wage", path.display(), line, value)
+            },
+        }
+    }
+}
+
+impl std::error::Error for WageDataError {}
+
+/// Rows of one CSV file, read by column name. Values are trimmed and read
+/// lossily, as OFLC files are not always UTF-8.
+struct Table {
+    path: PathBuf,
+    reader: csv::Reader<std::fs::File>,
+    headers: Vec<String>,
+}
+
+impl Table {
+    fn open(dir: &Path, name: &str) -> Result<Self, WageDataError> {
+        let path = dir.join(name);
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_path(&path)
+            .map_err(|e| WageDataError::Read(path.clone(), e))?;
+        let headers = reader
+            .byte_headers()
+            .map_err(|e| WageDataError::Read(path.clone(), e))?
+            .iter()
+            .map(|h| String::from_utf8_lossy(h).trim().trim_start_matches('\u{feff}').to_ascii_lowercase())
+            .collect();
+        Ok(Self { path, reader, headers })
+    }
+
+    fn column(&self, name: &'static str) -> Result<usize, WageDataError> {
+        self.headers
+            .iter()
+            .position(|h| h == &name.to_ascii_lowercase())
+            .ok_or_else(|| WageDataError::MissingColumn(self.path.clone(), name))
+    }
+
+    /// Calls `row` with the named columns of every record, and its line.
+    fn each<const N: usize>(
+        mut self,
+        columns: [&'static str; N],
+        mut row: impl FnMut([String; N], u64) -> Result<(), WageDataError>,
+    ) -> Result<(), WageDataError> {
+        let mut positions = [0; N];
+        for (position, name) in positions.iter_mut().zip(columns) {
+            *position = self.column(name)?;
+        }
+        for record in self.reader.byte_records() {
+            let record = record.map_err(|e| WageDataError::Read(self.path.clone(), e))?;
+            let line = record.position().map_or(0, |p| p.line());
+            let values = positions.map(|i| String::from_utf8_lossy(record.get(i).unwrap_or_default()).trim().to_string());
+            row(values, line)?;
+        }
+        Ok(())
+    }
+}
+
+fn county_key(state: &str, county: &str) -> (String, String) {
+    let mut county = county.to_lowercase().replace(['.', '\''], "");
+    if let Some(suffix) = COUNTY_SUFFIXES.iter().find(|s| county.ends_with(*s)) {
+        county.truncate(county.len() - suffix.len());
+    }
+    (state.to_ascii_uppercase(), county.trim().to_string())
+}
+
+/// The prevailing wages of one occupation in one area, hourly, by level.
+#[derive(Debug, Clone, Copy)]
+struct HourlyWages([Option<Decimal>; 4]);
+
+/// Prevailing wage tables loaded from a directory holding `WAGE_FILE`,
+/// `GEOGRAPHY_FILE` and `ZIP_FILE`.
+#[derive(Debug)]
+pub struct PrevailingWages {
+    wages: HashMap<(String, String), HourlyWages>,
+    area_names: HashMap<String, String>,
+    county_areas: HashMap<(String, String), String>,
+    zip_counties: HashMap<String, (String, String)>,
+}
+
+pub type SharedWages = Option<Arc<PrevailingWages>>;
+
+impl PrevailingWages {
+    pub fn load(dir: &Path) -> Result<Self, WageDataError> {
+        let mut wages = HashMap::new();
+        Table::open(dir, WAGE_FILE)?.each(
+            ["Area", "SocCode", "Level1", "Level2", "Level3", "Level4"],
+            |[area, soc, l1, l2, l3, l4], line| {
+                let mut levels = [None; 4];
+                for (level, value) in levels.iter_mut().zip([l1, l2, l3, l4]) {
+                    if !value.is_empty() {
+                        *level = Some(value.parse::<Decimal>().map_err(|_| WageDataError::InvalidWage {
+                            path: dir.join(WAGE_FILE),
+                            line,
+                            value,
+                        })?);
+                    }
+                }
+                wages.insert((area, soc), HourlyWages(levels));
+                Ok(())
+            },
+        )?;
+
+        let mut area_names = HashMap::new();
+        let mut county_areas = HashMap::new();
+        Table::open(dir, GEOGRAPHY_FILE)?.each(
+            ["Area", "AreaName", "StateAb", "CountyTownName"],
+            |[area, name, state, county], _| {
+                county_areas.insert(county_key(&state, &county), area.clone());
+                area_names.insert(area, name);
+                Ok(())
+            },
+        )?;
+
+        let mut zip_counties = HashMap::new();
+        Table::open(dir, ZIP_FILE)?.each(["zip", "state", "county"], |[zip, state, county], _| {
+            // Crosswalks list a ZIP once per county it spans; the first wins
+            zip_counties.entry(format!("{:0>5}", zip)).or_insert((state, county));
+            Ok(())
+        })?;
+
+        Ok(Self { wages, area_names, county_areas, zip_counties })
+    }
+
+    pub fn wage_count(&self) -> usize {
+        self.wages.len()
+    }
+
+    pub fn area_count(&self) -> usize {
+        self.area_names.len()
+    }
+
+    /// The wage area of a worksite ZIP code, through its county.
+    pub fn area_for_zip(&self, zip: &str) -> Option<WageArea> {
+        let (state, county) = self.zip_counties.get(zip.get(..5)?)?;
+        let code = self.county_areas.get(&county_key(state, county))?;
+        Some(WageArea {
+            code: code.clone(),
+            name: self.area_names.get(code).cloned().unwrap_or_default(),
+            county: county.clone(),
+            state: state.clone(),
+        })
+    }
+
+    /// Which prevailing wage level the customer's `lca_salary`, taken as a
+    /// yearly salary, meets at the `client_zip` worksite.
+    pub fn check(&self, customer: &Customer) -> WageCompliance {
+        let soc_code = customer.lca_code.split('.').next().unwrap_or_default().to_string();
+        let mut result = WageCompliance {
+            status: ComplianceStatus::Unknown,
+            wage_level: None,
+            soc_code,
+            client_zip: customer.client_zip.clone(),
+            area: None,
+            prevailing_wages: None,
+            lca_salary: customer.lca_salary,
+            shortfall: None,
+            reason: None,
+        };
+        let Some(area) = self.area_for_zip(&customer.client_zip) else {
+            result.reason = Some(format!("client_zip {} is not mapped to a wage area", customer.client_zip));
+            return result;
+        };
+        let Some(hourly) = self.wages.get(&(area.code.clone(), result.soc_code.clone())) else {
+            result.reason = Some(format!("no prevailing wage for SOC {} in {}", result.soc_code, area.name));
+            result.area = Some(area);
+            return result;
+        };
+
+        let annual = hourly.0.map(|wage| wage.map(|w| (w * Decimal::from(HOURS_PER_YEAR)).round_dp(2)));
+        result.wage_level = (1..=4u8).rev().find(|level| annual[*level as usize - 1].is_some_and(|w| customer.lca_salary >= w));
+        match (result.wage_level, annual[0]) {
+            (Some(_), _) => result.status = ComplianceStatus::Compliant,
+            (None, Some(level_1)) => {
+                result.status = ComplianceStatus::BelowLevel1;
+                result.shortfall = Some(level_1 - customer.lca_salary);
+            },
+            (None, None) => result.reason = Some(format!("no Level I wage for SOC {} in {}", result.soc_code, area.name)),
+        }
+        result.area = Some(area);
+        result.prevailing_wages = Some(LevelWages {
+            level_1: annual[0],
+            level_2: annual[1],
+            level_3: annual[2],
+            level_4: annual[3],
+        });
+        result
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ComplianceStatus {
+    /// `lca_salary` meets at least the Level I wage.
+    Compliant,
+    #[serde(rename = "below_level_1")]
+    BelowLevel1,
+    /// No wage to compare against; `reason` says why.
+    Unknown,
+}
+
+/// An OFLC wage area (a metropolitan or nonmetropolitan area).
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WageArea {
+    #[schema(example = "12420")]
+    pub code: String,
+    #[schema(example = "Austin-Round Rock, TX MSA")]
+    pub name: String,
+    /// County (or New England town) the ZIP code lies in.
+    #[schema(example = "Travis County")]
+    pub county: String,
+    #[schema(example = "TX")]
+    pub state: String,
+}
+
+/// Prevailing wages by level, annualized from OFLC's hourly wages.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct LevelWages {
+    pub level_1: Option<Decimal>,
+    pub level_2: Option<Decimal>,
+    pub level_3: Option<Decimal>,
+    pub level_4: Option<Decimal>,
+}
+
+/// How a customer's `lca_salary` compares with the prevailing wage at the
+/// client worksite.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WageCompliance {
+    pub status: ComplianceStatus,
+    /// Highest level, 1 to 4, the salary meets; `null` below Level I or
+    /// when unknown.
+    #[schema(example = 2)]
+    pub wage_level: Option<u8>,
+    /// `lca_code` without any O*NET suffix.
+    #[schema(example = "15-1252")]
+    pub soc_code: String,
+    pub client_zip: String,
+    pub area: Option<WageArea>,
+    pub prevailing_wages: Option<LevelWages>,
+    pub lca_salary: Decimal,
+    /// Level I wage minus `lca_salary`, when the salary is below it.
+    pub shortfall: Option<Decimal>,
+    /// Why the status is `unknown`.
+    pub reason: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory of fixture files, removed when dropped.
+    struct Fixture(PathBuf);
+
+    impl Fixture {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let dir = std::env::temp_dir().join(format!("wages-{}-{}", std::process::id(), name));
+            std::fs::create_dir_all(&dir).unwrap();
+            for (file, contents) in files {
+                std::fs::write(dir.join(file), contents).unwrap();
+            }
+            Self(dir)
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // Columns out of order, padded, with a BOM and extra columns, as OFLC exports vary
+    const WAGES: &str = "\u{feff}SocCode, Area ,GeoLvl,Level1,Level2,Level3,Level4,Average\n\
+        15-1252,12420,1,40.00,50.00,60.00,70.00,55.00\n\
+        15-1252,35380,1,30.00,,,,\n\
+        17-2051,35380,1,,,,,\n";
+    const GEOGRAPHY: &str = "Area,AreaName,StateAb,CountyTownName\n\
+        12420,\"Austin-Round Rock, TX MSA\",TX,Travis County\n\
+        35380,\"New Orleans-Metairie, LA MSA\",LA,Orleans Parish\n";
+    const ZIPS: &str = "zip,state,county\n\
+        78701,TX,Travis\n\
+        70112,LA,Orleans\n\
+        70112,LA,Jefferson Parish\n\
+        2108,MA,Suffolk County\n";
+
+    fn load(name: &str) -> PrevailingWages {
+        let fixture = Fixture::new(name, &[(WAGE_FILE, WAGES), (GEOGRAPHY_FILE, GEOGRAPHY), (ZIP_FILE, ZIPS)]);
+        PrevailingWages::load(&fixture.0).unwrap()
+    }
+
+    fn customer(client_zip: &str, lca_code: &str, lca_salary: u32) -> Customer {
+        serde_json::from_value(serde_json::json!({
+            "customer_id": "00000000-0000-0000-0000-000000000001",
+            "email": "jane@example.com", "first_name": "Jane", "last_name": "Doe",
+            "dob": "1990-05-01", "sex": "Female", "marital_status": "Single", "phone": "5551234567",
+            "emergency_contact_name": "Bob", "emergency_contact_phone": "5557654321",
+            "employment_start_date": "2024-02-01",
+            "street_name": "1 Main St", "city": "Austin", "state": "TX", "zip": "73301",
+            "client_name": "Acme", "client_street_name": "2 Market St", "client_city": "Austin",
+            "client_state": "TX", "client_zip": client_zip,
+            "lca_title": "Software Developers", "lca_salary": lca_salary.to_string(), "lca_code": lca_code,
+            "receipt_number": "EAC2400000001", "h1b_start_date": "2024-10-01", "h1b_end_date": "2027-09-30",
+            "login_email": "jane@example.com", "h1b_status": "Active", "case_status": "Filed", "version": 1
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn loads_columns_by_header_name() {
+        let wages = load("columns");
+        assert_eq!(wages.wage_count(), 3);
+        assert_eq!(wages.area_count(), 2);
+        let levels = wages.wages[&("12420".to_string(), "15-1252".to_string())].0;
+        assert_eq!(levels, [Some(Decimal::new(40, 0)), Some(Decimal::new(50, 0)), Some(Decimal::new(60, 0)), Some(Decimal::new(70, 0))]);
+    }
+
+    #[test]
+    fn resolves_zip_to_area_through_the_county() {
+        let wages = load("zips");
+        // "Travis" and "Travis County", "Orleans" and "Orleans Parish" name the same county
+        let area = wages.area_for_zip("78701-1234").unwrap();
+        assert_eq!((area.code.as_str(), area.name.as_str(), area.county.as_str()), ("12420", "Austin-Round Rock, TX MSA", "Travis"));
+        // The first county listed for a ZIP wins
+        assert_eq!(wages.area_for_zip("70112").unwrap().code, "35380");
+        assert!(wages.zip_counties.contains_key("02108"));
+        assert!(wages.area_for_zip("02108").is_none());
+        assert!(wages.area_for_zip("787").is_none());
+    }
+
+    #[test]
+    fn normalizes_county_suffixes() {
+        assert_eq!(county_key("tx", "Travis County"), ("TX".to_string(), "travis".to_string()));
+        assert_eq!(county_key("LA", "Orleans Parish"), county_key("LA", "Orleans"));
+        assert_eq!(county_key("AK", "Juneau City and Borough"), county_key("AK", "Juneau"));
+        assert_eq!(county_key("MD", "St. Mary's County"), county_key("MD", "St Marys"));
+    }
+
+    #[test]
+    fn finds_the_highest_level_met_on_2080_hours() {
+        let wages = load("levels");
+        // Annually 83,200, 104,000, 124,800 and 145,600
+        for (salary, level) in [(83_200, 1), (110_000, 2), (124_800, 3), (200_000, 4)] {
+            let result = wages.check(&customer("78701", "15-1252.00", salary));
+            assert_eq!(result.status, ComplianceStatus::Compliant, "{}", salary);
+            assert_eq!(result.wage_level, Some(level), "{}", salary);
+            assert_eq!(result.shortfall, None);
+        }
+        let result = wages.check(&customer("78701", "15-1252", 110_000));
+        assert_eq!(result.soc_code, "15-1252");
+        let annual = result.prevailing_wages.unwrap();
+        assert_eq!(annual.level_1, Some(Decimal::new(83_200, 0)));
+        assert_eq!(annual.level_4, Some(Decimal::new(145_600, 0)));
+    }
+
+    #[test]
+    fn reports_the_shortfall_below_level_1() {
+        let wages = load("shortfall");
+        let result = wages.check(&customer("78701", "15-1252", 80_000));
+        assert_eq!(result.status, ComplianceStatus::BelowLevel1);
+        assert_eq!(result.wage_level, None);
+        assert_eq!(result.shortfall, Some(Decimal::new(3_200, 0)));
+
+        // Levels left blank in the export are skipped
+        let result = wages.check(&customer("70112", "15-1252", 70_000));
+        assert_eq!(result.wage_level, Some(1));
+        assert_eq!(result.prevailing_wages.unwrap().level_2, None);
+    }
+
+    #[test]
+    fn explains_unknown_results() {
+        let wages = load("unknown");
+        let unknown = |zip, code| {
+            let result = wages.check(&customer(zip, code, 100_000));
+            assert_eq!(result.status, ComplianceStatus::Unknown);
+            assert_eq!(result.wage_level, None);
+            result.reason.unwrap()
+        };
+        assert_eq!(unknown("99999", "15-1252"), "client_zip 99999 is not mapped to a wage area");
+        assert_eq!(unknown("78701", "17-2051"), "no prevailing wage for SOC 17-2051 in Austin-Round Rock, TX MSA");
+        assert_eq!(unknown("70112", "17-2051"), "no Level I wage for SOC 17-2051 in New Orleans-Metairie, LA MSA");
+    }
+
+    #[test]
+    fn reports_missing_and_malformed_files() {
+        let missing = Fixture::new("missing", &[(WAGE_FILE, WAGES), (GEOGRAPHY_FILE, GEOGRAPHY)]);
+        assert!(matches!(PrevailingWages::load(&missing.0), Err(WageDataError::Read(path, _)) if path.ends_with(ZIP_FILE)));
+
+        let no_column = Fixture::new("no-column", &[(WAGE_FILE, "Area,SocCode,Level1,Level2,Level3\n12420,15-1252,1,2,3\n")]);
+        assert!(matches!(PrevailingWages::load(&no_column.0), Err(WageDataError::MissingColumn(_, "Level4"))));
+
+        let bad_wage = Fixture::new("bad-wage", &[(WAGE_FILE, "Area,SocCode,Level1,Level2,Level3,Level4\n12420,15-1252,40.00,n/a,60.00,70.00\n")]);
+        let error = PrevailingWages::load(&bad_wage.0).unwrap_err();
+        assert!(matches!(&error, WageDataError::InvalidWage { line: 2, value, .. } if value == "n/a"));
+        assert!(error.to_string().ends_with(":2: \"n/a\" is not an hourly wage"));
+    }
+}